@@ -0,0 +1,465 @@
+//! Command line modes. With no arguments the game starts as normal:
+//!
+//! ```text
+//! wire [--control <address>] [--import <netlist.json>] [--record <path>] [--replay <path>]
+//! wire --headless <circuit> [--ticks N] [--csv <path>] [--log <path>] [--stats] [--optimize] [--compile]
+//! wire --send <address> [request]...
+//! wire --dot <circuit> [--out <path>] [--cluster <name>=<node>,<node>...]...
+//! wire --verilog <circuit> [--out <path>]
+//! wire --verify <recording>
+//! ```
+//!
+//! `--control` starts the game with a control server, see the control
+//! module, and `--import` starts it with a Yosys netlist laid out in front
+//! of the player, see the netlist module. `--headless` runs a circuit
+//! without a window, collecting every sink its Output nodes use through a
+//! channel and printing them as `tick name value` lines, or writing them as
+//! CSV or a log, and with `--stats` printing a profile of the run at the end.
+//! `--optimize` runs the circuit after simplifying it, see the optimize
+//! module, and `--compile` runs it as a flat program, see the compile
+//! module. `--send` is a client for the control server, sending each
+//! request (or each line of stdin if none are given) and printing the
+//! responses. `--dot` writes a circuit as a Graphviz digraph to stdout or
+//! `--out`, with each `--cluster` drawn as a group of its own. `--verilog`
+//! writes a circuit as a Verilog module named after the file, see the
+//! verilog module. `--record` and `--replay` record the game's inputs to a
+//! file and play them back, and `--verify` plays a recording back without
+//! a window and fails unless it ends in the state recorded, see the
+//! recording module
+
+use wire::*;
+use control::{self, ControlAddress};
+use game::GameContext;
+use recording::load_recording;
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+const USAGE: &str = "usage: wire [--control <address>] [--import <netlist.json>] [--record <path>] [--replay <path>]
+       wire --headless <circuit> [--ticks N] [--csv <path>] [--log <path>] [--stats] [--optimize] [--compile]
+       wire --send <address> [request]...
+       wire --dot <circuit> [--out <path>] [--cluster <name>=<node>,<node>...]...
+       wire --verilog <circuit> [--out <path>]
+       wire --verify <recording>";
+
+const DEFAULT_TICKS: u64 = 100;
+
+pub struct GameOptions {
+	pub control: Option<ControlAddress>,
+	pub import: Option<String>,
+	pub record: Option<String>,
+	pub replay: Option<String>,
+}
+
+struct HeadlessOptions {
+	circuit: String,
+	ticks: u64,
+	csv: Option<String>,
+	log: Option<String>,
+	stats: bool,
+	optimize: bool,
+	compile: bool,
+}
+
+enum Mode {
+	Game(GameOptions),
+	Headless(HeadlessOptions),
+	Send { address: ControlAddress, requests: Vec<String> },
+	Dot { circuit: String, out: Option<String>, clusters: Vec<DotCluster> },
+	Verilog { circuit: String, out: Option<String> },
+	Verify { recording: String },
+}
+
+fn parse_cluster(text: &str) -> Result<DotCluster, String> {
+	let mut parts = text.splitn(2, '=');
+	let name = parts.next().unwrap();
+	let nodes = parts.next().ok_or("--cluster needs '<name>=<node>,<node>...'")?;
+
+	let nodes = nodes.split(',')
+		.map(|n| n.trim().parse().map_err(|_| format!("invalid node id '{}'", n)))
+		.collect::<Result<Vec<_>, _>>()?;
+
+	Ok(DotCluster { name: name.to_string(), nodes })
+}
+
+fn parse_args(args: &[String]) -> Result<Mode, String> {
+	let mut args = args.iter();
+	let mut circuit = None;
+	let mut ticks = None;
+	let mut csv = None;
+	let mut log = None;
+	let mut stats = false;
+	let mut optimize = false;
+	let mut compile = false;
+	let mut control = None;
+	let mut import = None;
+	let mut send = None;
+	let mut requests = Vec::new();
+	let mut dot = None;
+	let mut verilog = None;
+	let mut out = None;
+	let mut clusters = Vec::new();
+	let mut record = None;
+	let mut replay = None;
+	let mut verify = None;
+
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--headless" => circuit = Some(args.next().ok_or("--headless needs a circuit")?.clone()),
+			"--ticks" => ticks = Some(args.next().and_then(|t| t.parse().ok()).ok_or("--ticks needs a number")?),
+			"--csv" => csv = Some(args.next().ok_or("--csv needs a path")?.clone()),
+			"--log" => log = Some(args.next().ok_or("--log needs a path")?.clone()),
+			"--stats" => stats = true,
+			"--optimize" => optimize = true,
+			"--compile" => compile = true,
+			"--control" => control = Some(ControlAddress::parse(args.next().ok_or("--control needs an address")?)?),
+			"--import" => import = Some(args.next().ok_or("--import needs a netlist")?.clone()),
+			"--send" => send = Some(ControlAddress::parse(args.next().ok_or("--send needs an address")?)?),
+			"--dot" => dot = Some(args.next().ok_or("--dot needs a circuit")?.clone()),
+			"--verilog" => verilog = Some(args.next().ok_or("--verilog needs a circuit")?.clone()),
+			"--out" => out = Some(args.next().ok_or("--out needs a path")?.clone()),
+			"--record" => record = Some(args.next().ok_or("--record needs a path")?.clone()),
+			"--replay" => replay = Some(args.next().ok_or("--replay needs a recording")?.clone()),
+			"--verify" => verify = Some(args.next().ok_or("--verify needs a recording")?.clone()),
+			"--cluster" => clusters.push(parse_cluster(args.next().ok_or("--cluster needs a list of nodes")?)?),
+			_ if send.is_some() && !arg.starts_with("--") => requests.push(arg.clone()),
+			_ => return Err(format!("unknown argument '{}'", arg)),
+		}
+	}
+
+	let modes = [circuit.is_some(), send.is_some(), dot.is_some(), verilog.is_some(), verify.is_some()].iter()
+		.filter(|&&m| m)
+		.count();
+
+	if modes > 1 {
+		return Err("only one of --headless, --send, --dot, --verilog and --verify can be used".to_string());
+	}
+
+	if modes > 0 && (control.is_some() || import.is_some() || record.is_some() || replay.is_some()) {
+		return Err("--control, --import, --record and --replay can only be used with the game".to_string());
+	}
+
+	// Requests coming in would be mixed in with the recorded ones
+	if control.is_some() && replay.is_some() {
+		return Err("--control can't be used with --replay".to_string());
+	}
+
+	if circuit.is_none() && (ticks.is_some() || csv.is_some() || log.is_some() || stats || optimize || compile) {
+		return Err("--ticks, --csv, --log, --stats, --optimize and --compile need --headless".to_string());
+	}
+
+	if stats && compile {
+		return Err("--stats can't be used with --compile, compiled runs aren't profiled".to_string());
+	}
+
+	if dot.is_none() && !clusters.is_empty() {
+		return Err("--cluster needs --dot".to_string());
+	}
+
+	if dot.is_none() && verilog.is_none() && out.is_some() {
+		return Err("--out needs --dot or --verilog".to_string());
+	}
+
+	if let Some(circuit) = circuit {
+		return Ok(Mode::Headless(HeadlessOptions { circuit, ticks: ticks.unwrap_or(DEFAULT_TICKS), csv, log, stats, optimize, compile }));
+	}
+
+	if let Some(address) = send {
+		return Ok(Mode::Send { address, requests });
+	}
+
+	if let Some(circuit) = dot {
+		return Ok(Mode::Dot { circuit, out, clusters });
+	}
+
+	if let Some(circuit) = verilog {
+		return Ok(Mode::Verilog { circuit, out });
+	}
+
+	match verify {
+		Some(recording) => Ok(Mode::Verify { recording }),
+		None => Ok(Mode::Game(GameOptions { control, import, record, replay })),
+	}
+}
+
+/// Runs any mode that doesn't need a window, returning its exit code, or
+/// returns the options to start the game with
+pub fn run(args: &[String]) -> Result<GameOptions, i32> {
+	match parse_args(args) {
+		Ok(Mode::Game(options)) => Ok(options),
+		Ok(Mode::Headless(options)) => Err(run_headless(&options)),
+		Ok(Mode::Send { address, requests }) => Err(run_client(&address, &requests)),
+		Ok(Mode::Dot { circuit, out, clusters }) => Err(run_dot(&circuit, out.as_ref().map(|s| s.as_str()), &clusters)),
+		Ok(Mode::Verilog { circuit, out }) => Err(run_verilog(&circuit, out.as_ref().map(|s| s.as_str()))),
+		Ok(Mode::Verify { recording }) => Err(run_verify(&recording)),
+
+		Err(e) => {
+			println!("{}\n{}", e, USAGE);
+			Err(2)
+		}
+	}
+}
+
+fn run_dot(circuit: &str, out: Option<&str>, clusters: &[DotCluster]) -> i32 {
+	let ctx = match load_circuit(Path::new(circuit)) {
+		Ok(circuit) => circuit.context,
+		Err(e) => {
+			println!("Failed to load '{}': {}", circuit, e);
+			return 1;
+		}
+	};
+
+	let result = match out {
+		Some(path) => File::create(path).and_then(|mut file| write_dot(&ctx, clusters, &mut file)),
+		None => write_dot(&ctx, clusters, &mut io::stdout()),
+	};
+
+	match result {
+		Ok(()) => 0,
+		Err(e) => {
+			println!("Failed to write '{}': {}", out.unwrap_or("stdout"), e);
+			1
+		}
+	}
+}
+
+fn run_verilog(circuit: &str, out: Option<&str>) -> i32 {
+	let ctx = match load_circuit(Path::new(circuit)) {
+		Ok(circuit) => circuit.context,
+		Err(e) => {
+			println!("Failed to load '{}': {}", circuit, e);
+			return 1;
+		}
+	};
+
+	let module = Path::new(circuit).file_stem()
+		.map_or("circuit".to_string(), |s| s.to_string_lossy().into_owned());
+
+	let verilog = match to_verilog(&ctx, &module) {
+		Ok(verilog) => verilog,
+		Err(e) => {
+			println!("Can't export '{}': {}", circuit, e);
+			return 1;
+		}
+	};
+
+	let result = match out {
+		Some(path) => File::create(path).and_then(|mut file| file.write_all(verilog.as_bytes())),
+		None => io::stdout().write_all(verilog.as_bytes()),
+	};
+
+	match result {
+		Ok(()) => 0,
+		Err(e) => {
+			println!("Failed to write '{}': {}", out.unwrap_or("stdout"), e);
+			1
+		}
+	}
+}
+
+/// Plays a recording back without a window and compares the state it ends
+/// in with the one recorded
+fn run_verify(path: &str) -> i32 {
+	let mut recording = match load_recording(Path::new(path)) {
+		Ok(recording) => recording,
+		Err(e) => {
+			println!("Failed to load '{}': {}", path, e);
+			return 1;
+		}
+	};
+
+	let expected = match recording.final_state.take() {
+		Some(state) => state,
+		None => {
+			println!("'{}' has no final state, the game didn't exit normally while recording", path);
+			return 1;
+		}
+	};
+
+	let mut game = GameContext::headless();
+
+	while !recording.is_finished() {
+		if let Err(e) = recording.play_frame(&mut game) {
+			println!("{}", e);
+			return 1;
+		}
+	}
+
+	let actual = game.describe_state();
+
+	for i in 0..expected.len().max(actual.len()) {
+		let (expected, actual) = (expected.get(i), actual.get(i));
+		if expected == actual { continue }
+
+		println!("final state differs at line {}:", i + 1);
+		println!("  expected: {}", expected.map_or("(nothing)", |l| l.as_str()));
+		println!("  actual:   {}", actual.map_or("(nothing)", |l| l.as_str()));
+		return 1;
+	}
+
+	let frames = recording.entries.last().map_or(0, |e| e.frame + 1);
+	println!("{} frames played back, final state matches at tick {}", frames, game.get_ticks());
+	0
+}
+
+fn run_client(address: &ControlAddress, requests: &[String]) -> i32 {
+	let stream = match control::connect(address) {
+		Ok(stream) => stream,
+		Err(e) => {
+			println!("Failed to connect to '{}': {}", address, e);
+			return 1;
+		}
+	};
+
+	let mut reader = BufReader::new(stream);
+
+	let mut send = |request: &str| -> io::Result<()> {
+		writeln!(reader.get_mut(), "{}", request)?;
+
+		let mut response = String::new();
+		if reader.read_line(&mut response)? == 0 {
+			return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "server closed the connection"));
+		}
+
+		print!("{}", response);
+		Ok(())
+	};
+
+	let result = if requests.is_empty() {
+		let stdin = io::stdin();
+		let lines = stdin.lock().lines();
+
+		lines.filter(|l| l.as_ref().map_or(true, |l| !l.trim().is_empty()))
+			.map(|line| line.and_then(|line| send(&line)))
+			.collect::<io::Result<()>>()
+	} else {
+		requests.iter().map(|r| send(r)).collect::<io::Result<()>>()
+	};
+
+	match result {
+		Ok(()) => 0,
+		Err(e) => {
+			println!("{}", e);
+			1
+		}
+	}
+}
+
+/// Names of the sinks used by Output nodes in the circuit
+fn sink_names(ctx: &WireContext) -> HashSet<String> {
+	let mut names = HashSet::new();
+	names.insert(STDOUT_SINK.to_string());
+
+	for &(_, ref node) in ctx.nodes.iter() {
+		for param in node.get_params() {
+			if let (ParamType::String, "sink") = (param.ty, param.name) {
+				names.insert(param.format_value());
+			}
+		}
+	}
+
+	names
+}
+
+/// What a headless run steps, see the compile module
+enum Simulation {
+	Stepped(WireContext),
+	Compiled(CompiledCircuit),
+}
+
+impl Simulation {
+	fn step(&mut self) {
+		match *self {
+			Simulation::Stepped(ref mut ctx) => ctx.step(),
+			Simulation::Compiled(ref mut compiled) => compiled.step(),
+		}
+	}
+}
+
+fn run_headless(options: &HeadlessOptions) -> i32 {
+	let mut ctx = match load_circuit(Path::new(&options.circuit)) {
+		Ok(circuit) => circuit.context,
+		Err(e) => {
+			println!("Failed to load '{}': {}", options.circuit, e);
+			return 1;
+		}
+	};
+
+	let mut optimized = None;
+	if options.optimize {
+		let result = optimize(ctx);
+		optimized = Some(result.to_string());
+		ctx = result.context;
+	}
+
+	let (sink, receiver) = channel_sink();
+	for name in sink_names(&ctx) {
+		ctx.add_sink(&name, sink.clone());
+	}
+
+	drop(sink);
+	ctx.enable_stats(options.stats);
+
+	// Values are printed unless they're written somewhere
+	let mut writers: Vec<Box<OutputSink>> = Vec::new();
+
+	if let Some(ref path) = options.csv {
+		match CsvSink::create(Path::new(path)) {
+			Ok(csv) => writers.push(box csv),
+			Err(e) => {
+				println!("Failed to create '{}': {}", path, e);
+				return 1;
+			}
+		}
+	}
+
+	if let Some(ref path) = options.log {
+		match LogSink::create(Path::new(path)) {
+			Ok(log) => writers.push(box log),
+			Err(e) => {
+				println!("Failed to create '{}': {}", path, e);
+				return 1;
+			}
+		}
+	}
+
+	let mut simulation = if options.compile {
+		Simulation::Compiled(compile(ctx))
+	} else {
+		Simulation::Stepped(ctx)
+	};
+
+	for _ in 0..options.ticks {
+		simulation.step();
+
+		for event in receiver.try_iter() {
+			if writers.is_empty() {
+				println!("{} {} {}", event.tick, event.name, csv_value(event.value));
+			}
+
+			for writer in writers.iter_mut() {
+				if let Err(e) = writer.on_output(&event) {
+					println!("Failed to write output: {}", e);
+					return 1;
+				}
+			}
+		}
+	}
+
+	let ctx = match simulation {
+		Simulation::Stepped(ctx) => ctx,
+		Simulation::Compiled(compiled) => compiled.into_context(),
+	};
+
+	if let Some(optimized) = optimized {
+		println!("Optimized to {}", optimized);
+	}
+
+	if let Some(stats) = ctx.get_stats() {
+		println!("{}", stats);
+	}
+
+	0
+}
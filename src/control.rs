@@ -0,0 +1,545 @@
+//! Remote control of a running simulation over JSON lines. The server is
+//! off unless the game is started with `--control <address>`, where the
+//! address is `unix:<path>` or `tcp:<port>`. TCP only ever binds to
+//! 127.0.0.1.
+//!
+//! Each request is one JSON object per line, and gets exactly one response
+//! line back, in order:
+//!
+//! ```text
+//! -> {"id": 1, "cmd": "list_nodes"}
+//! <- {"id": 1, "ok": true, "result": [...]}
+//! <- {"id": 1, "ok": false, "error": "..."}
+//! ```
+//!
+//! `id` is optional and echoed back as is. Nodes are referred to by id and
+//! ports as `[node, port]`. Wire values are `null`, numbers, booleans, or
+//! the strings `"error"` and `"z"` (high impedance).
+//!
+//! | cmd                | fields                                      | result |
+//! |--------------------|---------------------------------------------|--------|
+//! | `list_nodes`       |                                             | `[{"id", "type", "label", "inputs", "outputs", "params": {name: value}}]` |
+//! | `list_connections` |                                             | `[{"from": [n, p], "to": [n, p], "value"}]` |
+//! | `get_output`       | `node`, `port`                              | value |
+//! | `frob`             | `node`                                      | `null` |
+//! | `add_node`         | `type`, `params`?: `{name: value}`, `position`?: `[x, y, z]` | `{"id"}` |
+//! | `remove_node`      | `node`                                      | `null` |
+//! | `connect`          | `from`: `[n, p]`, `to`: `[n, p]`            | `null` |
+//! | `set_param`        | `node`, `name`, `value`                     | `null` |
+//! | `list_buses`       |                                             | `[{"to": [n, p], "resolution", "conflict"}]` |
+//! | `set_bus`          | `to`: `[n, p]`, `resolution`: `"conflict"`, `"and"`, `"or"` or `null` | `null` |
+//! | `list_breakpoints` |                                             | `[{"id", "port": [n, p], "condition"}]` |
+//! | `add_breakpoint`   | `port`: `[n, p]`, `condition`: `"changes"`, `"rising"`, `"equals <value>"` or `"exceeds <int>"` | `{"id"}` |
+//! | `remove_breakpoint`| `id`                                        | `null` |
+//! | `get_break`        |                                             | `null` or `{"breakpoint", "port": [n, p], "value", "tick"}` |
+//! | `continue`         |                                             | `null` |
+//! | `list_watches`     |                                             | `[{"id", "expr", "value"}]` |
+//! | `add_watch`        | `expr`, e.g. `"3:0 - 4:0 >= 10"`            | `{"id"}` |
+//! | `remove_watch`     | `id`                                        | `null` |
+//! | `pause`, `resume`  |                                             | `null` |
+//! | `step`             | `count`? (default 1, at most 100000)        | `null` |
+//!
+//! Param values are given as their JSON type, except enums which are given
+//! by name.
+
+use wire::*;
+use json::Json;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, Ipv4Addr};
+use std::path::PathBuf;
+
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+/// Most steps a single `step` request can take, so one request can't hang
+/// the game
+pub const MAX_STEP_COUNT: i64 = 100_000;
+
+/// Longest request line taken from a client. A client sending more
+/// without a newline is answered with an error and dropped
+const MAX_LINE_LENGTH: usize = 1 << 16;
+
+/// Most response bytes kept for a client that isn't reading them before
+/// it's dropped
+const MAX_PENDING_OUTPUT: usize = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlAddress {
+	#[cfg(unix)]
+	Unix(PathBuf),
+	Tcp(u16),
+}
+
+impl ControlAddress {
+	pub fn parse(text: &str) -> Result<Self, String> {
+		let mut parts = text.splitn(2, ':');
+
+		match (parts.next(), parts.next()) {
+			#[cfg(unix)]
+			(Some("unix"), Some(path)) if !path.is_empty() => Ok(ControlAddress::Unix(PathBuf::from(path))),
+
+			(Some("tcp"), Some(port)) => port.parse().map(ControlAddress::Tcp)
+				.map_err(|_| format!("invalid port '{}'", port)),
+
+			_ => Err(format!("invalid address '{}', expected unix:<path> or tcp:<port>", text)),
+		}
+	}
+}
+
+impl fmt::Display for ControlAddress {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			#[cfg(unix)]
+			ControlAddress::Unix(ref path) => write!(f, "unix:{}", path.display()),
+			ControlAddress::Tcp(port) => write!(f, "tcp:{}", port),
+		}
+	}
+}
+
+pub trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+/// Opens a blocking connection to a server, for clients
+pub fn connect(address: &ControlAddress) -> io::Result<Box<Stream>> {
+	match *address {
+		#[cfg(unix)]
+		ControlAddress::Unix(ref path) => Ok(box UnixStream::connect(path)?),
+		ControlAddress::Tcp(port) => Ok(box TcpStream::connect((Ipv4Addr::new(127, 0, 0, 1), port))?),
+	}
+}
+
+enum Listener {
+	/// The listener, its path, and the inode of the socket bound there
+	#[cfg(unix)]
+	Unix(UnixListener, PathBuf, Option<u64>),
+	Tcp(TcpListener),
+}
+
+struct Client {
+	stream: Box<Stream>,
+	buffer: Vec<u8>,
+	/// Responses not yet written, for when the client reads slower than
+	/// it's answered
+	pending: Vec<u8>,
+}
+
+impl Client {
+	/// Writes as much pending output as the stream takes without blocking.
+	/// Returns false if the client has gone
+	fn flush(&mut self) -> bool {
+		while !self.pending.is_empty() {
+			match self.stream.write(&self.pending) {
+				Ok(0) => return false,
+				Ok(n) => { self.pending.drain(..n); }
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+				Err(_) => return false,
+			}
+		}
+
+		self.pending.len() <= MAX_PENDING_OUTPUT
+	}
+}
+
+pub struct ControlServer {
+	listener: Listener,
+	clients: Vec<Client>,
+}
+
+impl ControlServer {
+	pub fn bind(address: &ControlAddress) -> io::Result<Self> {
+		let listener = match *address {
+			#[cfg(unix)]
+			ControlAddress::Unix(ref path) => {
+				remove_stale_socket(path)?;
+
+				let listener = UnixListener::bind(path)?;
+				listener.set_nonblocking(true)?;
+				Listener::Unix(listener, path.clone(), socket_inode(path))
+			}
+
+			ControlAddress::Tcp(port) => {
+				let listener = TcpListener::bind((Ipv4Addr::new(127, 0, 0, 1), port))?;
+				listener.set_nonblocking(true)?;
+				Listener::Tcp(listener)
+			}
+		};
+
+		Ok(ControlServer { listener, clients: Vec::new() })
+	}
+
+	fn accept_clients(&mut self) {
+		loop {
+			let stream: Box<Stream> = match self.listener {
+				#[cfg(unix)]
+				Listener::Unix(ref listener, _, _) => match listener.accept() {
+					Ok((stream, _)) if stream.set_nonblocking(true).is_ok() => box stream,
+					Ok(_) => continue,
+					Err(_) => break,
+				},
+
+				Listener::Tcp(ref listener) => match listener.accept() {
+					Ok((stream, _)) if stream.set_nonblocking(true).is_ok() => box stream,
+					Ok(_) => continue,
+					Err(_) => break,
+				},
+			};
+
+			self.clients.push(Client { stream, buffer: Vec::new(), pending: Vec::new() });
+		}
+	}
+
+	/// Accepts new clients and answers every complete request received so
+	/// far using `handler`. Clients that hang up, error, send too long a
+	/// line, or leave too many responses unread are dropped
+	pub fn poll<F>(&mut self, mut handler: F) where F: FnMut(&Json) -> Result<Json, String> {
+		self.accept_clients();
+
+		let mut chunk = [0u8; 1024];
+		let mut i = 0;
+
+		while i < self.clients.len() {
+			let open = {
+				let client = &mut self.clients[i];
+
+				let open = loop {
+					match client.stream.read(&mut chunk) {
+						Ok(0) => break false,
+						Ok(n) => client.buffer.extend_from_slice(&chunk[..n]),
+						Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break true,
+						Err(_) => break false,
+					}
+
+					while let Some(end) = client.buffer.iter().position(|&b| b == b'\n') {
+						let line: Vec<u8> = client.buffer.drain(..end + 1).collect();
+						let line = String::from_utf8_lossy(&line);
+						if line.trim().is_empty() { continue }
+
+						let response = respond(&line, &mut handler);
+						client.pending.extend_from_slice(format!("{}\n", response).as_bytes());
+					}
+
+					if client.buffer.len() > MAX_LINE_LENGTH {
+						let response = error_response(Json::Null, format!("request longer than {} bytes", MAX_LINE_LENGTH));
+						client.pending.extend_from_slice(format!("{}\n", response).as_bytes());
+						break false;
+					}
+				};
+
+				client.flush() && open
+			};
+
+			if open {
+				i += 1;
+			} else {
+				self.clients.remove(i);
+			}
+		}
+	}
+}
+
+impl Drop for ControlServer {
+	fn drop(&mut self) {
+		#[cfg(unix)]
+		{
+			// Only remove the socket if another server hasn't bound the path
+			if let Listener::Unix(_, ref path, inode) = self.listener {
+				if inode.is_some() && socket_inode(path) == inode {
+					let _ = fs::remove_file(path);
+				}
+			}
+		}
+	}
+}
+
+fn respond<F>(line: &str, handler: &mut F) -> Json where F: FnMut(&Json) -> Result<Json, String> {
+	let request = match Json::parse(line) {
+		Ok(request) => request,
+		Err(e) => return error_response(Json::Null, format!("invalid JSON: {}", e)),
+	};
+
+	let id = request.get("id").cloned().unwrap_or(Json::Null);
+
+	match handler(&request) {
+		Ok(result) => Json::object(vec![("id", id), ("ok", Json::Bool(true)), ("result", result)]),
+		Err(e) => error_response(id, e),
+	}
+}
+
+fn error_response(id: Json, error: String) -> Json {
+	Json::object(vec![("id", id), ("ok", Json::Bool(false)), ("error", Json::String(error))])
+}
+
+
+pub fn value_to_json(value: WireValue) -> Json {
+	match value {
+		WireValue::Null => Json::Null,
+		WireValue::Int(v) => Json::from(v),
+		WireValue::Bool(v) => Json::Bool(v),
+		WireValue::Error => Json::from("error"),
+		WireValue::HighZ => Json::from("z"),
+	}
+}
+
+fn param_value_to_json(param: &Param) -> Json {
+	match param.value {
+		ParamValue::Int(v) => Json::from(v),
+		ParamValue::Bool(v) => Json::Bool(v),
+		ParamValue::Enum(_) => Json::String(param.format_value()),
+		ParamValue::String(ref v) => Json::String(v.clone()),
+	}
+}
+
+fn json_to_param_value(param: &Param, value: &Json) -> Result<ParamValue, String> {
+	let value = match (param.ty, value) {
+		(ParamType::Int { .. }, &Json::Number(_)) => value.as_i64()
+			.and_then(|v| if v >= i32::min_value() as i64 && v <= i32::max_value() as i64 { Some(v as i32) } else { None })
+			.map(ParamValue::Int),
+		(ParamType::Bool, &Json::Bool(v)) => Some(ParamValue::Bool(v)),
+		(ParamType::Enum(_), &Json::String(ref v)) => param.parse(v).ok(),
+		(ParamType::String, &Json::String(ref v)) => Some(ParamValue::String(v.clone())),
+		_ => None,
+	};
+
+	value.ok_or(format!("invalid value for '{}'", param.name))
+}
+
+fn field<'a>(request: &'a Json, name: &str) -> Result<&'a Json, String> {
+	request.get(name).ok_or(format!("missing '{}'", name))
+}
+
+fn as_u32(value: &Json) -> Option<u32> {
+	value.as_i64().and_then(|v| if v >= 0 && v <= u32::max_value() as i64 { Some(v as u32) } else { None })
+}
+
+fn u32_field(request: &Json, name: &str) -> Result<u32, String> {
+	as_u32(field(request, name)?).ok_or(format!("'{}' must be a non-negative integer", name))
+}
+
+/// Reads a `[node, port]` pair
+fn port_field(request: &Json, name: &str) -> Result<(u32, u32), String> {
+	let pair = field(request, name)?.as_array()
+		.and_then(|a| if a.len() == 2 { Some((as_u32(&a[0]), as_u32(&a[1]))) } else { None });
+
+	match pair {
+		Some((Some(node), Some(port))) => Ok((node, port)),
+		_ => Err(format!("'{}' must be [node, port]", name)),
+	}
+}
+
+fn set_param_json(ctx: &mut WireContext, node_id: u32, name: &str, value: &Json) -> Result<(), String> {
+	let param = ctx.get_node(node_id)
+		.ok_or(format!("no node with id {}", node_id))?
+		.get_params().into_iter()
+		.find(|p| p.name == name)
+		.ok_or(format!("no parameter named '{}'", name))?;
+
+	let value = json_to_param_value(&param, value)?;
+	ctx.set_param(node_id, name, value).map_err(|e| e.to_string())
+}
+
+fn describe_node(id: u32, node: &WireNode) -> Json {
+	let params = node.get_params().iter()
+		.map(|p| (p.name.to_string(), param_value_to_json(p)))
+		.collect();
+
+	Json::object(vec![
+		("id", Json::from(id)),
+		("type", Json::from(node.get_type_name())),
+		("label", Json::from(node.get_label())),
+		("inputs", Json::from(node.get_num_inputs())),
+		("outputs", Json::from(node.get_num_outputs())),
+		("params", Json::Object(params)),
+	])
+}
+
+/// Creates a node from an `add_node` request, returning its id
+pub fn add_node_json(ctx: &mut WireContext, request: &Json) -> Result<u32, String> {
+	let type_name = field(request, "type")?.as_str().ok_or("'type' must be a string")?;
+	let node = create_node(type_name).ok_or(format!("unknown node type '{}'", type_name))?;
+
+	let id = ctx.next_id;
+	ctx.insert_node(id, node);
+
+	if let Some(params) = request.get("params") {
+		let result = params.as_object().ok_or("'params' must be an object".to_string())
+			.and_then(|params| params.iter()
+				.map(|&(ref name, ref value)| set_param_json(ctx, id, name, value))
+				.collect::<Result<Vec<_>, _>>());
+
+		if let Err(e) = result {
+			ctx.remove_node(id);
+			return Err(e);
+		}
+	}
+
+	Ok(id)
+}
+
+/// Handles the requests that only need the WireContext
+pub fn handle_request(ctx: &mut WireContext, request: &Json) -> Result<Json, String> {
+	let cmd = field(request, "cmd")?.as_str().ok_or("'cmd' must be a string")?;
+
+	match cmd {
+		"list_nodes" => Ok(Json::Array(ctx.nodes.iter()
+			.map(|&(id, ref node)| describe_node(id, &**node))
+			.collect())),
+
+		"list_connections" => Ok(Json::Array(ctx.connections.iter()
+			.map(|c| Json::object(vec![
+				("from", Json::Array(vec![Json::from(c.input_node), Json::from(c.input_port)])),
+				("to", Json::Array(vec![Json::from(c.output_node), Json::from(c.output_port)])),
+				("value", value_to_json(c.value())),
+			]))
+			.collect())),
+
+		"get_output" => {
+			let (node_id, port) = (u32_field(request, "node")?, u32_field(request, "port")?);
+			let node = ctx.get_node(node_id).ok_or(format!("no node with id {}", node_id))?;
+
+			if port >= node.get_num_outputs() {
+				return Err(format!("node {} has no output #{}", node_id, port));
+			}
+
+			Ok(value_to_json(node.get_output(port)))
+		}
+
+		"frob" => {
+			let node_id = u32_field(request, "node")?;
+			ctx.get_node_mut(node_id).ok_or(format!("no node with id {}", node_id))?.on_frob();
+			Ok(Json::Null)
+		}
+
+		"add_node" => add_node_json(ctx, request).map(|id| Json::object(vec![("id", Json::from(id))])),
+
+		"remove_node" => {
+			let node_id = u32_field(request, "node")?;
+			if ctx.get_node(node_id).is_none() {
+				return Err(format!("no node with id {}", node_id));
+			}
+
+			ctx.remove_node(node_id);
+			Ok(Json::Null)
+		}
+
+		"connect" => {
+			let (from, to) = (port_field(request, "from")?, port_field(request, "to")?);
+
+			ctx.check_connection(from, to)?;
+			ctx.add_connection(from, to);
+			Ok(Json::Null)
+		}
+
+		"set_param" => {
+			let node_id = u32_field(request, "node")?;
+			let name = field(request, "name")?.as_str().ok_or("'name' must be a string")?;
+			set_param_json(ctx, node_id, name, field(request, "value")?)?;
+			Ok(Json::Null)
+		}
+
+		"list_buses" => Ok(Json::Array(ctx.get_buses().iter()
+			.map(|b| Json::object(vec![
+				("to", Json::Array(vec![Json::from(b.to.0), Json::from(b.to.1)])),
+				("resolution", Json::from(b.resolution.name())),
+				("conflict", Json::Bool(b.in_conflict())),
+			]))
+			.collect())),
+
+		"set_bus" => {
+			let to = port_field(request, "to")?;
+
+			let resolution = match *field(request, "resolution")? {
+				Json::Null => None,
+				Json::String(ref name) => Some(BusResolution::NAMES.iter().position(|n| n == name)
+					.and_then(BusResolution::from_index)
+					.ok_or(format!("unknown bus resolution '{}'", name))?),
+				_ => return Err("'resolution' must be a string or null".to_string()),
+			};
+
+			match ctx.get_node(to.0).map(|n| to.1 < n.get_num_inputs()) {
+				Some(true) => {}
+				Some(false) => return Err(format!("node {} has no input #{}", to.0, to.1)),
+				None => return Err(format!("no node with id {}", to.0)),
+			}
+
+			ctx.set_bus(to, resolution);
+			Ok(Json::Null)
+		}
+
+		"list_breakpoints" => Ok(Json::Array(ctx.get_breakpoints().iter()
+			.map(|b| Json::object(vec![
+				("id", Json::from(b.id)),
+				("port", Json::Array(vec![Json::from(b.port.0), Json::from(b.port.1)])),
+				("condition", Json::from(b.condition.to_string())),
+			]))
+			.collect())),
+
+		"add_breakpoint" => {
+			let port = port_field(request, "port")?;
+			let condition = field(request, "condition")?.as_str().ok_or("'condition' must be a string")?;
+			let condition = BreakCondition::parse(condition)?;
+
+			match ctx.get_node(port.0).map(|n| port.1 < n.get_num_outputs()) {
+				Some(true) => {}
+				Some(false) => return Err(format!("node {} has no output #{}", port.0, port.1)),
+				None => return Err(format!("no node with id {}", port.0)),
+			}
+
+			let id = ctx.add_breakpoint(port, condition);
+			Ok(Json::object(vec![("id", Json::from(id))]))
+		}
+
+		"remove_breakpoint" => {
+			let id = u32_field(request, "id")?;
+			if !ctx.remove_breakpoint(id) {
+				return Err(format!("no breakpoint with id {}", id));
+			}
+
+			Ok(Json::Null)
+		}
+
+		"get_break" => Ok(match ctx.get_break() {
+			Some(hit) => Json::object(vec![
+				("breakpoint", Json::from(hit.breakpoint)),
+				("port", Json::Array(vec![Json::from(hit.port.0), Json::from(hit.port.1)])),
+				("value", value_to_json(hit.value)),
+				("tick", Json::from(hit.tick)),
+			]),
+
+			None => Json::Null,
+		}),
+
+		"continue" => {
+			ctx.clear_break();
+			Ok(Json::Null)
+		}
+
+		"list_watches" => Ok(Json::Array(ctx.get_watches().iter()
+			.map(|w| Json::object(vec![
+				("id", Json::from(w.id)),
+				("expr", Json::from(w.text.as_str())),
+				("value", value_to_json(w.expr.evaluate(ctx))),
+			]))
+			.collect())),
+
+		"add_watch" => {
+			let expr = field(request, "expr")?.as_str().ok_or("'expr' must be a string")?;
+			let id = ctx.add_watch(expr)?;
+			Ok(Json::object(vec![("id", Json::from(id))]))
+		}
+
+		"remove_watch" => {
+			let id = u32_field(request, "id")?;
+			if !ctx.remove_watch(id) {
+				return Err(format!("no watch with id {}", id));
+			}
+
+			Ok(Json::Null)
+		}
+
+		_ => Err(format!("unknown command '{}'", cmd)),
+	}
+}
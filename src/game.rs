@@ -3,17 +3,40 @@ use wire::*;
 use gl;
 
 use text::TextRenderer;
+use control::{self, ControlAddress, ControlServer};
+use recording::{Input, Recorder};
+use json::Json;
 
 use std::ops::Fn;
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use std::f32;
 
 const WIRE_TICK_DURATION: f32 = 1.0/10.0;
 
+/// Multiples of the normal tick rate, with infinity stepping for as long
+/// as MAX_SPEED_BUDGET allows each frame
+const WIRE_SPEEDS: &[f32] = &[0.1, 0.25, 0.5, 1.0, 2.0, 5.0, 10.0, 100.0, f32::INFINITY];
+const DEFAULT_SPEED: usize = 3;
+
+/// Stops a slow circuit at a high speed from stalling the game
+const MAX_STEPS_PER_FRAME: u32 = 1000;
+const MAX_SPEED_BUDGET_MS: u64 = 10;
+
 const PLAYER_HEAD_HEIGHT: f32 = 2.0;
 const PLAYER_YAW_RATE: f32 = PI * 4.0;
 const PLAYER_PITCH_RATE: f32 = PLAYER_YAW_RATE;
 const PLAYER_WALK_SPEED: f32 = 5.0;
 
-#[derive(Copy, Clone)]
+/// Imported netlists are laid out with columns going right and rows going
+/// away from the player, starting this far in front of them
+const NETLIST_DISTANCE: f32 = 3.0;
+const NETLIST_COLUMN_SPACING: f32 = 1.5;
+const NETLIST_ROW_SPACING: f32 = 1.0;
+
+#[derive(Copy, Clone, Debug)]
 pub enum Key {
 	Forward, Left, Right, Back
 }
@@ -22,13 +45,35 @@ pub struct Item {
 	spawn: Box<Fn(&mut WireContext) -> u32>,
 	color: Vec3,
 	name: &'static str,
+
+	/// The category of the node spawned, for grouping the palette
+	category: Category,
+}
+
+impl Item {
+	fn new<T, F>(name: &'static str, color: Vec3, node: F) -> Self
+		where T: WireNode + 'static, F: Fn() -> T + 'static
+	{
+		let category = node().get_info().category;
+		Item { spawn: box move |wire: &mut WireContext| wire.add_node(node()), color, name, category }
+	}
+
+	/// An item whose node loads a file or binds a socket when made. `probe`
+	/// makes the same type of node without doing so, to find the category
+	fn with_probe<T, P, F>(name: &'static str, color: Vec3, probe: P, node: F) -> Self
+		where T: WireNode + 'static, P: Fn() -> T, F: Fn() -> T + 'static
+	{
+		let category = probe().get_info().category;
+		Item { spawn: box move |wire: &mut WireContext| wire.add_node(node()), color, name, category }
+	}
 }
 
 pub struct GameContext {
 	wire_context: WireContext,
 	wire_update_timer: f32,
 
-	text_renderer: TextRenderer,
+	/// None when running without a window
+	text_renderer: Option<TextRenderer>,
 
 	player_position: Vec2,
 	player_yaw: f32,
@@ -44,6 +89,15 @@ pub struct GameContext {
 
 	items: Vec<Item>,
 	current_item: i32,
+
+	param_edit: Option<ParamEdit>,
+
+	control: Option<ControlServer>,
+	paused: bool,
+	/// Index into WIRE_SPEEDS
+	speed: usize,
+
+	recorder: Option<Recorder>,
 }
 
 struct NodeView {
@@ -52,57 +106,153 @@ struct NodeView {
 	color: Vec3,
 }
 
+/// Colors for nodes that weren't placed from an item, by type
+fn node_type_color(type_name: &str) -> Vec3 {
+	match type_name {
+		"Toggle" => Vec3::new(0.2, 0.5, 0.6),
+		"Output" => Vec3::new(0.6, 0.2, 0.2),
+		"And" | "Gate" | "Not" | "Mux" => Vec3::new(0.4, 0.6, 0.2),
+		"DFlipFlop" | "DLatch" => Vec3::new(0.5, 0.3, 0.6),
+		_ => Vec3::new(0.5, 0.5, 0.5),
+	}
+}
+
+struct ParamEdit {
+	node_id: u32,
+	param: usize,
+
+	/// Text being typed in place of the current value
+	text: Option<String>,
+}
+
 impl GameContext {
 	pub fn new() -> Self {
-		let items = vec![
-			Item{
-				spawn: box |wire: &mut WireContext| wire.add_node( ConstantNode{value: 5} ),
-				color: Vec3::new(0.2, 0.2, 0.2),
-				name: "Constant",
-			},
-
-			Item{
-				spawn: box |wire: &mut WireContext| wire.add_node( AddNode::new() ),
-				color: Vec3::new(0.2, 0.6, 0.2),
-				name: "Adder",
-			},
-
-			Item{
-				spawn: box |wire: &mut WireContext| wire.add_node( AndNode::new() ),
-				color: Vec3::new(0.4, 0.6, 0.2),
-				name: "And",
-			},
-
-			Item{
-				spawn: box |wire: &mut WireContext| wire.add_node( CounterNode::new() ),
-				color: Vec3::new(0.6, 0.5, 0.2),
-				name: "Counter",
-			},
-
-			Item{
-				spawn: box |wire: &mut WireContext| wire.add_node( OutputNode::new("output") ),
-				color: Vec3::new(0.6, 0.2, 0.2),
-				name: "Output",
-			},
-
-			Item{
-				spawn: box |wire: &mut WireContext| wire.add_node( ButtonNode::new() ),
-				color: Vec3::new(0.2, 0.2, 0.6),
-				name: "Button",
-			},
-
-			Item{
-				spawn: box |wire: &mut WireContext| wire.add_node( ToggleNode::new() ),
-				color: Vec3::new(0.2, 0.5, 0.6),
-				name: "Toggle",
-			},
+		GameContext::create(Some(TextRenderer::new()))
+	}
+
+	/// A game that can't be drawn, for playing recordings back without a
+	/// window. Nothing else needs a GL context
+	pub fn headless() -> Self {
+		GameContext::create(None)
+	}
+
+	fn create(text_renderer: Option<TextRenderer>) -> Self {
+		let mut items = vec![
+			Item::new("Constant", Vec3::new(0.2, 0.2, 0.2), || ConstantNode{value: 5}),
+			Item::new("Adder", Vec3::new(0.2, 0.6, 0.2), || AddNode::new(2)),
+			Item::new("And", Vec3::new(0.4, 0.6, 0.2), || AndNode::new(2)),
+			Item::new("Not", Vec3::new(0.4, 0.6, 0.2), NotNode::new),
+			Item::new("Counter", Vec3::new(0.6, 0.5, 0.2), CounterNode::new),
+			Item::new("Output", Vec3::new(0.6, 0.2, 0.2), || OutputNode::new("output")),
+			Item::new("Button", Vec3::new(0.2, 0.2, 0.6), ButtonNode::new),
+			Item::new("Toggle", Vec3::new(0.2, 0.5, 0.6), ToggleNode::new),
+		];
+
+		let gate_ops = [
+			(GateOp::Or, "Or"),
+			(GateOp::Xor, "Xor"),
+			(GateOp::Nand, "Nand"),
+			(GateOp::Nor, "Nor"),
+			(GateOp::Xnor, "Xnor"),
+		];
+
+		for &(op, name) in gate_ops.iter() {
+			items.push(Item::new(name, Vec3::new(0.4, 0.6, 0.2), move || GateNode::new(op, 2)));
+		}
+
+		let binary_ops = [
+			(BinaryOp::Sub, "Subtract"),
+			(BinaryOp::Mul, "Multiply"),
+			(BinaryOp::Div, "Divide"),
+			(BinaryOp::Mod, "Modulo"),
+			(BinaryOp::Min, "Min"),
+			(BinaryOp::Max, "Max"),
+			(BinaryOp::Shl, "Shift Left"),
+			(BinaryOp::Shr, "Shift Right"),
+		];
+
+		for &(op, name) in binary_ops.iter() {
+			items.push(Item::new(name, Vec3::new(0.2, 0.6, 0.4), move || BinaryOpNode::new(op)));
+		}
+
+		for &(op, name) in [(UnaryOp::Neg, "Negate"), (UnaryOp::Abs, "Abs")].iter() {
+			items.push(Item::new(name, Vec3::new(0.2, 0.5, 0.5), move || UnaryOpNode::new(op)));
+		}
+
+		let compare_ops = [
+			(CompareOp::Eq, "Equal"),
+			(CompareOp::Ne, "Not Equal"),
+			(CompareOp::Lt, "Less"),
+			(CompareOp::Le, "Less Equal"),
+			(CompareOp::Gt, "Greater"),
+			(CompareOp::Ge, "Greater Equal"),
 		];
 
+		for &(op, name) in compare_ops.iter() {
+			items.push(Item::new(name, Vec3::new(0.5, 0.6, 0.2), move || CompareNode::new(op)));
+		}
+
+		items.extend(vec![
+			Item::new("SR Latch", Vec3::new(0.5, 0.3, 0.6), SrLatchNode::new),
+			Item::new("D Latch", Vec3::new(0.5, 0.3, 0.6), DLatchNode::new),
+			Item::new("D Flip-Flop", Vec3::new(0.4, 0.2, 0.6), DFlipFlopNode::new),
+			Item::new("T Flip-Flop", Vec3::new(0.4, 0.2, 0.6), TFlipFlopNode::new),
+			Item::new("JK Flip-Flop", Vec3::new(0.4, 0.2, 0.6), JkFlipFlopNode::new),
+			Item::new("Register", Vec3::new(0.3, 0.2, 0.5), || RegisterNode::new(8)),
+
+			Item::with_probe("ROM", Vec3::new(0.6, 0.4, 0.3), || RomNode::new(8, 8), || {
+				let path = Path::new("rom.hex");
+				RomNode::from_file(path, 8, 8).unwrap_or_else(|e| {
+					println!("Failed to load '{}': {}", path.display(), e);
+					RomNode::new(8, 8)
+				})
+			}),
+
+			Item::new("RAM", Vec3::new(0.6, 0.3, 0.3), || RamNode::new(8, 8)),
+			Item::new("Mux", Vec3::new(0.6, 0.6, 0.3), || MuxNode::new(4)),
+			Item::new("Demux", Vec3::new(0.6, 0.6, 0.3), || DemuxNode::new(4)),
+			Item::new("Encoder", Vec3::new(0.5, 0.5, 0.4), || EncoderNode::new(4)),
+			Item::new("Decoder", Vec3::new(0.5, 0.5, 0.4), || DecoderNode::new(4)),
+			Item::new("Priority Encoder", Vec3::new(0.5, 0.5, 0.4), || PriorityEncoderNode::new(4)),
+			Item::new("Delay", Vec3::new(0.3, 0.4, 0.6), || DelayNode::new(5)),
+			Item::new("Pulse", Vec3::new(0.3, 0.4, 0.6), || PulseNode::new(5)),
+			Item::new("Rising Edge", Vec3::new(0.3, 0.5, 0.6), || EdgeDetectorNode::new(EdgeMode::Rising)),
+			Item::new("Falling Edge", Vec3::new(0.3, 0.5, 0.6), || EdgeDetectorNode::new(EdgeMode::Falling)),
+			Item::new("Debounce", Vec3::new(0.3, 0.4, 0.5), || DebounceNode::new(3)),
+			Item::new("Watchdog", Vec3::new(0.5, 0.3, 0.4), || WatchdogNode::new(20)),
+			Item::new("Random", Vec3::new(0.6, 0.3, 0.5), || RandomNode::new(RandomOutput::Int{min: 0, max: 9}, None)),
+			Item::new("Random Bool", Vec3::new(0.6, 0.3, 0.5), || RandomNode::new(RandomOutput::Bool, None)),
+
+			Item::with_probe("Replay", Vec3::new(0.3, 0.6, 0.6), ReplayNode::new, || {
+				let path = Path::new("replay.txt");
+				ReplayNode::from_file(path).unwrap_or_else(|e| {
+					println!("Failed to load '{}': {}", path.display(), e);
+					ReplayNode::new()
+				})
+			}),
+
+			Item::new("Stdin", Vec3::new(0.3, 0.6, 0.6), || StdinNode::new("")),
+			Item::new("Tri-State", Vec3::new(0.6, 0.6, 0.3), TriStateNode::new),
+		]);
+
+		#[cfg(unix)]
+		items.push(Item::with_probe("Socket", Vec3::new(0.3, 0.6, 0.6), SocketNode::new, || {
+			let path = Path::new("wire.sock");
+			SocketNode::bind(path).unwrap_or_else(|e| {
+				println!("Failed to listen on '{}': {}", path.display(), e);
+				SocketNode::new()
+			})
+		}));
+
+		// The palette is grouped by category, keeping the order above within
+		// each. The sort is stable
+		items.sort_by_key(|item| item.category);
+
 		GameContext {
-			wire_context: WireContext::new(),
+			wire_context: WireContext::with_seed(DEFAULT_SEED),
 			wire_update_timer: 0.0,
 
-			text_renderer: TextRenderer::new(),
+			text_renderer,
 
 			player_position: Vec2::splat(0.0),
 			player_yaw: 0.0,
@@ -117,6 +267,14 @@ impl GameContext {
 			node_views: Vec::new(),
 			items,
 			current_item: 0,
+
+			param_edit: None,
+
+			control: None,
+			paused: false,
+			speed: DEFAULT_SPEED,
+
+			recorder: None,
 		}
 	}
 
@@ -155,7 +313,197 @@ impl GameContext {
 		}
 	}
 
+	fn get_edited_param(&self) -> Option<Param> {
+		let edit = self.param_edit.as_ref()?;
+		let node = self.wire_context.get_node(edit.node_id)?;
+		node.get_params().into_iter().nth(edit.param)
+	}
+
+	fn set_edited_param(&mut self, value: ParamValue) {
+		let param = match self.get_edited_param() {
+			Some(param) => param,
+			None => return,
+		};
+
+		let node_id = self.param_edit.as_ref().unwrap().node_id;
+		if let Err(e) = self.wire_context.set_param(node_id, param.name, value) {
+			println!("Can't set '{}': {}", param.name, e);
+		}
+
+		// The node may have lost the port being connected from, or the one
+		// being hovered
+		if let Some((src, port)) = self.connecting_node {
+			let num_outputs = self.wire_context.get_node(src).map_or(0, |n| n.get_num_outputs());
+			if src == node_id && port >= num_outputs {
+				self.connecting_node = None;
+			}
+		}
+
+		if self.hovered_node == Some(node_id) {
+			let connecting = self.connecting_node.is_some();
+			let port_count = self.get_hovered_node()
+				.map_or(0, |n| if connecting { n.get_num_inputs() } else { n.get_num_outputs() });
+
+			self.hovered_port = self.hovered_port.min(port_count.saturating_sub(1));
+		}
+	}
+
+	/// Starts or stops editing the params of the hovered node
+	pub fn toggle_param_edit(&mut self) {
+		if self.param_edit.is_some() {
+			self.param_edit = None;
+			return;
+		}
+
+		if let Some(node_id) = self.hovered_node {
+			let has_params = self.wire_context.get_node(node_id)
+				.map_or(false, |n| !n.get_params().is_empty());
+
+			if has_params {
+				self.param_edit = Some(ParamEdit { node_id, param: 0, text: None });
+			}
+		}
+	}
+
+	/// The hovered output port, if any
+	fn get_hovered_output(&self) -> Option<(u32, u32)> {
+		if self.connecting_node.is_some() { return None }
+
+		let node_id = self.hovered_node?;
+		let node = self.wire_context.get_node(node_id)?;
+
+		if self.hovered_port < node.get_num_outputs() {
+			Some((node_id, self.hovered_port))
+		} else {
+			None
+		}
+	}
+
+	/// Breaks when the hovered output changes, or removes the breakpoints
+	/// already on it
+	pub fn toggle_breakpoint(&mut self) {
+		let port = match self.get_hovered_output() {
+			Some(port) => port,
+			None => return,
+		};
+
+		let existing: Vec<_> = self.wire_context.get_breakpoints().iter()
+			.filter(|b| b.port == port)
+			.map(|b| b.id)
+			.collect();
+
+		if existing.is_empty() {
+			self.wire_context.add_breakpoint(port, BreakCondition::Changes);
+		}
+
+		for id in existing {
+			self.wire_context.remove_breakpoint(id);
+		}
+	}
+
+	pub fn toggle_watch(&mut self) {
+		let port = match self.get_hovered_output() {
+			Some(port) => port,
+			None => return,
+		};
+
+		let text = format!("{}:{}", port.0, port.1);
+		let existing = self.wire_context.get_watches().iter()
+			.find(|w| w.text == text)
+			.map(|w| w.id);
+
+		match existing {
+			Some(id) => { self.wire_context.remove_watch(id); }
+			None => { self.wire_context.add_watch(&text).unwrap(); }
+		}
+	}
+
+	pub fn toggle_pause(&mut self) {
+		self.paused = !self.paused;
+		self.wire_update_timer = 0.0;
+	}
+
+	/// Pauses, and steps once if already paused
+	pub fn single_step(&mut self) {
+		if self.paused {
+			self.wire_context.step();
+		}
+
+		self.paused = true;
+	}
+
+	pub fn speed_up(&mut self) {
+		self.speed = (self.speed + 1).min(WIRE_SPEEDS.len() - 1);
+	}
+
+	pub fn slow_down(&mut self) {
+		self.speed = self.speed.saturating_sub(1);
+	}
+
+	/// Carries on after a breakpoint was hit
+	pub fn continue_from_break(&mut self) {
+		self.wire_context.clear_break();
+	}
+
+	/// Shows or hides the profiling overlay. Stats are only collected while
+	/// it's shown
+	pub fn toggle_stats(&mut self) {
+		let enabled = self.wire_context.get_stats().is_some();
+		self.wire_context.enable_stats(!enabled);
+	}
+
+	pub fn is_typing(&self) -> bool {
+		self.param_edit.as_ref().map_or(false, |e| e.text.is_some())
+	}
+
+	pub fn on_text_input(&mut self, input: &str) {
+		if let Some(&mut ParamEdit{ text: Some(ref mut text), .. }) = self.param_edit.as_mut() {
+			text.push_str(input);
+		}
+	}
+
+	pub fn on_backspace(&mut self) {
+		if let Some(&mut ParamEdit{ text: Some(ref mut text), .. }) = self.param_edit.as_mut() {
+			text.pop();
+		}
+	}
+
+	pub fn cancel_typing(&mut self) {
+		if let Some(edit) = self.param_edit.as_mut() {
+			edit.text = None;
+		}
+	}
+
+	/// Starts typing a new value for the edited param, or commits the
+	/// typed value
+	pub fn on_return(&mut self) {
+		let param = match self.get_edited_param() {
+			Some(param) => param,
+			None => return,
+		};
+
+		let text = {
+			let edit = self.param_edit.as_mut().unwrap();
+			if edit.text.is_none() {
+				edit.text = Some(param.format_value());
+				return;
+			}
+
+			edit.text.take().unwrap()
+		};
+
+		match param.parse(&text) {
+			Ok(value) => self.set_edited_param(value),
+			Err(e) => println!("Can't set '{}' to '{}': {}", param.name, text, e),
+		}
+	}
+
 	pub fn prev_item(&mut self) {
+		if let Some(edit) = self.param_edit.as_mut() {
+			if edit.param > 0 { edit.param -= 1; }
+			return;
+		}
+
 		self.current_item -= 1;
 		if self.current_item < 0 {
 			self.current_item += self.items.len() as i32;
@@ -163,6 +511,16 @@ impl GameContext {
 	}
 
 	pub fn next_item(&mut self) {
+		if self.param_edit.is_some() {
+			let num_params = self.param_edit.as_ref()
+				.and_then(|e| self.wire_context.get_node(e.node_id))
+				.map_or(0, |n| n.get_params().len());
+
+			let edit = self.param_edit.as_mut().unwrap();
+			if edit.param + 1 < num_params { edit.param += 1; }
+			return;
+		}
+
 		self.current_item += 1;
 		if self.current_item >= self.items.len() as i32 {
 			self.current_item = 0;
@@ -170,6 +528,13 @@ impl GameContext {
 	}
 
 	pub fn prev_port(&mut self) {
+		if self.param_edit.is_some() {
+			if let Some(param) = self.get_edited_param() {
+				self.set_edited_param(param.adjusted(-1));
+			}
+			return;
+		}
+
 		let connecting = self.connecting_node.is_some();
 
 		if let Some(node) = self.get_hovered_node() {
@@ -188,6 +553,13 @@ impl GameContext {
 	}
 
 	pub fn next_port(&mut self) {
+		if self.param_edit.is_some() {
+			if let Some(param) = self.get_edited_param() {
+				self.set_edited_param(param.adjusted(1));
+			}
+			return;
+		}
+
 		let mut port_count = 0;
 		let connecting = self.connecting_node.is_some();
 
@@ -248,6 +620,111 @@ impl GameContext {
 		}
 	}
 
+	/// Cycles the hovered input through no bus, then each bus resolution.
+	/// Only does anything while connecting, since that's when inputs are picked
+	pub fn cycle_bus(&mut self) {
+		if self.connecting_node.is_none() { return }
+
+		let to = match self.hovered_node {
+			Some(node_id) => (node_id, self.hovered_port),
+			None => return,
+		};
+
+		match self.wire_context.get_node(to.0) {
+			Some(node) if to.1 < node.get_num_inputs() => {}
+			_ => return,
+		}
+
+		let next = match self.wire_context.get_bus(to) {
+			None => BusResolution::from_index(0),
+			Some(resolution) => BusResolution::from_index(resolution.to_index() + 1),
+		};
+
+		self.wire_context.set_bus(to, next);
+	}
+
+	/// Writes the circuit along with where each node is in the world
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		let mut file = File::create(path)?;
+		write_circuit(&self.wire_context, &mut file)?;
+		self.write_views(&mut file)
+	}
+
+	fn write_views<W: Write>(&self, out: &mut W) -> io::Result<()> {
+		for v in self.node_views.iter() {
+			let (p, c) = (v.position, v.color);
+			writeln!(out, "view {} {} {} {} {} {} {}", v.node_id, p.x, p.y, p.z, c.x, c.y, c.z)?;
+		}
+
+		Ok(())
+	}
+
+	/// Writes the circuit as a Graphviz digraph, see the dot module
+	pub fn export_dot(&self, path: &Path) -> io::Result<()> {
+		let mut file = File::create(path)?;
+		write_dot(&self.wire_context, &[], &mut file)
+	}
+
+	pub fn load(&mut self, path: &Path) -> Result<(), CircuitError> {
+		let circuit = load_circuit(path)?;
+		let mut node_views = Vec::new();
+
+		for &(line, ref tokens) in circuit.extra_lines.iter() {
+			let values = tokens[1..].iter()
+				.map(|t| t.parse::<f32>())
+				.collect::<Result<Vec<_>, _>>();
+
+			match (tokens[0].as_str(), values) {
+				("view", Ok(ref v)) if v.len() == 7 => node_views.push(NodeView {
+					node_id: v[0] as u32,
+					position: Vec3::new(v[1], v[2], v[3]),
+					color: Vec3::new(v[4], v[5], v[6]),
+				}),
+
+				_ => println!("{}:{}: ignoring '{}'", path.display(), line, tokens.join(" ")),
+			}
+		}
+
+		self.replace_circuit(circuit.context, node_views);
+		Ok(())
+	}
+
+	/// Replaces the circuit with one built from a Yosys netlist, laid out in
+	/// columns in front of the player
+	pub fn import_netlist(&mut self, path: &Path) -> Result<(), NetlistError> {
+		let context = load_netlist(path)?;
+
+		let right = Vec3::from_y_angle(-self.player_yaw);
+		let fwd = Vec3::new(right.z, 0.0, -right.x);
+		let origin = self.get_head_pos() + fwd * NETLIST_DISTANCE;
+
+		let node_views = auto_place(&context).iter()
+			.map(|p| NodeView {
+				node_id: p.node_id,
+				position: origin + right * (p.column as f32 * NETLIST_COLUMN_SPACING) + fwd * (p.row as f32 * NETLIST_ROW_SPACING),
+				color: node_type_color(context.get_node(p.node_id).unwrap().get_type_name()),
+			})
+			.collect();
+
+		self.replace_circuit(context, node_views);
+		Ok(())
+	}
+
+	/// Swaps in a new circuit, keeping stats on if they were and letting go
+	/// of anything that referred to the old one's nodes
+	fn replace_circuit(&mut self, context: WireContext, node_views: Vec<NodeView>) {
+		let stats = self.wire_context.get_stats().is_some();
+
+		self.wire_context = context;
+		self.wire_context.enable_stats(stats);
+		self.node_views = node_views;
+
+		self.hovered_node = None;
+		self.hovered_port = 0;
+		self.connecting_node = None;
+		self.param_edit = None;
+	}
+
 	pub fn get_eye_fwd(&self) -> Vec3 {
 		let Vec2{x, y: z} = Vec2::from_angle(-self.player_yaw - PI/2.0);
 		let y = self.player_pitch.sin();
@@ -268,13 +745,241 @@ impl GameContext {
 			.min(PI/3.0);
 	}
 
-	pub fn update(&mut self, dt: f32) {
-		self.wire_update_timer -= dt;
-		if self.wire_update_timer < 0.0 {
+	pub fn start_control_server(&mut self, address: &ControlAddress) -> io::Result<()> {
+		self.control = Some(ControlServer::bind(address)?);
+		Ok(())
+	}
+
+	pub fn get_ticks(&self) -> u64 { self.wire_context.get_ticks() }
+
+	/// Records every input from now on to `path`, see the recording module
+	pub fn start_recording(&mut self, path: &Path) -> io::Result<()> {
+		self.recorder = Some(Recorder::create(path)?);
+		Ok(())
+	}
+
+	/// Ends the recording with the state the game is in now
+	pub fn stop_recording(&mut self) -> io::Result<()> {
+		match self.recorder.take() {
+			Some(recorder) => recorder.finish(&self.describe_state()),
+			None => Ok(()),
+		}
+	}
+
+	fn record(&mut self, tick: u64, input: &Input) {
+		let result = match self.recorder {
+			Some(ref mut recorder) => recorder.record(tick, input),
+			None => return,
+		};
+
+		if let Err(e) = result {
+			println!("Stopped recording: {}", e);
+			self.recorder = None;
+		}
+	}
+
+	/// Applies something the player did, recording it if a recording is
+	/// running. Only loading files can fail, in which case nothing changes
+	/// and nothing is recorded
+	pub fn handle_input(&mut self, input: Input) -> Result<(), String> {
+		let tick = self.wire_context.get_ticks();
+
+		match input {
+			Input::Key(key, down) => self.set_key_state(key, down),
+			Input::MouseDelta(delta) => self.process_mouse_delta(delta),
+			Input::Frob => self.on_frob(),
+			Input::Click => self.on_click(),
+			Input::RightClick => self.on_rclick(),
+			Input::PrevItem => self.prev_item(),
+			Input::NextItem => self.next_item(),
+			Input::PrevPort => self.prev_port(),
+			Input::NextPort => self.next_port(),
+			Input::ToggleParamEdit => self.toggle_param_edit(),
+			Input::TextInput(ref text) => self.on_text_input(text),
+			Input::Backspace => self.on_backspace(),
+			Input::Return => self.on_return(),
+			Input::CancelTyping => self.cancel_typing(),
+			Input::CycleBus => self.cycle_bus(),
+			Input::ToggleStats => self.toggle_stats(),
+			Input::ToggleBreakpoint => self.toggle_breakpoint(),
+			Input::ToggleWatch => self.toggle_watch(),
+			Input::ContinueFromBreak => self.continue_from_break(),
+			Input::TogglePause => self.toggle_pause(),
+			Input::SingleStep => self.single_step(),
+			Input::SpeedUp => self.speed_up(),
+			Input::SlowDown => self.slow_down(),
+
+			Input::Load(ref path) => self.load(Path::new(path))
+				.map_err(|e| format!("Failed to load '{}': {}", path, e))?,
+
+			Input::Import(ref path) => self.import_netlist(Path::new(path))
+				.map_err(|e| format!("Failed to import '{}': {}", path, e))?,
+
+			// Only seen when playing back, live requests are recorded as
+			// they're handled. The response went to the client at the time
+			Input::Control(ref request) => {
+				let _ = self.handle_control(&Json::parse(request)?);
+			}
+
+			Input::Update { dt, steps } => {
+				let steps = self.update(dt, steps);
+				self.record(tick, &Input::Update { dt, steps });
+				return Ok(());
+			}
+		}
+
+		self.record(tick, &input);
+		Ok(())
+	}
+
+	/// The circuit and world as lines of text, for checking that a
+	/// recording plays back the same. Along with what's saved, this has the
+	/// player, the state and outputs of each node, and what's selected
+	pub fn describe_state(&self) -> Vec<String> {
+		let Vec2{x, y} = self.player_position;
+
+		let mut lines = vec![
+			format!("tick {}", self.wire_context.get_ticks()),
+			format!("player {} {} {} {}", x, y, self.player_yaw, self.player_pitch),
+			format!("speed {} paused {}", WIRE_SPEEDS[self.speed], self.paused),
+			format!("item {} hovered {:?} port {} connecting {:?}",
+				self.items[self.current_item as usize].name, self.hovered_node, self.hovered_port, self.connecting_node),
+		];
+
+		// Writing to a Vec can't fail
+		let mut saved = Vec::new();
+		write_circuit(&self.wire_context, &mut saved).unwrap();
+		self.write_views(&mut saved).unwrap();
+		lines.extend(String::from_utf8_lossy(&saved).lines().map(|l| l.to_string()));
+
+		for &(id, ref node) in self.wire_context.nodes.iter() {
+			let state = node.save_state();
+			if !state.is_empty() {
+				let state: Vec<_> = state.iter().map(|v| v.to_string()).collect();
+				lines.push(format!("state {} {}", id, state.join(" ")));
+			}
+
+			let outputs: Vec<_> = (0..node.get_num_outputs()).map(|p| node.get_output(p).to_string()).collect();
+			if !outputs.is_empty() {
+				lines.push(format!("outputs {} {}", id, outputs.join(" ")));
+			}
+		}
+
+		lines
+	}
+
+	/// Handles requests that need more than the WireContext, passing the
+	/// rest on to control::handle_request
+	fn handle_control(&mut self, request: &Json) -> Result<Json, String> {
+		match request.get("cmd").and_then(Json::as_str) {
+			Some("pause") => self.paused = true,
+			Some("resume") => self.paused = false,
+
+			Some("step") => {
+				let count = match request.get("count") {
+					Some(count) => count.as_i64().and_then(|c| if c >= 0 && c <= control::MAX_STEP_COUNT { Some(c) } else { None })
+						.ok_or(format!("'count' must be an integer from 0 to {}", control::MAX_STEP_COUNT))?,
+					None => 1,
+				};
+
+				self.wire_context.run(count as u64);
+			}
+
+			Some("add_node") => {
+				let position = match request.get("position").and_then(Json::as_array) {
+					Some(&[ref x, ref y, ref z]) => match (x.as_f64(), y.as_f64(), z.as_f64()) {
+						(Some(x), Some(y), Some(z)) => Vec3::new(x as f32, y as f32, z as f32),
+						_ => return Err("'position' must be [x, y, z]".to_string()),
+					},
+
+					Some(_) => return Err("'position' must be [x, y, z]".to_string()),
+					None => self.get_head_pos() + self.get_eye_fwd() * 1.5,
+				};
+
+				let node_id = control::add_node_json(&mut self.wire_context, request)?;
+
+				self.node_views.push(NodeView {
+					node_id, position,
+					color: Vec3::new(0.5, 0.5, 0.5),
+				});
+
+				return Ok(Json::object(vec![("id", Json::from(node_id))]));
+			}
+
+			Some("remove_node") => {
+				let result = control::handle_request(&mut self.wire_context, request)?;
+				let node_id = request.get("node").and_then(Json::as_i64).unwrap() as u32;
+
+				self.node_views.retain(|v| v.node_id != node_id);
+
+				if self.hovered_node == Some(node_id) { self.hovered_node = None; }
+				if self.connecting_node.map_or(false, |c| c.0 == node_id) { self.connecting_node = None; }
+				if self.param_edit.as_ref().map_or(false, |e| e.node_id == node_id) { self.param_edit = None; }
+
+				return Ok(result);
+			}
+
+			_ => return control::handle_request(&mut self.wire_context, request),
+		}
+
+		Ok(Json::Null)
+	}
+
+	/// Steps as many times as the speed calls for in `dt`. At max speed
+	/// that's `steps` if given, and the number taken is returned
+	fn step_wire(&mut self, dt: f32, steps: Option<u32>) -> Option<u32> {
+		let speed = WIRE_SPEEDS[self.speed];
+
+		if speed.is_infinite() {
+			let start = Instant::now();
+			let budget = Duration::from_millis(MAX_SPEED_BUDGET_MS);
+			let mut taken = 0;
+
+			while !self.paused && self.wire_context.get_break().is_none() {
+				let done = match steps {
+					Some(steps) => taken >= steps,
+					None => start.elapsed() >= budget,
+				};
+
+				if done { break }
+
+				self.wire_context.step();
+				taken += 1;
+			}
+
+			return Some(taken);
+		}
+
+		self.wire_update_timer -= dt * speed;
+
+		let mut steps = 0;
+		while self.wire_update_timer < 0.0 && steps < MAX_STEPS_PER_FRAME {
+			if self.paused || self.wire_context.get_break().is_some() { break }
+
 			self.wire_context.step();
-			self.wire_update_timer = WIRE_TICK_DURATION;
+			self.wire_update_timer += WIRE_TICK_DURATION;
+			steps += 1;
 		}
 
+		// Don't try to catch up on ticks missed while stopped or behind
+		self.wire_update_timer = self.wire_update_timer.max(0.0);
+		None
+	}
+
+	/// Runs a frame, see step_wire for `steps`
+	fn update(&mut self, dt: f32, steps: Option<u32>) -> Option<u32> {
+		if let Some(mut control) = self.control.take() {
+			control.poll(|request| {
+				let tick = self.wire_context.get_ticks();
+				self.record(tick, &Input::Control(request.to_string()));
+				self.handle_control(request)
+			});
+
+			self.control = Some(control);
+		}
+
+		let steps = self.step_wire(dt, steps);
+
 		let mut vel = Vec2::zero();
 		let right = Vec2::from_angle(-self.player_yaw);
 		let fwd = Vec2::new(right.y, -right.x);
@@ -311,9 +1016,16 @@ impl GameContext {
 		if !prev_hovered && self.hovered_node.is_some() {
 			self.hovered_port = 0;
 		}
+
+		steps
 	}
 
 	pub unsafe fn draw(&mut self) {
+		let text_renderer = match self.text_renderer {
+			Some(ref text_renderer) => text_renderer,
+			None => return,
+		};
+
 		gl::ClearColor(0.1, 0.1, 0.1, 1.0);
 		gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
@@ -324,13 +1036,18 @@ impl GameContext {
 		GameContext::draw_floor(5.0);
 
 		let hovered_node_id = self.hovered_node.unwrap_or(!0);
+		let break_node_id = self.wire_context.get_break().map_or(!0, |b| b.port.0);
 		let right = Vec3::from_y_angle(-self.player_yaw);
 
 		for v in self.node_views.iter() {
 			let hovered = v.node_id == hovered_node_id;
 
 			let boost = if hovered { Vec3::splat(0.1) } else { Vec3::zero() };
-			let col = v.color + boost;
+			let col = if v.node_id == break_node_id {
+				Vec3::new(1.0, 0.2, 0.2) + boost
+			} else {
+				v.color + boost
+			};
 
 			gl::Color3f(col.x, col.y, col.z);
 
@@ -342,7 +1059,7 @@ impl GameContext {
 			
 			if let Some(node) = self.wire_context.get_node(v.node_id) {
 				gl::Color3f(0.8, 0.8, 0.8);
-				self.text_renderer.draw(&node.get_label(),
+				text_renderer.draw(&node.get_label(),
 					v.position + Vec3::new(0.0, 0.11, 0.0),
 					right, true);
 			}
@@ -377,8 +1094,15 @@ impl GameContext {
 			let up = Vec3::new(0.0, 0.05, 0.0);
 			let right = Vec3::new(-0.15, 0.0, 0.0);
 
+			let conflict = self.wire_context.get_buses().iter()
+				.any(|b| b.to == c.to() && b.in_conflict());
+
 			gl::Begin(gl::LINES);
-			gl::Color3f(0.5, 0.8, 0.8);
+			if conflict {
+				gl::Color3f(1.0, 0.2, 0.2);
+			} else {
+				gl::Color3f(0.5, 0.8, 0.8);
+			}
 			gl::Vertex3fv(&(src.position - right + up * (c.input_port as f32 - 2.0)).x);
 			gl::Vertex3fv(&(dst.position + right + up * (c.output_port as f32 - 2.0)).x);
 			gl::End();
@@ -399,37 +1123,172 @@ impl GameContext {
 
 		{
 			let item = &self.items[self.current_item as usize];
-			self.text_renderer.draw_scale(item.name, Vec3::new(0.1, 0.1, 0.0),
-				Vec3::new(1.0, 0.0, 0.0), 6.0, false);
-		}
+			let group: Vec<_> = self.items.iter()
+				.filter(|i| i.category == item.category)
+				.map(|i| i.name)
+				.collect();
 
-		let connecting = self.connecting_node.is_some();
+			let index = group.iter().position(|&name| name == item.name).unwrap_or(0);
 
-		if let Some(node) = self.get_hovered_node() {
-			let port_count = if connecting {
-				node.get_num_inputs()
-			} else {
-				node.get_num_outputs()
-			};
+			gl::Color3f(0.7, 0.7, 0.7);
+			text_renderer.draw_scale(&format!("{} {}/{}", item.category, index + 1, group.len()),
+				Vec3::new(0.1, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0), 3.0, false);
 
-			let center = 6.0;
-			let size = 0.4;
-			let start = center - port_count as f32 / 2.0 * size + 0.5;
+			gl::Color3f(1.0, 1.0, 1.0);
+			text_renderer.draw_scale(item.name, Vec3::new(0.1, 0.1, 0.0),
+				Vec3::new(1.0, 0.0, 0.0), 6.0, false);
+		}
+
+		if let Some(edit) = self.param_edit.as_ref() {
+			let params = self.wire_context.get_node(edit.node_id)
+				.map_or(Vec::new(), |n| n.get_params());
 
-			gl::PointSize(10.0);
-			gl::Begin(gl::POINTS);
+			for (i, param) in params.iter().enumerate() {
+				let value = match edit.text {
+					Some(ref text) if i == edit.param => format!("{}_", text),
+					_ => param.format_value(),
+				};
 
-			for i in 0..port_count {
-				if i == self.hovered_port {
+				if i == edit.param {
 					gl::Color3f(1.0, 0.5, 0.5);
 				} else {
 					gl::Color3f(1.0, 1.0, 1.0);
 				}
 
-				gl::Vertex3f(7.0, start + size * i as f32, 0.0);
+				let pos = Vec3::new(0.1, 11.5 - i as f32 * 0.4, 0.0);
+				text_renderer.draw_scale(&format!("{}: {}", param.name, value), pos,
+					Vec3::new(1.0, 0.0, 0.0), 3.0, false);
 			}
 
-			gl::End();
+			gl::Color3f(1.0, 1.0, 1.0);
+		}
+
+		if let Some(stats) = self.wire_context.get_stats() {
+			let report = stats.to_string();
+
+			for (i, line) in report.lines().enumerate() {
+				let pos = Vec3::new(7.0, 11.5 - i as f32 * 0.3, 0.0);
+				text_renderer.draw_scale(line, pos, Vec3::new(1.0, 0.0, 0.0), 2.0, false);
+			}
+		}
+
+		{
+			let mut y = 5.0;
+
+			if let Some(hit) = self.wire_context.get_break() {
+				let condition = self.wire_context.get_breakpoints().iter()
+					.find(|b| b.id == hit.breakpoint)
+					.map_or(String::new(), |b| b.condition.to_string());
+
+				gl::Color3f(1.0, 0.2, 0.2);
+				text_renderer.draw_scale(&format!("break at tick {}: {}:{} {} ({})",
+						hit.tick, hit.port.0, hit.port.1, condition, hit.value),
+					Vec3::new(0.1, y, 0.0), Vec3::new(1.0, 0.0, 0.0), 3.0, false);
+				y -= 0.4;
+			}
+
+			gl::Color3f(0.8, 0.8, 1.0);
+
+			for watch in self.wire_context.get_watches() {
+				let value = watch.expr.evaluate(&self.wire_context);
+				text_renderer.draw_scale(&format!("{} = {}", watch.text, value),
+					Vec3::new(0.1, y, 0.0), Vec3::new(1.0, 0.0, 0.0), 3.0, false);
+				y -= 0.4;
+			}
+
+			gl::Color3f(1.0, 1.0, 1.0);
+		}
+
+		{
+			let speed = WIRE_SPEEDS[self.speed];
+			let speed = if speed.is_infinite() { "max".to_string() } else { format!("{}x", speed) };
+			let state = if self.paused { " paused" } else { "" };
+
+			text_renderer.draw_scale(&format!("tick {} {}{}", self.wire_context.get_ticks(), speed, state),
+				Vec3::new(8.5, 0.1, 0.0), Vec3::new(1.0, 0.0, 0.0), 3.0, false);
+		}
+
+		let conflicts = self.wire_context.get_buses().iter()
+			.filter(|b| b.in_conflict())
+			.count();
+
+		if conflicts > 0 {
+			gl::Color3f(1.0, 0.2, 0.2);
+			text_renderer.draw_scale(&format!("{} bus conflicts", conflicts),
+				Vec3::new(0.1, 0.6, 0.0), Vec3::new(1.0, 0.0, 0.0), 3.0, false);
+			gl::Color3f(1.0, 1.0, 1.0);
+		}
+
+		let connecting = self.connecting_node.is_some();
+
+		if let Some(node_id) = self.hovered_node {
+			if let Some(node) = self.wire_context.get_node(node_id) {
+				let info = node.get_info();
+
+				// Inputs are what's being picked while connecting
+				let ports: Vec<_> = if connecting {
+					info.inputs.iter().enumerate().map(|(i, port)| {
+						let value = self.wire_context.connections.iter()
+							.find(|c| c.to() == (node_id, i as u32))
+							.map_or(WireValue::Null, |c| c.value());
+
+						(port, value)
+					}).collect()
+				} else {
+					info.outputs.iter().enumerate()
+						.map(|(i, port)| (port, node.get_output(i as u32)))
+						.collect()
+				};
+
+				let center = 6.0;
+				let size = 0.4;
+				let start = center - ports.len() as f32 / 2.0 * size + 0.5;
+
+				gl::PointSize(10.0);
+
+				for (i, &(port, value)) in ports.iter().enumerate() {
+					if i as u32 == self.hovered_port {
+						gl::Color3f(1.0, 0.5, 0.5);
+					} else {
+						gl::Color3f(1.0, 1.0, 1.0);
+					}
+
+					let y = start + size * i as f32;
+
+					gl::Begin(gl::POINTS);
+					gl::Vertex3f(7.0, y, 0.0);
+					gl::End();
+
+					text_renderer.draw_scale(&format!("{} {}", port.name, value),
+						Vec3::new(7.2, y - 0.1, 0.0), Vec3::new(1.0, 0.0, 0.0), 2.0, false);
+				}
+
+				gl::Color3f(0.8, 0.8, 0.8);
+
+				let hovered = if connecting {
+					info.get_input(self.hovered_port)
+				} else {
+					info.get_output(self.hovered_port)
+				};
+
+				let mut lines = vec![if info.help.is_empty() {
+					info.type_name.to_string()
+				} else {
+					format!("{}: {}", info.type_name, info.help)
+				}];
+
+				match hovered {
+					Some(port) if !port.description.is_empty() =>
+						lines.push(format!("{} ({}): {}", port.name, port.ty, port.description)),
+					Some(port) => lines.push(format!("{} ({})", port.name, port.ty)),
+					None => {}
+				}
+
+				for (i, line) in lines.iter().enumerate() {
+					text_renderer.draw_scale(line, Vec3::new(6.2, 3.0 - i as f32 * 0.3, 0.0),
+						Vec3::new(1.0, 0.0, 0.0), 2.0, false);
+				}
+			}
 		}
 
 		gl::Color3f(1.0, 1.0, 1.0);
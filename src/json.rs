@@ -0,0 +1,355 @@
+//! Just enough JSON for the control protocol and importing netlists
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Json>),
+	/// Keys are kept in the order they were written
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	pub fn parse(text: &str) -> Result<Json, String> {
+		let mut parser = Parser { chars: text.chars().collect(), pos: 0, depth: 0 };
+
+		let value = parser.parse_value()?;
+		parser.skip_whitespace();
+
+		if parser.pos < parser.chars.len() {
+			return Err(parser.error("trailing characters"));
+		}
+
+		Ok(value)
+	}
+
+	pub fn object(pairs: Vec<(&str, Json)>) -> Json {
+		Json::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+	}
+
+	pub fn get(&self, key: &str) -> Option<&Json> {
+		match *self {
+			Json::Object(ref pairs) => pairs.iter().find(|p| p.0 == key).map(|p| &p.1),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			Json::String(ref s) => Some(s),
+			_ => None,
+		}
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			Json::Bool(v) => Some(v),
+			_ => None,
+		}
+	}
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match *self {
+			Json::Number(v) => Some(v),
+			_ => None,
+		}
+	}
+
+	/// Only numbers without a fractional part
+	pub fn as_i64(&self) -> Option<i64> {
+		match *self {
+			Json::Number(v) if v.fract() == 0.0 => Some(v as i64),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&[Json]> {
+		match *self {
+			Json::Array(ref items) => Some(items),
+			_ => None,
+		}
+	}
+
+	pub fn as_object(&self) -> Option<&[(String, Json)]> {
+		match *self {
+			Json::Object(ref pairs) => Some(pairs),
+			_ => None,
+		}
+	}
+}
+
+impl From<bool> for Json {
+	fn from(v: bool) -> Json { Json::Bool(v) }
+}
+
+impl From<i32> for Json {
+	fn from(v: i32) -> Json { Json::Number(v as f64) }
+}
+
+impl From<u32> for Json {
+	fn from(v: u32) -> Json { Json::Number(v as f64) }
+}
+
+impl From<u64> for Json {
+	fn from(v: u64) -> Json { Json::Number(v as f64) }
+}
+
+impl From<f32> for Json {
+	fn from(v: f32) -> Json { Json::Number(v as f64) }
+}
+
+impl<'a> From<&'a str> for Json {
+	fn from(v: &str) -> Json { Json::String(v.to_string()) }
+}
+
+impl From<String> for Json {
+	fn from(v: String) -> Json { Json::String(v) }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+	write!(f, "\"")?;
+
+	for c in s.chars() {
+		match c {
+			'"' => write!(f, "\\\"")?,
+			'\\' => write!(f, "\\\\")?,
+			'\n' => write!(f, "\\n")?,
+			'\r' => write!(f, "\\r")?,
+			'\t' => write!(f, "\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => write!(f, "{}", c)?,
+		}
+	}
+
+	write!(f, "\"")
+}
+
+/// Writes compact JSON on a single line
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Json::Null => write!(f, "null"),
+			Json::Bool(v) => write!(f, "{}", v),
+			Json::Number(v) if !v.is_finite() => write!(f, "null"),
+			Json::Number(v) => write!(f, "{}", v),
+			Json::String(ref s) => write_string(f, s),
+
+			Json::Array(ref items) => {
+				write!(f, "[")?;
+				for (i, item) in items.iter().enumerate() {
+					if i > 0 { write!(f, ",")?; }
+					write!(f, "{}", item)?;
+				}
+				write!(f, "]")
+			}
+
+			Json::Object(ref pairs) => {
+				write!(f, "{{")?;
+				for (i, &(ref key, ref value)) in pairs.iter().enumerate() {
+					if i > 0 { write!(f, ",")?; }
+					write_string(f, key)?;
+					write!(f, ":{}", value)?;
+				}
+				write!(f, "}}")
+			}
+		}
+	}
+}
+
+
+/// Deepest arrays and objects can be nested, so deep input fails instead
+/// of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser {
+	chars: Vec<char>,
+	pos: usize,
+	/// Arrays and objects currently being parsed
+	depth: usize,
+}
+
+impl Parser {
+	fn error(&self, message: &str) -> String {
+		format!("{} at character {}", message, self.pos + 1)
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).cloned()
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.peek();
+		self.pos += 1;
+		c
+	}
+
+	fn skip_whitespace(&mut self) {
+		while self.peek().map_or(false, |c| c.is_whitespace()) {
+			self.pos += 1;
+		}
+	}
+
+	fn expect(&mut self, c: char) -> Result<(), String> {
+		self.skip_whitespace();
+
+		if self.peek() == Some(c) {
+			self.pos += 1;
+			Ok(())
+		} else {
+			Err(self.error(&format!("expected '{}'", c)))
+		}
+	}
+
+	fn parse_keyword(&mut self, keyword: &str, value: Json) -> Result<Json, String> {
+		for expected in keyword.chars() {
+			if self.next() != Some(expected) {
+				return Err(self.error("invalid literal"));
+			}
+		}
+
+		Ok(value)
+	}
+
+	fn parse_value(&mut self) -> Result<Json, String> {
+		self.skip_whitespace();
+
+		match self.peek() {
+			Some('n') => self.parse_keyword("null", Json::Null),
+			Some('t') => self.parse_keyword("true", Json::Bool(true)),
+			Some('f') => self.parse_keyword("false", Json::Bool(false)),
+			Some('"') => self.parse_string().map(Json::String),
+			Some('[') => self.parse_nested(Parser::parse_array),
+			Some('{') => self.parse_nested(Parser::parse_object),
+			Some(c) if c == '-' || c.is_digit(10) => self.parse_number(),
+			Some(_) => Err(self.error("unexpected character")),
+			None => Err(self.error("unexpected end of input")),
+		}
+	}
+
+	fn parse_nested<F>(&mut self, parse: F) -> Result<Json, String> where F: FnOnce(&mut Parser) -> Result<Json, String> {
+		if self.depth >= MAX_DEPTH {
+			return Err(self.error("nested too deeply"));
+		}
+
+		self.depth += 1;
+		let result = parse(self);
+		self.depth -= 1;
+		result
+	}
+
+	fn parse_number(&mut self) -> Result<Json, String> {
+		let start = self.pos;
+
+		while self.peek().map_or(false, |c| c.is_digit(10) || "+-.eE".contains(c)) {
+			self.pos += 1;
+		}
+
+		let text: String = self.chars[start..self.pos].iter().cloned().collect();
+		text.parse().map(Json::Number).map_err(|_| self.error("invalid number"))
+	}
+
+	fn parse_hex4(&mut self) -> Result<u32, String> {
+		let mut value = 0;
+
+		for _ in 0..4 {
+			let digit = self.next().and_then(|c| c.to_digit(16)).ok_or(self.error("invalid escape"))?;
+			value = value * 16 + digit;
+		}
+
+		Ok(value)
+	}
+
+	fn parse_string(&mut self) -> Result<String, String> {
+		self.expect('"')?;
+		let mut s = String::new();
+
+		loop {
+			match self.next() {
+				Some('"') => return Ok(s),
+
+				Some('\\') => match self.next() {
+					Some('"') => s.push('"'),
+					Some('\\') => s.push('\\'),
+					Some('/') => s.push('/'),
+					Some('b') => s.push('\u{8}'),
+					Some('f') => s.push('\u{c}'),
+					Some('n') => s.push('\n'),
+					Some('r') => s.push('\r'),
+					Some('t') => s.push('\t'),
+
+					Some('u') => {
+						let mut code = self.parse_hex4()?;
+
+						// Characters outside the BMP come as surrogate pairs
+						if code >= 0xd800 && code < 0xdc00 {
+							if self.next() != Some('\\') || self.next() != Some('u') {
+								return Err(self.error("unpaired surrogate"));
+							}
+
+							let low = self.parse_hex4()?;
+							code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+						}
+
+						s.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+					}
+
+					_ => return Err(self.error("invalid escape")),
+				},
+
+				Some(c) => s.push(c),
+				None => return Err(self.error("unterminated string")),
+			}
+		}
+	}
+
+	fn parse_array(&mut self) -> Result<Json, String> {
+		self.expect('[')?;
+		let mut items = Vec::new();
+
+		self.skip_whitespace();
+		if self.peek() == Some(']') {
+			self.pos += 1;
+			return Ok(Json::Array(items));
+		}
+
+		loop {
+			items.push(self.parse_value()?);
+			self.skip_whitespace();
+
+			match self.next() {
+				Some(',') => {}
+				Some(']') => return Ok(Json::Array(items)),
+				_ => return Err(self.error("expected ',' or ']'")),
+			}
+		}
+	}
+
+	fn parse_object(&mut self) -> Result<Json, String> {
+		self.expect('{')?;
+		let mut pairs = Vec::new();
+
+		self.skip_whitespace();
+		if self.peek() == Some('}') {
+			self.pos += 1;
+			return Ok(Json::Object(pairs));
+		}
+
+		loop {
+			self.skip_whitespace();
+			let key = self.parse_string()?;
+			self.expect(':')?;
+			pairs.push((key, self.parse_value()?));
+			self.skip_whitespace();
+
+			match self.next() {
+				Some(',') => {}
+				Some('}') => return Ok(Json::Object(pairs)),
+				_ => return Err(self.error("expected ',' or '}'")),
+			}
+		}
+	}
+}
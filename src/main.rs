@@ -3,6 +3,8 @@
 
 extern crate sdl2;
 extern crate lodepng;
+#[macro_use]
+extern crate lazy_static;
 
 #[macro_export]
 macro_rules! match_enum {
@@ -19,14 +21,32 @@ mod gl {
 }
 
 mod game;
+mod cli;
+mod control;
+mod recording;
+mod json;
 mod wire;
 mod text;
 mod math;
 mod easing;
 
 use math::*;
+use recording::Input;
+
+use std::env;
+use std::path::Path;
+use std::process;
+
+const CIRCUIT_PATH: &str = "circuit.wire";
+const DOT_PATH: &str = "circuit.dot";
 
 fn main() {
+	let args: Vec<String> = env::args().skip(1).collect();
+	let options = match cli::run(&args) {
+		Ok(options) => options,
+		Err(code) => process::exit(code),
+	};
+
 	let sdl_ctx = sdl2::init().unwrap();
 	let video = sdl_ctx.video().unwrap();
 
@@ -50,6 +70,40 @@ fn main() {
 
 	let mut game_ctx = game::GameContext::new();
 
+	// Started first so that the import is recorded too
+	if let Some(path) = options.record {
+		if let Err(e) = game_ctx.start_recording(Path::new(&path)) {
+			println!("Failed to record to '{}': {}", path, e);
+			process::exit(1);
+		}
+	}
+
+	let mut replay = match options.replay {
+		Some(path) => match recording::load_recording(Path::new(&path)) {
+			Ok(recording) => Some(recording),
+			Err(e) => {
+				println!("Failed to load '{}': {}", path, e);
+				process::exit(1);
+			}
+		},
+
+		None => None,
+	};
+
+	if let Some(path) = options.import {
+		if let Err(e) = game_ctx.handle_input(Input::Import(path)) {
+			println!("{}", e);
+			process::exit(1);
+		}
+	}
+
+	if let Some(address) = options.control {
+		if let Err(e) = game_ctx.start_control_server(&address) {
+			println!("Failed to start control server on '{}': {}", address, e);
+			process::exit(1);
+		}
+	}
+
 	unsafe {
 		gl::Enable(gl::CULL_FACE);
 		gl::Enable(gl::DEPTH_TEST);
@@ -68,72 +122,106 @@ fn main() {
 			use sdl2::mouse::MouseButton;
 			use game::Key;
 
-			match event {
+			let input = match event {
+				Event::KeyDown { keycode: Some(key), .. } if game_ctx.is_typing() => {
+					match key {
+						Keycode::Return => Input::Return,
+						Keycode::Backspace => Input::Backspace,
+						Keycode::Escape => Input::CancelTyping,
+						_ => continue
+					}
+				}
+
+				Event::TextInput { text, .. } => Input::TextInput(text),
+
 				Event::KeyDown { keycode: Some(Keycode::Escape), .. } |
 				Event::Quit { .. } => break 'main,
 
 				Event::KeyDown { keycode: Some(Keycode::C), repeat: false, .. } => {
 					capture = !capture;
 					sdl_ctx.mouse().warp_mouse_in_window(&window, 400, 300);
+					continue
 				}
 
 				Event::KeyDown { keycode: Some(key), repeat: false, .. } => {
-					let key = match key {
-						Keycode::W => Key::Forward,
-						Keycode::A => Key::Left,
-						Keycode::S => Key::Back,
-						Keycode::D => Key::Right,
-
-						Keycode::Q => {
-							game_ctx.prev_item();
-							break;
-						},
-
-						Keycode::E => {
-							game_ctx.next_item();
-							break;
-						},
-
-						Keycode::F => {
-							game_ctx.on_frob();
-							break;
+					match key {
+						Keycode::W => Input::Key(Key::Forward, true),
+						Keycode::A => Input::Key(Key::Left, true),
+						Keycode::S => Input::Key(Key::Back, true),
+						Keycode::D => Input::Key(Key::Right, true),
+
+						Keycode::Q => Input::PrevItem,
+						Keycode::E => Input::NextItem,
+						Keycode::F => Input::Frob,
+						Keycode::R => Input::ToggleParamEdit,
+						Keycode::Return => Input::Return,
+						Keycode::B => Input::CycleBus,
+						Keycode::F3 => Input::ToggleStats,
+						Keycode::K => Input::ToggleBreakpoint,
+						Keycode::V => Input::ToggleWatch,
+						Keycode::F8 => Input::ContinueFromBreak,
+						Keycode::P => Input::TogglePause,
+						Keycode::N => Input::SingleStep,
+						Keycode::Equals => Input::SpeedUp,
+						Keycode::Minus => Input::SlowDown,
+
+						// Saving doesn't change anything, so isn't an input
+						Keycode::F5 => {
+							if let Err(e) = game_ctx.save(Path::new(CIRCUIT_PATH)) {
+								println!("Failed to save '{}': {}", CIRCUIT_PATH, e);
+							}
+							continue
 						}
 
-						_ => break
-					};
+						Keycode::F6 => {
+							if let Err(e) = game_ctx.export_dot(Path::new(DOT_PATH)) {
+								println!("Failed to write '{}': {}", DOT_PATH, e);
+							}
+							continue
+						}
 
-					game_ctx.set_key_state(key, true);
+						Keycode::F9 => Input::Load(CIRCUIT_PATH.to_string()),
+
+						_ => continue
+					}
 				}
 
 				Event::KeyUp { keycode: Some(key), .. } => {
-					let key = match key {
-						Keycode::W => Key::Forward,
-						Keycode::A => Key::Left,
-						Keycode::S => Key::Back,
-						Keycode::D => Key::Right,
-						_ => break
-					};
-
-					game_ctx.set_key_state(key, false);
+					match key {
+						Keycode::W => Input::Key(Key::Forward, false),
+						Keycode::A => Input::Key(Key::Left, false),
+						Keycode::S => Input::Key(Key::Back, false),
+						Keycode::D => Input::Key(Key::Right, false),
+						_ => continue
+					}
 				}
 
 				Event::MouseButtonDown{ mouse_btn, .. } => {
 					match mouse_btn {
-						MouseButton::Left => game_ctx.on_click(),
-						MouseButton::Right => game_ctx.on_rclick(),
-						_ => {}
+						MouseButton::Left => Input::Click,
+						MouseButton::Right => Input::RightClick,
+						_ => continue
 					}
 				}
 
 				Event::MouseWheel{ y, .. } => {
 					if y > 0 {
-						game_ctx.next_port()
+						Input::NextPort
 					} else if y < 0 {
-						game_ctx.prev_port()
+						Input::PrevPort
+					} else {
+						continue
 					}
 				}
 
-				_ => {}
+				_ => continue
+			};
+
+			// The player takes over once the replay is done
+			if replay.is_some() { continue }
+
+			if let Err(e) = game_ctx.handle_input(input) {
+				println!("{}", e);
 			}
 		}
 
@@ -145,10 +233,37 @@ fn main() {
 		if capture {
 			let mdiff = (mouse - Vec2i::new(400, 300)).to_vec2() / Vec2::new(400.0, 300.0);
 			sdl_ctx.mouse().warp_mouse_in_window(&window, 400, 300);
-			game_ctx.process_mouse_delta(mdiff * 1.0/16.0);
+
+			if replay.is_none() && (mdiff.x != 0.0 || mdiff.y != 0.0) {
+				game_ctx.handle_input(Input::MouseDelta(mdiff * 1.0/16.0)).unwrap();
+			}
+		}
+
+		let replay_done = match replay {
+			Some(ref mut replay) => match replay.play_frame(&mut game_ctx) {
+				Ok(()) if replay.is_finished() => {
+					println!("Replay finished at tick {}", game_ctx.get_ticks());
+					true
+				}
+
+				Ok(()) => false,
+
+				Err(e) => {
+					println!("Stopped replaying: {}", e);
+					true
+				}
+			},
+
+			None => {
+				game_ctx.handle_input(Input::Update { dt: 1.0/60.0, steps: None }).unwrap();
+				false
+			}
+		};
+
+		if replay_done {
+			replay = None;
 		}
 
-		game_ctx.update(1.0/60.0);
 
 		unsafe {
 			game_ctx.draw();
@@ -156,4 +271,8 @@ fn main() {
 
 		window.gl_swap_window();
 	}
+
+	if let Err(e) = game_ctx.stop_recording() {
+		println!("Failed to finish recording: {}", e);
+	}
 }
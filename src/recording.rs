@@ -0,0 +1,434 @@
+//! Recording everything the player does to the game, so that a session can
+//! be played back to exactly the same circuit and world. The game is
+//! started with `--record <path>` to record and `--replay <path>` to play
+//! back, after which the player takes over. `--verify <path>` plays a
+//! recording back without a window and checks it ends in the same state.
+//!
+//! A recording is one input per line, with the frame and tick it happened
+//! on. Each frame ends with an `update` line, which also holds how many
+//! steps were taken if the game was running at max speed, since that
+//! depends on how fast the machine was. Ticks are checked on playback, so
+//! a recording that goes out of sync stops at the first input that does.
+//! The file ends with `end` and the final state, see
+//! GameContext::describe_state:
+//!
+//! ```text
+//! 0 0 key forward down
+//! 0 0 mouse 0.0125 -0.003
+//! 0 0 update 0.016666668
+//! 1 0 frob
+//! 1 0 control {"cmd":"step","count":3}
+//! 1 3 update 0.016666668
+//! end
+//! tick 3
+//! ...
+//! ```
+//!
+//! Nodes reading from outside the game, like Stdin or Socket, and files
+//! that are loaded, like circuits and ROM contents, aren't recorded, so
+//! they need to be the same when playing back.
+
+use game::{GameContext, Key};
+use math::*;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Something the player did, see GameContext::handle_input
+#[derive(Clone, Debug)]
+pub enum Input {
+	Key(Key, bool),
+	MouseDelta(Vec2),
+	Frob,
+	Click,
+	RightClick,
+	PrevItem,
+	NextItem,
+	PrevPort,
+	NextPort,
+	ToggleParamEdit,
+	TextInput(String),
+	Backspace,
+	Return,
+	CancelTyping,
+	CycleBus,
+	ToggleStats,
+	ToggleBreakpoint,
+	ToggleWatch,
+	ContinueFromBreak,
+	TogglePause,
+	SingleStep,
+	SpeedUp,
+	SlowDown,
+	Load(String),
+	Import(String),
+	/// A request to the control server, as JSON
+	Control(String),
+
+	/// The end of a frame. `steps` is how many steps to take at max speed,
+	/// or None to take as many as fit in the frame
+	Update { dt: f32, steps: Option<u32> },
+}
+
+fn key_name(key: Key) -> &'static str {
+	match key {
+		Key::Forward => "forward",
+		Key::Left => "left",
+		Key::Right => "right",
+		Key::Back => "back",
+	}
+}
+
+impl fmt::Display for Input {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Input::Key(key, down) => write!(f, "key {} {}", key_name(key), if down { "down" } else { "up" }),
+			Input::MouseDelta(delta) => write!(f, "mouse {} {}", delta.x, delta.y),
+			Input::Frob => write!(f, "frob"),
+			Input::Click => write!(f, "click"),
+			Input::RightClick => write!(f, "rclick"),
+			Input::PrevItem => write!(f, "prev_item"),
+			Input::NextItem => write!(f, "next_item"),
+			Input::PrevPort => write!(f, "prev_port"),
+			Input::NextPort => write!(f, "next_port"),
+			Input::ToggleParamEdit => write!(f, "param_edit"),
+			Input::TextInput(ref text) => write!(f, "text {}", text),
+			Input::Backspace => write!(f, "backspace"),
+			Input::Return => write!(f, "return"),
+			Input::CancelTyping => write!(f, "cancel_typing"),
+			Input::CycleBus => write!(f, "cycle_bus"),
+			Input::ToggleStats => write!(f, "stats"),
+			Input::ToggleBreakpoint => write!(f, "breakpoint"),
+			Input::ToggleWatch => write!(f, "watch"),
+			Input::ContinueFromBreak => write!(f, "continue"),
+			Input::TogglePause => write!(f, "pause"),
+			Input::SingleStep => write!(f, "single_step"),
+			Input::SpeedUp => write!(f, "speed_up"),
+			Input::SlowDown => write!(f, "slow_down"),
+			Input::Load(ref path) => write!(f, "load {}", path),
+			Input::Import(ref path) => write!(f, "import {}", path),
+			Input::Control(ref request) => write!(f, "control {}", request),
+			Input::Update { dt, steps: None } => write!(f, "update {}", dt),
+			Input::Update { dt, steps: Some(steps) } => write!(f, "update {} {}", dt, steps),
+		}
+	}
+}
+
+impl Input {
+	/// Reads an input as written by Display
+	pub fn parse(text: &str) -> Result<Input, String> {
+		let mut parts = text.splitn(2, ' ');
+		let name = parts.next().unwrap();
+		let rest = parts.next().unwrap_or("");
+		let args: Vec<&str> = rest.split_whitespace().collect();
+
+		let float = |text: &str| text.parse::<f32>().map_err(|_| format!("invalid number '{}'", text));
+
+		let input = match (name, args.as_slice()) {
+			("key", &[key, state]) => {
+				let key = match key {
+					"forward" => Key::Forward,
+					"left" => Key::Left,
+					"right" => Key::Right,
+					"back" => Key::Back,
+					_ => return Err(format!("unknown key '{}'", key)),
+				};
+
+				match state {
+					"down" => Input::Key(key, true),
+					"up" => Input::Key(key, false),
+					_ => return Err(format!("expected 'down' or 'up', not '{}'", state)),
+				}
+			}
+
+			("mouse", &[x, y]) => Input::MouseDelta(Vec2::new(float(x)?, float(y)?)),
+			("frob", &[]) => Input::Frob,
+			("click", &[]) => Input::Click,
+			("rclick", &[]) => Input::RightClick,
+			("prev_item", &[]) => Input::PrevItem,
+			("next_item", &[]) => Input::NextItem,
+			("prev_port", &[]) => Input::PrevPort,
+			("next_port", &[]) => Input::NextPort,
+			("param_edit", &[]) => Input::ToggleParamEdit,
+			("text", _) => Input::TextInput(rest.to_string()),
+			("backspace", &[]) => Input::Backspace,
+			("return", &[]) => Input::Return,
+			("cancel_typing", &[]) => Input::CancelTyping,
+			("cycle_bus", &[]) => Input::CycleBus,
+			("stats", &[]) => Input::ToggleStats,
+			("breakpoint", &[]) => Input::ToggleBreakpoint,
+			("watch", &[]) => Input::ToggleWatch,
+			("continue", &[]) => Input::ContinueFromBreak,
+			("pause", &[]) => Input::TogglePause,
+			("single_step", &[]) => Input::SingleStep,
+			("speed_up", &[]) => Input::SpeedUp,
+			("slow_down", &[]) => Input::SlowDown,
+			("load", _) if !rest.is_empty() => Input::Load(rest.to_string()),
+			("import", _) if !rest.is_empty() => Input::Import(rest.to_string()),
+			("control", _) if !rest.is_empty() => Input::Control(rest.to_string()),
+			("update", &[dt]) => Input::Update { dt: float(dt)?, steps: None },
+
+			("update", &[dt, steps]) => {
+				let steps = steps.parse().map_err(|_| format!("invalid step count '{}'", steps))?;
+				Input::Update { dt: float(dt)?, steps: Some(steps) }
+			}
+
+			_ => return Err(format!("invalid input '{}'", text)),
+		};
+
+		Ok(input)
+	}
+}
+
+/// Writes inputs to a file as they happen, see GameContext::start_recording
+pub struct Recorder {
+	out: BufWriter<File>,
+	frame: u64,
+}
+
+impl Recorder {
+	pub fn create(path: &Path) -> io::Result<Self> {
+		let mut out = BufWriter::new(File::create(path)?);
+		writeln!(out, "# wire recording")?;
+
+		Ok(Recorder { out, frame: 0 })
+	}
+
+	/// Writes an input that happened on `tick`. Frames end, and the file is
+	/// flushed, with each update
+	pub fn record(&mut self, tick: u64, input: &Input) -> io::Result<()> {
+		writeln!(self.out, "{} {} {}", self.frame, tick, input)?;
+
+		if let Input::Update { .. } = *input {
+			self.frame += 1;
+			self.out.flush()?;
+		}
+
+		Ok(())
+	}
+
+	pub fn finish(mut self, state: &[String]) -> io::Result<()> {
+		writeln!(self.out, "end")?;
+
+		for line in state {
+			writeln!(self.out, "{}", line)?;
+		}
+
+		self.out.flush()
+	}
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+	Io(io::Error),
+	Parse { line: usize, message: String },
+}
+
+impl From<io::Error> for RecordingError {
+	fn from(e: io::Error) -> Self { RecordingError::Io(e) }
+}
+
+impl fmt::Display for RecordingError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			RecordingError::Io(ref e) => write!(f, "{}", e),
+			RecordingError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+		}
+	}
+}
+
+pub struct Entry {
+	pub frame: u64,
+	pub tick: u64,
+	pub input: Input,
+}
+
+/// A recording being played back into a GameContext
+pub struct Recording {
+	pub entries: Vec<Entry>,
+
+	/// The lines after `end`, or None if the recording was cut short
+	pub final_state: Option<Vec<String>>,
+
+	/// Index of the next entry to play
+	next: usize,
+}
+
+impl Recording {
+	pub fn is_finished(&self) -> bool {
+		self.next >= self.entries.len()
+	}
+
+	/// Plays inputs up to the end of the next frame. Fails without playing
+	/// anything more if the game's tick isn't the one an input was recorded
+	/// on, or an input fails, since from there on it wouldn't play out the
+	/// same. Inputs that failed while recording weren't recorded
+	pub fn play_frame(&mut self, game: &mut GameContext) -> Result<(), String> {
+		while let Some(entry) = self.entries.get(self.next) {
+			if entry.tick != game.get_ticks() {
+				return Err(format!("out of sync at frame {}: '{}' was recorded on tick {}, but the game is on tick {}",
+					entry.frame, entry.input, entry.tick, game.get_ticks()));
+			}
+
+			self.next += 1;
+
+			game.handle_input(entry.input.clone())
+				.map_err(|e| format!("frame {}: {}", entry.frame, e))?;
+
+			if let Input::Update { .. } = entry.input { break }
+		}
+
+		Ok(())
+	}
+}
+
+pub fn read_recording(text: &str) -> Result<Recording, RecordingError> {
+	let mut entries = Vec::new();
+	let mut lines = text.lines().enumerate();
+
+	while let Some((line_no, line)) = lines.next() {
+		if line.trim().is_empty() || line.starts_with('#') { continue }
+
+		if line.trim() == "end" {
+			let state = lines.map(|(_, line)| line.to_string()).collect();
+			return Ok(Recording { entries, final_state: Some(state), next: 0 });
+		}
+
+		let error = |message| RecordingError::Parse { line: line_no + 1, message };
+
+		let mut parts = line.splitn(3, ' ');
+		let frame = parts.next().and_then(|f| f.parse().ok());
+		let tick = parts.next().and_then(|t| t.parse().ok());
+
+		let (frame, tick) = match (frame, tick) {
+			(Some(frame), Some(tick)) => (frame, tick),
+			_ => return Err(error("expected '<frame> <tick> <input>'".to_string())),
+		};
+
+		let input = Input::parse(parts.next().unwrap_or("")).map_err(&error)?;
+		entries.push(Entry { frame, tick, input });
+	}
+
+	Ok(Recording { entries, final_state: None, next: 0 })
+}
+
+pub fn load_recording(path: &Path) -> Result<Recording, RecordingError> {
+	let mut text = String::new();
+	File::open(path)?.read_to_string(&mut text)?;
+	read_recording(&text)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// One of every input. The match is there so that a new variant can't
+	/// be added without adding it here
+	fn every_input() -> Vec<Input> {
+		let inputs = vec![
+			Input::Key(Key::Forward, true),
+			Input::Key(Key::Left, false),
+			Input::Key(Key::Right, true),
+			Input::Key(Key::Back, false),
+			Input::MouseDelta(Vec2::new(0.0125, -0.003)),
+			Input::Frob,
+			Input::Click,
+			Input::RightClick,
+			Input::PrevItem,
+			Input::NextItem,
+			Input::PrevPort,
+			Input::NextPort,
+			Input::ToggleParamEdit,
+			Input::TextInput("hello".to_string()),
+			Input::TextInput("  leading and trailing  ".to_string()),
+			Input::TextInput("".to_string()),
+			Input::Backspace,
+			Input::Return,
+			Input::CancelTyping,
+			Input::CycleBus,
+			Input::ToggleStats,
+			Input::ToggleBreakpoint,
+			Input::ToggleWatch,
+			Input::ContinueFromBreak,
+			Input::TogglePause,
+			Input::SingleStep,
+			Input::SpeedUp,
+			Input::SlowDown,
+			Input::Load("circuits/with space.wire".to_string()),
+			Input::Import("netlist.json".to_string()),
+			Input::Control("{\"cmd\": \"step\", \"count\": 3}".to_string()),
+			Input::Update { dt: 1.0/60.0, steps: None },
+			Input::Update { dt: 0.25, steps: Some(1200) },
+		];
+
+		for input in inputs.iter() {
+			match *input {
+				Input::Key(..) | Input::MouseDelta(_) | Input::Frob | Input::Click | Input::RightClick |
+				Input::PrevItem | Input::NextItem | Input::PrevPort | Input::NextPort |
+				Input::ToggleParamEdit | Input::TextInput(_) | Input::Backspace | Input::Return |
+				Input::CancelTyping | Input::CycleBus | Input::ToggleStats | Input::ToggleBreakpoint |
+				Input::ToggleWatch | Input::ContinueFromBreak | Input::TogglePause | Input::SingleStep |
+				Input::SpeedUp | Input::SlowDown | Input::Load(_) | Input::Import(_) | Input::Control(_) |
+				Input::Update { .. } => {}
+			}
+		}
+
+		inputs
+	}
+
+	#[test]
+	fn inputs_round_trip() {
+		for input in every_input() {
+			let text = input.to_string();
+			let parsed = Input::parse(&text).unwrap_or_else(|e| panic!("'{}': {}", text, e));
+			assert_eq!(format!("{:?}", parsed), format!("{:?}", input), "'{}'", text);
+		}
+	}
+
+	#[test]
+	fn invalid_inputs() {
+		for text in ["", "jump", "key up down", "key forward sideways", "mouse 1", "mouse x 1",
+			"frob now", "load", "update", "update 0.1 -3", "update 0.1 2 3"].iter()
+		{
+			assert!(Input::parse(text).is_err(), "'{}' should be invalid", text);
+		}
+	}
+
+	#[test]
+	fn recording_round_trip() {
+		let inputs = every_input();
+
+		let mut text = "# wire recording\n".to_string();
+		for (i, input) in inputs.iter().enumerate() {
+			text += &format!("{} {} {}\n", i / 4, i * 2, input);
+		}
+		text += "end\ntick 3\nplayer 0 0 0 0\n";
+
+		let recording = read_recording(&text).unwrap();
+		assert_eq!(recording.entries.len(), inputs.len());
+
+		for (i, (entry, input)) in recording.entries.iter().zip(inputs.iter()).enumerate() {
+			assert_eq!((entry.frame, entry.tick), ((i / 4) as u64, (i * 2) as u64));
+			assert_eq!(format!("{:?}", entry.input), format!("{:?}", input));
+		}
+
+		assert_eq!(recording.final_state, Some(vec!["tick 3".to_string(), "player 0 0 0 0".to_string()]));
+		assert!(read_recording("0 0 frob\n").unwrap().final_state.is_none());
+	}
+
+	#[test]
+	fn recording_errors() {
+		let line_of = |text: &str| match read_recording(text) {
+			Err(RecordingError::Parse { line, .. }) => line,
+			_ => panic!("'{}' should fail to read", text),
+		};
+
+		assert_eq!(line_of("# wire recording\n\n0 0 frob\n0 0 wiggle\n"), 4);
+		assert_eq!(line_of("0 0 frob\nframe 0 frob\n"), 2);
+		assert_eq!(line_of("0 0 frob\n0 0 update 0.1\n1\n"), 3);
+		assert_eq!(line_of("0 x frob"), 1);
+	}
+}
@@ -0,0 +1,430 @@
+use wire::*;
+
+use std::i32;
+
+/// What an arithmetic node does when a result doesn't fit in an `i32`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OverflowMode {
+	/// Two's complement wraparound
+	Wrapping,
+	/// Clamp to `i32::MIN`/`i32::MAX`
+	Saturating,
+	/// Output `Error` and raise the error port
+	Error,
+}
+
+param_enum!(OverflowMode {
+	Wrapping => "wrapping",
+	Saturating => "saturating",
+	Error => "error",
+});
+
+impl Default for OverflowMode {
+	fn default() -> Self { OverflowMode::Wrapping }
+}
+
+impl OverflowMode {
+	/// Narrows a widened result according to the mode. `None` means the
+	/// result is an error
+	pub fn resolve(self, wide: i64) -> Option<i32> {
+		if wide >= i32::MIN as i64 && wide <= i32::MAX as i64 {
+			return Some(wide as i32)
+		}
+
+		match self {
+			OverflowMode::Wrapping => Some(wide as i32),
+			OverflowMode::Saturating => Some(if wide < 0 { i32::MIN } else { i32::MAX }),
+			OverflowMode::Error => None,
+		}
+	}
+}
+
+fn result_output(port: u32, value: Option<i32>, error: bool) -> WireValue {
+	match port {
+		0 => value.map(WireValue::Int).unwrap_or(WireValue::Error),
+		1 => WireValue::Bool(error),
+		_ => WireValue::Null,
+	}
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BinaryOp {
+	Sub, Mul, Div, Mod,
+	Min, Max,
+	Shl, Shr,
+}
+
+param_enum!(BinaryOp {
+	Sub => "sub", Mul => "mul", Div => "div", Mod => "mod",
+	Min => "min", Max => "max",
+	Shl => "shl", Shr => "shr",
+});
+
+impl BinaryOp {
+	pub fn symbol(&self) -> &'static str {
+		use self::BinaryOp::*;
+
+		match *self {
+			Sub => "-", Mul => "*", Div => "/", Mod => "%",
+			Min => "min", Max => "max",
+			Shl => "<<", Shr => ">>",
+		}
+	}
+
+	/// Returns the result and whether an error occurred, with `None` meaning
+	/// there's no result. Division or modulo by zero is an error whatever the
+	/// mode. Shift amounts outside `0..32` are masked when wrapping, clamped
+	/// when saturating and an error otherwise
+	pub fn apply(&self, a: i32, b: i32, mode: OverflowMode) -> (Option<i32>, bool) {
+		use self::BinaryOp::*;
+
+		let (a, b) = (a as i64, b as i64);
+
+		let wide = match *self {
+			Sub => a - b,
+			Mul => a * b,
+			Min => a.min(b),
+			Max => a.max(b),
+
+			Div | Mod if b == 0 => return (None, true),
+
+			Div => a / b,
+			Mod => a % b,
+
+			Shl | Shr => {
+				let amount = match mode {
+					OverflowMode::Wrapping => b & 31,
+					OverflowMode::Saturating => b.max(0).min(31),
+					OverflowMode::Error if b < 0 || b > 31 => return (None, true),
+					OverflowMode::Error => b,
+				};
+
+				if *self == Shl { a << amount } else { a >> amount }
+			}
+		};
+
+		let value = mode.resolve(wide);
+		(value, value.is_none())
+	}
+}
+
+/// Two input integer operation with a result and an error output. The
+/// result is Error if either input isn't an Int
+pub struct BinaryOpNode {
+	pub op: BinaryOp,
+	pub overflow: OverflowMode,
+	pub inputs: [WireValue; 2],
+
+	value: Option<i32>,
+	error: bool,
+}
+
+impl BinaryOpNode {
+	pub fn new(op: BinaryOp) -> Self {
+		BinaryOpNode {
+			op,
+			overflow: OverflowMode::default(),
+			inputs: [WireValue::Int(0); 2],
+
+			value: Some(0),
+			error: false,
+		}
+	}
+}
+
+impl WireNode for BinaryOpNode {
+	fn get_type_name(&self) -> &'static str { "BinaryOp" }
+
+	fn get_num_inputs(&self) -> u32 { self.inputs.len() as u32 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Arithmetic,
+			help: "Applies its op to two Ints",
+			inputs: vec![
+				PortInfo::int("a", "Left hand side"),
+				PortInfo::int("b", "Right hand side"),
+			],
+			outputs: vec![
+				PortInfo::int("result", "Error if either input isn't an Int, or the op fails"),
+				PortInfo::bool("error", "Whether the result is Error"),
+			],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port >= self.get_num_inputs() { return }
+		self.inputs[port as usize] = value;
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Int(0) }
+
+	fn update(&mut self, _tick: u64) {
+		let (value, error) = match self.inputs {
+			[WireValue::Int(a), WireValue::Int(b)] => self.op.apply(a, b, self.overflow),
+			_ => (None, true),
+		};
+
+		self.value = value;
+		self.error = error;
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		result_output(port, self.value, self.error)
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		let label = format!("{} {} {}", self.inputs[0], self.op.symbol(), self.inputs[1]);
+		if self.error { label + " !" } else { label }
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![
+			Param::enumeration("op", self.op.to_index(), BinaryOp::NAMES),
+			Param::enumeration("overflow", self.overflow.to_index(), OverflowMode::NAMES),
+		]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		match (name, value) {
+			("op", ParamValue::Enum(v)) => self.op = BinaryOp::from_index(v).unwrap(),
+			("overflow", ParamValue::Enum(v)) => self.overflow = OverflowMode::from_index(v).unwrap(),
+			_ => {}
+		}
+
+		Ok(())
+	}
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum UnaryOp {
+	Neg, Abs,
+}
+
+param_enum!(UnaryOp {
+	Neg => "neg", Abs => "abs",
+});
+
+impl UnaryOp {
+	pub fn symbol(&self) -> &'static str {
+		match *self {
+			UnaryOp::Neg => "-",
+			UnaryOp::Abs => "abs ",
+		}
+	}
+
+	pub fn apply(&self, a: i32, mode: OverflowMode) -> (Option<i32>, bool) {
+		let a = a as i64;
+
+		let wide = match *self {
+			UnaryOp::Neg => -a,
+			UnaryOp::Abs => a.abs(),
+		};
+
+		let value = mode.resolve(wide);
+		(value, value.is_none())
+	}
+}
+
+/// Single input integer operation with a result and an error output. The
+/// result is Error if the input isn't an Int
+pub struct UnaryOpNode {
+	pub op: UnaryOp,
+	pub overflow: OverflowMode,
+	pub input: WireValue,
+
+	value: Option<i32>,
+	error: bool,
+}
+
+impl UnaryOpNode {
+	pub fn new(op: UnaryOp) -> Self {
+		UnaryOpNode {
+			op,
+			overflow: OverflowMode::default(),
+			input: WireValue::Int(0),
+
+			value: Some(0),
+			error: false,
+		}
+	}
+}
+
+impl WireNode for UnaryOpNode {
+	fn get_type_name(&self) -> &'static str { "UnaryOp" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Arithmetic,
+			help: "Applies its op to an Int",
+			inputs: vec![PortInfo::int("in", "")],
+			outputs: vec![
+				PortInfo::int("result", "Error if the input isn't an Int, or the op fails"),
+				PortInfo::bool("error", "Whether the result is Error"),
+			],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port != 0 { return }
+		self.input = value;
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Int(0) }
+
+	fn update(&mut self, _tick: u64) {
+		let (value, error) = match self.input {
+			WireValue::Int(a) => self.op.apply(a, self.overflow),
+			_ => (None, true),
+		};
+
+		self.value = value;
+		self.error = error;
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		result_output(port, self.value, self.error)
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		let label = format!("{}{}", self.op.symbol(), self.input);
+		if self.error { label + " !" } else { label }
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![
+			Param::enumeration("op", self.op.to_index(), UnaryOp::NAMES),
+			Param::enumeration("overflow", self.overflow.to_index(), OverflowMode::NAMES),
+		]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		match (name, value) {
+			("op", ParamValue::Enum(v)) => self.op = UnaryOp::from_index(v).unwrap(),
+			("overflow", ParamValue::Enum(v)) => self.overflow = OverflowMode::from_index(v).unwrap(),
+			_ => {}
+		}
+
+		Ok(())
+	}
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CompareOp {
+	Eq, Ne, Lt, Le, Gt, Ge,
+}
+
+param_enum!(CompareOp {
+	Eq => "eq", Ne => "ne", Lt => "lt", Le => "le", Gt => "gt", Ge => "ge",
+});
+
+impl CompareOp {
+	pub fn symbol(&self) -> &'static str {
+		use self::CompareOp::*;
+
+		match *self {
+			Eq => "==", Ne => "!=",
+			Lt => "<", Le => "<=",
+			Gt => ">", Ge => ">=",
+		}
+	}
+
+	pub fn apply(&self, a: i32, b: i32) -> bool {
+		use self::CompareOp::*;
+
+		match *self {
+			Eq => a == b, Ne => a != b,
+			Lt => a < b, Le => a <= b,
+			Gt => a > b, Ge => a >= b,
+		}
+	}
+}
+
+/// Compares two integers, outputting a `Bool`, or Error if either input
+/// isn't an Int
+pub struct CompareNode {
+	pub op: CompareOp,
+	pub inputs: [WireValue; 2],
+}
+
+impl CompareNode {
+	pub fn new(op: CompareOp) -> Self {
+		CompareNode {
+			op,
+			inputs: [WireValue::Int(0); 2],
+		}
+	}
+}
+
+impl WireNode for CompareNode {
+	fn get_type_name(&self) -> &'static str { "Compare" }
+
+	fn get_num_inputs(&self) -> u32 { self.inputs.len() as u32 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Arithmetic,
+			help: "Compares two Ints",
+			inputs: vec![
+				PortInfo::int("a", "Left hand side"),
+				PortInfo::int("b", "Right hand side"),
+			],
+			outputs: vec![PortInfo::bool("result", "Error if either input isn't an Int")],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port >= self.get_num_inputs() { return }
+		self.inputs[port as usize] = value;
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Int(0) }
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port != 0 { return WireValue::Null }
+
+		match self.inputs {
+			[WireValue::Int(a), WireValue::Int(b)] => WireValue::Bool(self.op.apply(a, b)),
+			_ => WireValue::Error,
+		}
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		format!("{} {} {}", self.inputs[0], self.op.symbol(), self.inputs[1])
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::enumeration("op", self.op.to_index(), CompareOp::NAMES)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Enum(v) = value {
+			self.op = CompareOp::from_index(v).unwrap();
+		}
+
+		Ok(())
+	}
+}
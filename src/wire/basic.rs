@@ -1,10 +1,24 @@
 use wire::*;
 
+use std::i32;
+
 pub struct ConstantNode { pub value: i32 }
 
 impl WireNode for ConstantNode {
+	fn get_type_name(&self) -> &'static str { "Constant" }
+
 	fn get_num_outputs(&self) -> u32 { 1 }
 
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Source,
+			help: "Always outputs the same Int",
+			inputs: Vec::new(),
+			outputs: vec![PortInfo::int("value", "The value param")],
+		}
+	}
+
 	fn get_output(&self, port: u32) -> WireValue {
 		if port == 0 {
 			WireValue::Int(self.value)
@@ -13,133 +27,560 @@ impl WireNode for ConstantNode {
 		}
 	}
 
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		format!("Constant({})", self.value)
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("value", self.value, i32::MIN, i32::MAX)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.value = v;
+		}
+
+		Ok(())
+	}
+}
+
+
+/// Always outputs the same Bool
+pub struct BoolConstantNode { pub value: bool }
+
+impl WireNode for BoolConstantNode {
+	fn get_type_name(&self) -> &'static str { "BoolConstant" }
+
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Source,
+			help: "Always outputs the same Bool",
+			inputs: Vec::new(),
+			outputs: vec![PortInfo::bool("value", "The value param")],
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { WireValue::Bool(self.value) } else { WireValue::Null }
+	}
+
+	fn is_pure(&self) -> bool { true }
+
 	fn get_label(&self) -> String {
 		format!("Constant({})", self.value)
 	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::bool("value", self.value)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Bool(v) = value {
+			self.value = v;
+		}
+
+		Ok(())
+	}
 }
 
 
-pub struct OutputNode { pub name: String, value: WireValue }
+/// Reports the values it receives to an output sink on the context, by
+/// default the one printing to stdout
+pub struct OutputNode {
+	pub name: String,
+	pub sink: String,
+
+	value: WireValue,
+	pending: bool,
+}
 
 impl OutputNode {
 	pub fn new(name: &str) -> Self {
 		OutputNode {
 			name: name.to_string(),
-			value: WireValue::Null
+			sink: STDOUT_SINK.to_string(),
+
+			value: WireValue::Null,
+			pending: false,
 		}
 	}
 }
 
 impl WireNode for OutputNode {	
+	fn get_type_name(&self) -> &'static str { "Output" }
+
 	fn get_num_inputs(&self) -> u32 { 1 }
 
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Output,
+			help: "Reports the values it receives to an output sink, by default stdout",
+			inputs: vec![PortInfo::any("value", "Reported each time it changes")],
+			outputs: Vec::new(),
+		}
+	}
+
 	fn on_input_changed(&mut self, port: u32, value: WireValue) {
 		if port != 0 { return }
 
 		self.value = value;
-		println!("'{}': {:?}", self.name, value);
+		self.pending = true;
+	}
+
+	fn take_output(&mut self) -> Option<PendingOutput> {
+		if !self.pending { return None }
+		self.pending = false;
+
+		Some(PendingOutput {
+			sink: self.sink.clone(),
+			name: self.name.clone(),
+			value: self.value,
+		})
 	}
 
 	fn get_label(&self) -> String {
 		format!("{}:{:?}", self.name, self.value)
 	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![
+			Param::string("name", &self.name),
+			Param::string("sink", &self.sink),
+		]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		match (name, value) {
+			("name", ParamValue::String(v)) => self.name = v,
+			("sink", ParamValue::String(v)) => self.sink = v,
+			_ => {}
+		}
+
+		Ok(())
+	}
 }
 
 
-pub struct CounterNode { count: i32 }
+pub struct CounterNode { pub overflow: OverflowMode, count: Option<i32> }
 
 impl CounterNode {
 	pub fn new() -> Self {
-		CounterNode { count: 0 }
+		CounterNode {
+			overflow: OverflowMode::default(),
+			count: Some(0),
+		}
 	}
 }
 
 impl WireNode for CounterNode {
-	fn get_num_outputs(&self) -> u32 { 1 }
+	fn get_type_name(&self) -> &'static str { "Counter" }
+
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Source,
+			help: "Counts up by one every tick",
+			inputs: Vec::new(),
+			outputs: vec![
+				PortInfo::int("count", "Ticks so far, or Error once it overflows in error mode"),
+				PortInfo::bool("overflow", "Whether the count has overflowed"),
+			],
+		}
+	}
 
-	fn update(&mut self) {
-		self.count += 1;
+	fn update(&mut self, _tick: u64) {
+		// Once errored, the counter stays errored
+		if let Some(count) = self.count {
+			self.count = self.overflow.resolve(count as i64 + 1);
+		}
 	}
 
 	fn get_output(&self, port: u32) -> WireValue {
-		if port != 0 { return WireValue::Null }
-		WireValue::Int(self.count)
+		match port {
+			0 => self.count.map(WireValue::Int).unwrap_or(WireValue::Error),
+			1 => WireValue::Bool(self.count.is_none()),
+			_ => WireValue::Null,
+		}
 	}
 
 	fn get_label(&self) -> String {
-		format!("{}", self.count)
+		match self.count {
+			Some(count) => format!("{}", count),
+			None => "overflow".to_string(),
+		}
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		match self.count {
+			Some(count) => vec![count],
+			None => Vec::new(),
+		}
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		self.count = state.first().cloned();
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::enumeration("overflow", self.overflow.to_index(), OverflowMode::NAMES)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Enum(v) = value {
+			self.overflow = OverflowMode::from_index(v).unwrap();
+		}
+
+		Ok(())
 	}
 }
 
 
-pub struct AddNode { pub inputs: [i32; 2], value: i32 }
+/// Most inputs a node with a variable number of inputs can have
+pub const MAX_INPUTS: u32 = 32;
+
+fn check_inputs(inputs: u32) {
+	assert!(inputs > 0 && inputs <= MAX_INPUTS,
+		"Input count must be between 1 and {}", MAX_INPUTS);
+}
+
+
+/// Sums any number of inputs. Outputs are the sum and an error flag. The
+/// sum is Error if any input isn't an Int, or on overflow in Error mode.
+/// Disconnected inputs count as 0
+pub struct AddNode { pub overflow: OverflowMode, pub inputs: Vec<WireValue>, value: Option<i32> }
 
 impl AddNode {
-	pub fn new() -> Self {
+	pub fn new(inputs: u32) -> Self {
+		check_inputs(inputs);
+
 		AddNode {
-			inputs: [0; 2],
-			value: 0,
+			overflow: OverflowMode::default(),
+			inputs: vec![WireValue::Int(0); inputs as usize],
+			value: Some(0),
+		}
+	}
+
+	/// The sum of `inputs`, or None if any isn't an Int or it overflows in
+	/// Error mode. Shared with compiled circuits
+	pub fn sum(inputs: &[WireValue], overflow: OverflowMode) -> Option<i32> {
+		let mut sum = 0i64;
+
+		for value in inputs.iter() {
+			match *value {
+				WireValue::Int(v) => sum += v as i64,
+				_ => return None,
+			}
 		}
+
+		overflow.resolve(sum)
 	}
 }
 
 impl WireNode for AddNode {
+	fn get_type_name(&self) -> &'static str { "Add" }
+
 	fn get_num_inputs(&self) -> u32 { self.inputs.len() as u32 }
-	fn get_num_outputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Arithmetic,
+			help: "Sums its inputs. Disconnected inputs count as 0",
+			inputs: PortInfo::numbered("in", PortType::Int, "A term of the sum", self.get_num_inputs()),
+			outputs: vec![
+				PortInfo::int("sum", "Error if any input isn't an Int, or on overflow in error mode"),
+				PortInfo::bool("error", "Whether the sum is Error"),
+			],
+		}
+	}
 
 	fn on_input_changed(&mut self, port: u32, value: WireValue) {
 		if port >= self.get_num_inputs() { return }
-		if let WireValue::Int(val) = value {
-			self.inputs[port as usize] = val;
-		}
+		self.inputs[port as usize] = value;
 	}
 
-	fn update(&mut self) {
-		self.value = self.inputs.iter().sum();
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Int(0) }
+
+	fn update(&mut self, _tick: u64) {
+		self.value = AddNode::sum(&self.inputs, self.overflow);
 	}
 
 	fn get_output(&self, port: u32) -> WireValue {
-		if port == 0 {
-			WireValue::Int(self.value)
-		} else {
-			WireValue::Null
+		match port {
+			0 => self.value.map(WireValue::Int).unwrap_or(WireValue::Error),
+			1 => WireValue::Bool(self.value.is_none()),
+			_ => WireValue::Null,
 		}
 	}
 
+	fn is_pure(&self) -> bool { true }
+
 	fn get_label(&self) -> String {
-		format!("{} + {}", self.inputs[0], self.inputs[1])
+		self.inputs.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" + ")
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![
+			Param::int("inputs", self.get_num_inputs() as i32, 1, MAX_INPUTS as i32),
+			Param::enumeration("overflow", self.overflow.to_index(), OverflowMode::NAMES),
+		]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		match (name, value) {
+			("inputs", ParamValue::Int(v)) => self.inputs.resize(v as usize, WireValue::Int(0)),
+			("overflow", ParamValue::Enum(v)) => self.overflow = OverflowMode::from_index(v).unwrap(),
+			_ => {}
+		}
+
+		Ok(())
 	}
 }
 
 
-pub struct AndNode { inputs: [bool; 2] }
+/// True when all of its inputs are. Error if any input isn't a Bool.
+/// Disconnected inputs count as false
+pub struct AndNode { inputs: Vec<WireValue> }
 
 impl AndNode {
-	pub fn new() -> Self {
-		AndNode { inputs: [false; 2] }
+	pub fn new(inputs: u32) -> Self {
+		check_inputs(inputs);
+		AndNode { inputs: vec![WireValue::Bool(false); inputs as usize] }
+	}
+
+	/// True when all of `inputs` are, or Error if any isn't a Bool. Shared
+	/// with compiled circuits
+	pub fn apply(inputs: &[WireValue]) -> WireValue {
+		let mut all = true;
+
+		for value in inputs.iter() {
+			match *value {
+				WireValue::Bool(v) => all &= v,
+				_ => return WireValue::Error,
+			}
+		}
+
+		WireValue::Bool(all)
 	}
 }
 
 impl WireNode for AndNode {
+	fn get_type_name(&self) -> &'static str { "And" }
+
 	fn get_num_inputs(&self) -> u32 { self.inputs.len() as u32 }
 	fn get_num_outputs(&self) -> u32 { 1 }
 
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Logic,
+			help: "True when all of its inputs are. Disconnected inputs count as false",
+			inputs: PortInfo::numbered("in", PortType::Bool, "", self.get_num_inputs()),
+			outputs: vec![PortInfo::bool("out", "Error if any input isn't a Bool")],
+		}
+	}
+
 	fn on_input_changed(&mut self, port: u32, value: WireValue) {
 		if port >= self.get_num_inputs() { return }
-		if let WireValue::Bool(val) = value {
-			self.inputs[port as usize] = val;
+		self.inputs[port as usize] = value;
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { AndNode::apply(&self.inputs) } else { WireValue::Null }
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		self.inputs.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" and ")
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("inputs", self.get_num_inputs() as i32, 1, MAX_INPUTS as i32)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.inputs.resize(v as usize, WireValue::Bool(false));
+		}
+
+		Ok(())
+	}
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GateOp {
+	Or, Xor, Nand, Nor, Xnor,
+}
+
+param_enum!(GateOp {
+	Or => "or", Xor => "xor", Nand => "nand", Nor => "nor", Xnor => "xnor",
+});
+
+impl GateOp {
+	/// The gate's output for `inputs`, or Error if any isn't a Bool
+	pub fn apply(&self, inputs: &[WireValue]) -> WireValue {
+		let mut high = 0;
+
+		for value in inputs.iter() {
+			match *value {
+				WireValue::Bool(v) => if v { high += 1 },
+				_ => return WireValue::Error,
+			}
 		}
+
+		let (any, all, odd) = (high > 0, high == inputs.len(), high % 2 == 1);
+
+		WireValue::Bool(match *self {
+			GateOp::Or => any,
+			GateOp::Xor => odd,
+			GateOp::Nand => !all,
+			GateOp::Nor => !any,
+			GateOp::Xnor => !odd,
+		})
+	}
+}
+
+/// The other logic gates, with And being a node of its own. Xor is true when
+/// an odd number of inputs are. Error if any input isn't a Bool.
+/// Disconnected inputs count as false
+pub struct GateNode { pub op: GateOp, inputs: Vec<WireValue> }
+
+impl GateNode {
+	pub fn new(op: GateOp, inputs: u32) -> Self {
+		check_inputs(inputs);
+		GateNode { op, inputs: vec![WireValue::Bool(false); inputs as usize] }
 	}
+}
+
+impl WireNode for GateNode {
+	fn get_type_name(&self) -> &'static str { "Gate" }
+
+	fn get_num_inputs(&self) -> u32 { self.inputs.len() as u32 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Logic,
+			help: match self.op {
+				GateOp::Or => "True when any input is",
+				GateOp::Xor => "True when an odd number of inputs are",
+				GateOp::Nand => "False when all inputs are true",
+				GateOp::Nor => "True when no input is",
+				GateOp::Xnor => "True when an even number of inputs are",
+			},
+			inputs: PortInfo::numbered("in", PortType::Bool, "Counts as false when disconnected", self.get_num_inputs()),
+			outputs: vec![PortInfo::bool("out", "Error if any input isn't a Bool")],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port >= self.get_num_inputs() { return }
+		self.inputs[port as usize] = value;
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
 
 	fn get_output(&self, port: u32) -> WireValue {
-		if port == 0 {
-			WireValue::Bool(self.inputs[0] & self.inputs[1])
-		} else {
-			WireValue::Null
+		if port == 0 { self.op.apply(&self.inputs) } else { WireValue::Null }
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		let separator = format!(" {} ", self.op.name());
+		self.inputs.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(&separator)
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![
+			Param::enumeration("op", self.op.to_index(), GateOp::NAMES),
+			Param::int("inputs", self.get_num_inputs() as i32, 1, MAX_INPUTS as i32),
+		]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		match (name, value) {
+			("op", ParamValue::Enum(v)) => self.op = GateOp::from_index(v).unwrap(),
+			("inputs", ParamValue::Int(v)) => self.inputs.resize(v as usize, WireValue::Bool(false)),
+			_ => {}
 		}
+
+		Ok(())
 	}
+}
+
+
+/// Inverts a Bool, or is Error if the input isn't one. A disconnected input
+/// counts as false, so a lone Not is a constant true
+pub struct NotNode { input: WireValue }
+
+impl NotNode {
+	pub fn new() -> Self {
+		NotNode { input: WireValue::Bool(false) }
+	}
+}
+
+impl WireNode for NotNode {
+	fn get_type_name(&self) -> &'static str { "Not" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Logic,
+			help: "Inverts a Bool. A lone Not is a constant true",
+			inputs: vec![PortInfo::bool("in", "Counts as false when disconnected")],
+			outputs: vec![PortInfo::bool("out", "Error if the input isn't a Bool")],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port == 0 { self.input = value; }
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn get_output(&self, port: u32) -> WireValue {
+		match (port, self.input) {
+			(0, WireValue::Bool(v)) => WireValue::Bool(!v),
+			(0, _) => WireValue::Error,
+			_ => WireValue::Null,
+		}
+	}
+
+	fn is_pure(&self) -> bool { true }
 
 	fn get_label(&self) -> String {
-		format!("{} and {}", self.inputs[0], self.inputs[1])
+		format!("not {}", self.input)
 	}
-}
\ No newline at end of file
+}
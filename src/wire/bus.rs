@@ -0,0 +1,125 @@
+use wire::*;
+
+/// How an input driven by several outputs at once decides its value.
+/// Drivers that are Null or HighZ aren't driving and are ignored, and the
+/// bus is Null when none are. An Error from any driver is passed on
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BusResolution {
+	/// Drivers must agree, otherwise the bus is Error and in conflict
+	Conflict,
+	/// Bools are ANDed and Ints bitwise ANDed together
+	WiredAnd,
+	/// Bools are ORed and Ints bitwise ORed together
+	WiredOr,
+}
+
+param_enum!(BusResolution {
+	Conflict => "conflict",
+	WiredAnd => "and",
+	WiredOr => "or",
+});
+
+impl BusResolution {
+	/// Returns the value of the bus, and whether the drivers conflict.
+	/// Mixing Bools and Ints is always a conflict
+	pub fn resolve<I: Iterator<Item=WireValue>>(&self, drivers: I) -> (WireValue, bool) {
+		let mut value = WireValue::Null;
+
+		for driver in drivers {
+			let combined = match (value, driver) {
+				(_, WireValue::Null) | (_, WireValue::HighZ) => continue,
+				(WireValue::Error, _) | (_, WireValue::Error) => WireValue::Error,
+				(WireValue::Null, v) => v,
+
+				(a, b) => match (*self, a, b) {
+					(BusResolution::Conflict, a, b) if a == b => a,
+					(BusResolution::WiredAnd, WireValue::Bool(a), WireValue::Bool(b)) => WireValue::Bool(a && b),
+					(BusResolution::WiredAnd, WireValue::Int(a), WireValue::Int(b)) => WireValue::Int(a & b),
+					(BusResolution::WiredOr, WireValue::Bool(a), WireValue::Bool(b)) => WireValue::Bool(a || b),
+					(BusResolution::WiredOr, WireValue::Int(a), WireValue::Int(b)) => WireValue::Int(a | b),
+					_ => return (WireValue::Error, true),
+				},
+			};
+
+			value = combined;
+		}
+
+		(value, false)
+	}
+}
+
+/// An input that accepts any number of drivers, see WireContext::set_bus
+#[derive(Copy, Clone, Debug)]
+pub struct Bus {
+	pub to: (u32, u32),
+	pub resolution: BusResolution,
+
+	pub(super) conflict: bool,
+	pub(super) dirty: bool,
+}
+
+impl Bus {
+	/// Whether the drivers disagreed when the bus was last resolved
+	pub fn in_conflict(&self) -> bool { self.conflict }
+}
+
+
+/// Passes Data through while Enable is true, and is HighZ otherwise, so that
+/// several buffers can take turns driving a bus
+pub struct TriStateNode {
+	input: WireValue,
+	enable: bool,
+}
+
+impl TriStateNode {
+	pub fn new() -> Self {
+		TriStateNode {
+			input: WireValue::Null,
+			enable: false,
+		}
+	}
+}
+
+impl WireNode for TriStateNode {
+	fn get_type_name(&self) -> &'static str { "TriState" }
+
+	fn get_num_inputs(&self) -> u32 { 2 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Routing,
+			help: "Drives Data onto a bus while enabled, and lets go otherwise",
+			inputs: vec![
+				PortInfo::any("Data", ""),
+				PortInfo::bool("Enable", ""),
+			],
+			outputs: vec![PortInfo::any("out", "Data, or HighZ when not enabled")],
+		}
+	}
+
+	fn get_input_default(&self, port: u32) -> WireValue {
+		if port == 1 { WireValue::Bool(false) } else { WireValue::Null }
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		match (port, value) {
+			(0, value) => self.input = value,
+			(1, WireValue::Bool(val)) => self.enable = val,
+			_ => {}
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		match port {
+			0 if self.enable => self.input,
+			0 => WireValue::HighZ,
+			_ => WireValue::Null,
+		}
+	}
+
+	fn get_label(&self) -> String {
+		if self.enable { format!("buf {}", self.input) } else { "buf Z".to_string() }
+	}
+}
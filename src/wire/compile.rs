@@ -0,0 +1,555 @@
+//! Running a circuit as a flat program instead of through its nodes. The
+//! built in pure nodes are lowered to ops on an array of value registers,
+//! and connections to copies between registers, so a step is a few tight
+//! loops with no virtual calls or searching for nodes. Every other node is
+//! kept as it is and called through WireNode as usual.
+//!
+//! Running compiled gives the same outputs and sink values as step, but
+//! observers, breakpoints and stats aren't updated until the context is
+//! taken back with into_context.
+
+use wire::*;
+
+/// Where a connection delivers its value
+#[derive(Copy, Clone, Debug)]
+enum Target {
+	/// An input register of a compiled node
+	Register { reg: usize, default: WireValue, int_only: bool },
+	/// An input of a node that isn't compiled, by index into the nodes
+	Node { index: usize, port: u32 },
+	Bus(usize),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Link {
+	/// The register holding the source output
+	source: usize,
+	target: Target,
+
+	value: WireValue,
+	changed: bool,
+}
+
+#[derive(Clone, Debug)]
+struct BusLink {
+	target: Target,
+	resolution: BusResolution,
+	/// Indices into the links
+	drivers: Vec<usize>,
+
+	dirty: bool,
+	conflict: bool,
+}
+
+/// Inputs are a run of registers starting at `inputs`, and outputs at `out`
+#[derive(Copy, Clone, Debug)]
+enum Op {
+	Add { inputs: usize, count: usize, overflow: OverflowMode, out: usize },
+	And { inputs: usize, count: usize, out: usize },
+	Gate { op: GateOp, inputs: usize, count: usize, out: usize },
+	Not { input: usize, out: usize },
+	Binary { op: BinaryOp, overflow: OverflowMode, inputs: usize, out: usize },
+	Unary { op: UnaryOp, overflow: OverflowMode, input: usize, out: usize },
+	Compare { op: CompareOp, inputs: usize, out: usize },
+	Mux { inputs: usize, count: usize, out: usize },
+}
+
+/// Output ports of a node that isn't compiled, read into registers after
+/// each update
+#[derive(Copy, Clone, Debug)]
+struct Read {
+	index: usize,
+	port: u32,
+	reg: usize,
+}
+
+/// A circuit lowered by compile, see the module docs
+pub struct CompiledCircuit {
+	ctx: WireContext,
+
+	values: Vec<WireValue>,
+	ops: Vec<Op>,
+	links: Vec<Link>,
+	buses: Vec<BusLink>,
+
+	/// Indices of the nodes that are run through WireNode
+	fallback: Vec<usize>,
+	reads: Vec<Read>,
+
+	/// First output register of each node, in the same order as the nodes
+	outputs: Vec<usize>,
+	/// First input register of each compiled node
+	inputs: Vec<Option<usize>>,
+}
+
+fn enum_param(node: &WireNode, name: &str) -> usize {
+	node.get_params().into_iter()
+		.find(|p| p.name == name)
+		.and_then(|p| match p.value {
+			ParamValue::Enum(v) => Some(v),
+			_ => None,
+		})
+		.unwrap_or(0)
+}
+
+fn overflow_param(node: &WireNode) -> OverflowMode {
+	OverflowMode::from_index(enum_param(node, "overflow")).unwrap_or_default()
+}
+
+/// Lowers a node to an op, or returns None if it has to be run as it is.
+/// Constants need no op, their output registers never change
+fn lower(node: &WireNode, inputs: usize, out: usize) -> Option<Option<Op>> {
+	let count = node.get_num_inputs() as usize;
+
+	let op = match node.get_type_name() {
+		"Constant" | "BoolConstant" => return Some(None),
+
+		"Add" => Op::Add { inputs, count, overflow: overflow_param(node), out },
+		"And" => Op::And { inputs, count, out },
+		"Gate" => Op::Gate { op: GateOp::from_index(enum_param(node, "op"))?, inputs, count, out },
+		"Not" => Op::Not { input: inputs, out },
+		"BinaryOp" => Op::Binary { op: BinaryOp::from_index(enum_param(node, "op"))?, overflow: overflow_param(node), inputs, out },
+		"UnaryOp" => Op::Unary { op: UnaryOp::from_index(enum_param(node, "op"))?, overflow: overflow_param(node), input: inputs, out },
+		"Compare" => Op::Compare { op: CompareOp::from_index(enum_param(node, "op"))?, inputs, out },
+		"Mux" => Op::Mux { inputs, count: count - 1, out },
+
+		_ => return None,
+	};
+
+	Some(Some(op))
+}
+
+fn result(values: &mut [WireValue], out: usize, (value, error): (Option<i32>, bool)) {
+	values[out] = value.map_or(WireValue::Error, WireValue::Int);
+	values[out + 1] = WireValue::Bool(error);
+}
+
+impl Op {
+	fn run(&self, values: &mut [WireValue]) {
+		match *self {
+			Op::Add { inputs, count, overflow, out } => {
+				let value = AddNode::sum(&values[inputs..inputs + count], overflow);
+				result(values, out, (value, value.is_none()));
+			}
+
+			Op::And { inputs, count, out } => {
+				values[out] = AndNode::apply(&values[inputs..inputs + count]);
+			}
+
+			Op::Gate { op, inputs, count, out } => {
+				values[out] = op.apply(&values[inputs..inputs + count]);
+			}
+
+			Op::Not { input, out } => {
+				values[out] = match values[input] {
+					WireValue::Bool(v) => WireValue::Bool(!v),
+					_ => WireValue::Error,
+				};
+			}
+
+			Op::Binary { op, overflow, inputs, out } => {
+				let value = match (values[inputs], values[inputs + 1]) {
+					(WireValue::Int(a), WireValue::Int(b)) => op.apply(a, b, overflow),
+					_ => (None, true),
+				};
+
+				result(values, out, value);
+			}
+
+			Op::Unary { op, overflow, input, out } => {
+				let value = match values[input] {
+					WireValue::Int(a) => op.apply(a, overflow),
+					_ => (None, true),
+				};
+
+				result(values, out, value);
+			}
+
+			Op::Compare { op, inputs, out } => {
+				values[out] = match (values[inputs], values[inputs + 1]) {
+					(WireValue::Int(a), WireValue::Int(b)) => WireValue::Bool(op.apply(a, b)),
+					_ => WireValue::Error,
+				};
+			}
+
+			Op::Mux { inputs, count, out } => {
+				values[out] = match values[inputs + count] {
+					WireValue::Int(select) if select >= 0 && (select as usize) < count =>
+						values[inputs + select as usize],
+					WireValue::Error => WireValue::Error,
+					_ => WireValue::Null,
+				};
+			}
+		}
+	}
+}
+
+/// Lowers a circuit, see the module docs. Compiled nodes start from what
+/// their connections last carried, which is what they hold unless a change
+/// is waiting to be delivered, in which case it's delivered before they use
+/// it anyway
+pub fn compile(ctx: WireContext) -> CompiledCircuit {
+	let mut ctx = ctx;
+
+	// Step drops connections to nodes that are gone before anything else
+	let ids: Vec<u32> = ctx.nodes.iter().map(|n| n.0).collect();
+	ctx.remove_connections(|c| ids.binary_search(&c.input_node).is_err() || ids.binary_search(&c.output_node).is_err());
+
+	let mut values = Vec::new();
+	let mut ops = Vec::new();
+	let mut fallback = Vec::new();
+	let mut outputs = Vec::new();
+	let mut inputs = Vec::new();
+
+	for (index, &(_, ref node)) in ctx.nodes.iter().enumerate() {
+		let out = values.len();
+		for port in 0..node.get_num_outputs() {
+			values.push(node.get_output(port));
+		}
+
+		outputs.push(out);
+
+		let first_input = values.len();
+		match lower(&**node, first_input, out) {
+			Some(op) => {
+				for port in 0..node.get_num_inputs() {
+					values.push(node.get_input_default(port));
+				}
+
+				// Mux keeps its select in its state, as it can't always be
+				// worked out from the input
+				if let Some(Op::Mux { inputs, count, .. }) = op {
+					values[inputs + count] = match *node.save_state() {
+						[_, error] if error != 0 => WireValue::Error,
+						[select, _] => WireValue::Int(select),
+						_ => values[inputs + count],
+					};
+				}
+
+				ops.extend(op);
+				inputs.push(Some(first_input));
+			}
+
+			None => {
+				fallback.push(index);
+				inputs.push(None);
+			}
+		}
+	}
+
+	let index_of = |node_id: u32| ctx.nodes.binary_search_by_key(&node_id, |n| n.0).unwrap();
+
+	let target_of = |to: (u32, u32)| {
+		let index = index_of(to.0);
+
+		match inputs[index] {
+			Some(first) => Target::Register {
+				reg: first + to.1 as usize,
+				default: ctx.nodes[index].1.get_input_default(to.1),
+				int_only: ctx.nodes[index].1.get_type_name() == "Mux" && to.1 == ctx.nodes[index].1.get_num_inputs() - 1,
+			},
+
+			None => Target::Node { index, port: to.1 },
+		}
+	};
+
+	let mut buses: Vec<BusLink> = ctx.buses.iter()
+		.map(|b| BusLink {
+			target: target_of(b.to),
+			resolution: b.resolution,
+			drivers: Vec::new(),
+
+			dirty: b.dirty,
+			conflict: b.conflict,
+		})
+		.collect();
+
+	let mut links = Vec::new();
+	let mut reads: Vec<Read> = Vec::new();
+
+	for (i, c) in ctx.connections.iter().enumerate() {
+		let from = index_of(c.input_node);
+		let source = outputs[from] + c.input_port as usize;
+
+		if inputs[from].is_none() && !reads.iter().any(|r| r.reg == source) {
+			reads.push(Read { index: from, port: c.input_port, reg: source });
+		}
+
+		let target = match ctx.buses.iter().position(|b| b.to == c.to()) {
+			Some(bus) => {
+				buses[bus].drivers.push(i);
+				Target::Bus(bus)
+			}
+
+			None => target_of(c.to()),
+		};
+
+		links.push(Link { source, target, value: c.value, changed: c.changed });
+	}
+
+	let mut compiled = CompiledCircuit {
+		ctx, values, ops, links, buses, fallback, reads, outputs, inputs,
+	};
+
+	// Give compiled inputs what they were last delivered
+	for i in 0..compiled.links.len() {
+		let link = compiled.links[i];
+		if let Target::Register { .. } = link.target {
+			compiled.store(link.target, link.value);
+		}
+	}
+
+	for i in 0..compiled.buses.len() {
+		if let Target::Register { .. } = compiled.buses[i].target {
+			let (value, _) = compiled.resolve(i);
+			compiled.store(compiled.buses[i].target, value);
+		}
+	}
+
+	compiled
+}
+
+impl CompiledCircuit {
+	/// Delivers a value the way WireContext does, substituting the default
+	/// for Null and HighZ
+	fn store(&mut self, target: Target, value: WireValue) {
+		match target {
+			Target::Register { reg, default, int_only } => {
+				let value = match value {
+					WireValue::Null | WireValue::HighZ => default,
+					value => value,
+				};
+
+				// Mux takes its select as a number, and is Error while it's Error
+				self.values[reg] = match value.to_int() {
+					Some(v) if int_only => WireValue::Int(v),
+					_ => value,
+				};
+			}
+
+			Target::Node { index, port } => deliver(&mut *self.ctx.nodes[index].1, port, value),
+			Target::Bus(bus) => self.buses[bus].dirty = true,
+		}
+	}
+
+	fn resolve(&self, bus: usize) -> (WireValue, bool) {
+		let bus = &self.buses[bus];
+		bus.resolution.resolve(bus.drivers.iter().map(|&l| self.links[l].value))
+	}
+
+	/// Same as WireContext::step
+	pub fn step(&mut self) {
+		for i in 0..self.links.len() {
+			if self.links[i].changed {
+				self.links[i].changed = false;
+				let link = self.links[i];
+				self.store(link.target, link.value);
+			}
+		}
+
+		for i in 0..self.buses.len() {
+			if !self.buses[i].dirty { continue }
+
+			let (value, conflict) = self.resolve(i);
+			self.buses[i].dirty = false;
+			self.buses[i].conflict = conflict;
+
+			let target = self.buses[i].target;
+			self.store(target, value);
+		}
+
+		self.ctx.send_outputs();
+
+		let ticks = self.ctx.ticks;
+		for &index in self.fallback.iter() {
+			self.ctx.nodes[index].1.update(ticks);
+		}
+
+		for op in self.ops.iter() {
+			op.run(&mut self.values);
+		}
+
+		for read in self.reads.iter() {
+			self.values[read.reg] = self.ctx.nodes[read.index].1.get_output(read.port);
+		}
+
+		for link in self.links.iter_mut() {
+			let value = self.values[link.source];
+
+			if value != link.value {
+				link.value = value;
+				link.changed = true;
+			}
+		}
+
+		self.ctx.ticks += 1;
+	}
+
+	pub fn run(&mut self, ticks: u64) {
+		for _ in 0..ticks {
+			self.step();
+		}
+	}
+
+	pub fn get_ticks(&self) -> u64 { self.ctx.ticks }
+
+	/// How many nodes were lowered to ops rather than run as they are
+	pub fn compiled_count(&self) -> usize {
+		self.ctx.nodes.len() - self.fallback.len()
+	}
+
+	pub fn get_output(&self, node_id: u32, port: u32) -> WireValue {
+		let index = match self.ctx.nodes.binary_search_by_key(&node_id, |n| n.0) {
+			Ok(index) => index,
+			Err(_) => return WireValue::Null,
+		};
+
+		let node = &self.ctx.nodes[index].1;
+
+		match self.inputs[index] {
+			_ if port >= node.get_num_outputs() => WireValue::Null,
+			Some(_) => self.values[self.outputs[index] + port as usize],
+			None => node.get_output(port),
+		}
+	}
+
+	/// Hands the circuit back, with the compiled nodes given the inputs they
+	/// had here so they carry on from the same state
+	pub fn into_context(self) -> WireContext {
+		let mut ctx = self.ctx;
+		let ticks = ctx.ticks;
+
+		for (index, &mut (_, ref mut node)) in ctx.nodes.iter_mut().enumerate() {
+			if let Some(first) = self.inputs[index] {
+				for port in 0..node.get_num_inputs() {
+					node.on_input_changed(port, self.values[first + port as usize]);
+				}
+
+				node.update(ticks);
+			}
+		}
+
+		for (c, link) in ctx.connections.iter_mut().zip(self.links.iter()) {
+			c.value = link.value;
+			c.changed = link.changed;
+		}
+
+		for (b, bus) in ctx.buses.iter_mut().zip(self.buses.iter()) {
+			b.dirty = bus.dirty;
+			b.conflict = bus.conflict;
+		}
+
+		ctx
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::i32;
+
+	fn choose(rng: &mut Rng, count: usize) -> usize {
+		rng.range(0, count as i32 - 1) as usize
+	}
+
+	/// A random circuit of the node types that are lowered, fed by Random
+	/// nodes so values keep changing. Inputs are connected to any output,
+	/// so there are type mismatches and loops, or left disconnected
+	fn random_circuit(seed: u64) -> WireContext {
+		let mut rng = Rng::new(seed);
+		let mut ctx = WireContext::with_seed(seed);
+
+		// Flips every tick, as a clock for the Random nodes
+		let clock = ctx.add_node(NotNode::new());
+		ctx.add_connection((clock, 0), (clock, 0));
+
+		let randoms = vec![
+			ctx.add_node(RandomNode::new(RandomOutput::Int { min: -4, max: 4 }, None)),
+			ctx.add_node(RandomNode::new(RandomOutput::Int { min: i32::MIN, max: i32::MAX }, None)),
+			ctx.add_node(RandomNode::new(RandomOutput::Bool, None)),
+			ctx.add_node(RandomNode::new(RandomOutput::Bool, None)),
+		];
+
+		for &id in randoms.iter() {
+			ctx.add_connection((clock, 0), (id, 0));
+		}
+
+		ctx.add_node(CounterNode::new());
+		ctx.add_node(ConstantNode { value: i32::MAX });
+		ctx.add_node(BoolConstantNode { value: true });
+
+		let first_lowered = ctx.next_id;
+
+		for _ in 0..20 {
+			let inputs = rng.range(1, 3) as u32;
+
+			let node: Box<WireNode> = match choose(&mut rng, 8) {
+				0 => box AddNode::new(inputs),
+				1 => box AndNode::new(inputs),
+				2 => box GateNode::new(GateOp::from_index(choose(&mut rng, GateOp::NAMES.len())).unwrap(), inputs),
+				3 => box NotNode::new(),
+				4 => box BinaryOpNode::new(BinaryOp::from_index(choose(&mut rng, BinaryOp::NAMES.len())).unwrap()),
+				5 => box UnaryOpNode::new(UnaryOp::from_index(choose(&mut rng, UnaryOp::NAMES.len())).unwrap()),
+				6 => box CompareNode::new(CompareOp::from_index(choose(&mut rng, CompareOp::NAMES.len())).unwrap()),
+				_ => box MuxNode::new(inputs),
+			};
+
+			let id = ctx.next_id;
+			ctx.insert_node(id, node);
+
+			let overflow = ParamValue::Enum(choose(&mut rng, OverflowMode::NAMES.len()));
+			if ctx.get_node(id).unwrap().get_params().iter().any(|p| p.name == "overflow") {
+				ctx.set_param(id, "overflow", overflow).unwrap();
+			}
+		}
+
+		let ids: Vec<(u32, u32)> = ctx.nodes.iter().map(|n| (n.0, n.1.get_num_outputs())).collect();
+		let num_sources = first_lowered as usize;
+
+		for &(id, _) in ids[num_sources..].iter() {
+			for port in 0..ctx.get_node(id).unwrap().get_num_inputs() {
+				if choose(&mut rng, 5) == 0 { continue }
+
+				// Mostly from the sources, so there's plenty of overflow
+				let from = match choose(&mut rng, 4) {
+					0 => choose(&mut rng, ids.len()),
+					_ => choose(&mut rng, num_sources),
+				};
+
+				let (from, outputs) = ids[from];
+				ctx.add_connection((from, choose(&mut rng, outputs as usize) as u32), (id, port));
+			}
+		}
+
+		ctx
+	}
+
+	#[test]
+	fn compiled_matches_step() {
+		for seed in 0..100 {
+			// Compiling part way through a run has to carry on from there
+			let warmup = seed % 4;
+
+			let mut ctx = random_circuit(seed);
+			ctx.run(warmup);
+
+			let mut compiled = random_circuit(seed);
+			compiled.run(warmup);
+			let mut compiled = compile(compiled);
+
+			for tick in 0..40 {
+				ctx.step();
+				compiled.step();
+
+				for &(id, ref node) in ctx.nodes.iter() {
+					for port in 0..node.get_num_outputs() {
+						assert_eq!(node.get_output(port), compiled.get_output(id, port),
+							"seed {}, tick {}: {} {} output #{}", seed, tick + warmup, node.get_type_name(), id, port);
+					}
+				}
+			}
+		}
+	}
+}
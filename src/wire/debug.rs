@@ -0,0 +1,187 @@
+use wire::*;
+
+use std::fmt;
+
+pub type BreakpointId = u32;
+pub type WatchId = u32;
+
+/// When a breakpoint on an output port stops the simulation. Equals and
+/// Exceeds only trigger when they become true, so that continuing doesn't
+/// stop again straight away
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BreakCondition {
+	Equals(WireValue),
+	Changes,
+	/// A Bool going from false (or anything else) to true
+	Rising,
+	/// An Int going above the value
+	Exceeds(i32),
+}
+
+impl BreakCondition {
+	fn holds(&self, value: WireValue) -> bool {
+		match (*self, value) {
+			(BreakCondition::Equals(v), value) => value == v,
+			(BreakCondition::Exceeds(limit), WireValue::Int(v)) => v > limit,
+			(BreakCondition::Rising, WireValue::Bool(v)) => v,
+			_ => false,
+		}
+	}
+
+	pub fn triggers(&self, last: WireValue, value: WireValue) -> bool {
+		match *self {
+			BreakCondition::Changes => value != last,
+			_ => self.holds(value) && !self.holds(last),
+		}
+	}
+
+	/// Parses `changes`, `rising`, `equals <value>` or `exceeds <int>`
+	pub fn parse(text: &str) -> Result<BreakCondition, String> {
+		let mut words = text.split_whitespace();
+
+		let condition = match (words.next(), words.next()) {
+			(Some("changes"), None) => BreakCondition::Changes,
+			(Some("rising"), None) => BreakCondition::Rising,
+
+			(Some("equals"), Some(value)) => BreakCondition::Equals(WireValue::parse(value)
+				.ok_or(format!("invalid value '{}'", value))?),
+
+			(Some("exceeds"), Some(value)) => BreakCondition::Exceeds(value.parse()
+				.map_err(|_| format!("invalid integer '{}'", value))?),
+
+			_ => return Err(format!("invalid condition '{}'", text)),
+		};
+
+		match words.next() {
+			Some(_) => Err(format!("invalid condition '{}'", text)),
+			None => Ok(condition),
+		}
+	}
+}
+
+impl fmt::Display for BreakCondition {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			BreakCondition::Equals(v) => write!(f, "equals {}", v),
+			BreakCondition::Changes => write!(f, "changes"),
+			BreakCondition::Rising => write!(f, "rising"),
+			BreakCondition::Exceeds(v) => write!(f, "exceeds {}", v),
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Breakpoint {
+	pub id: BreakpointId,
+	/// An output port
+	pub port: (u32, u32),
+	pub condition: BreakCondition,
+
+	pub(super) last: WireValue,
+}
+
+/// The breakpoint that stopped the simulation, see WireContext::get_break
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BreakHit {
+	pub breakpoint: BreakpointId,
+	pub port: (u32, u32),
+	pub value: WireValue,
+	pub tick: u64,
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchOp {
+	Add,
+	Binary(BinaryOp),
+	Compare(CompareOp),
+}
+
+impl WatchOp {
+	fn parse(text: &str) -> Option<WatchOp> {
+		if text == "+" { return Some(WatchOp::Add) }
+
+		let binary = (0..BinaryOp::NAMES.len()).filter_map(BinaryOp::from_index)
+			.find(|op| op.symbol() == text)
+			.map(WatchOp::Binary);
+
+		binary.or_else(|| (0..CompareOp::NAMES.len()).filter_map(CompareOp::from_index)
+			.find(|op| op.symbol() == text)
+			.map(WatchOp::Compare))
+	}
+
+	fn apply(&self, a: WireValue, b: WireValue) -> WireValue {
+		let (a, b) = match (a, b) {
+			(WireValue::Int(a), WireValue::Int(b)) => (a, b),
+			_ => return WireValue::Error,
+		};
+
+		match *self {
+			WatchOp::Add => WireValue::Int(a.wrapping_add(b)),
+			WatchOp::Binary(op) => op.apply(a, b, OverflowMode::Wrapping).0
+				.map_or(WireValue::Error, WireValue::Int),
+			WatchOp::Compare(op) => WireValue::Bool(op.apply(a, b)),
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WatchTerm {
+	/// An output port
+	Port(u32, u32),
+	Value(WireValue),
+}
+
+/// Output ports and values combined by integer operators, like
+/// `3:0 - 4:0 >= 10`. There's no precedence, operators apply left to right
+#[derive(Clone, Debug, PartialEq)]
+pub struct WatchExpr {
+	pub first: WatchTerm,
+	pub rest: Vec<(WatchOp, WatchTerm)>,
+}
+
+impl WatchExpr {
+	pub fn parse(text: &str) -> Result<WatchExpr, String> {
+		let tokens: Vec<_> = text.split_whitespace().collect();
+
+		let parse_term = |token: &str| -> Result<WatchTerm, String> {
+			if let Some(port) = parse_port(token) {
+				return Ok(WatchTerm::Port(port.0, port.1))
+			}
+
+			WireValue::parse(token).map(WatchTerm::Value)
+				.ok_or(format!("expected a port or value, found '{}'", token))
+		};
+
+		if tokens.len() % 2 == 0 {
+			return Err("expected '<term> [<op> <term>]...'".to_string());
+		}
+
+		let mut rest = Vec::new();
+		for pair in tokens[1..].chunks(2) {
+			let op = WatchOp::parse(pair[0]).ok_or(format!("unknown operator '{}'", pair[0]))?;
+			rest.push((op, parse_term(pair[1])?));
+		}
+
+		Ok(WatchExpr { first: parse_term(tokens[0])?, rest })
+	}
+
+	/// Ports that don't exist are Null
+	pub fn evaluate(&self, ctx: &WireContext) -> WireValue {
+		let value_of = |term: WatchTerm| match term {
+			WatchTerm::Port(node, port) => ctx.get_node(node)
+				.map_or(WireValue::Null, |n| n.get_output(port)),
+			WatchTerm::Value(value) => value,
+		};
+
+		self.rest.iter().fold(value_of(self.first), |acc, &(op, term)| op.apply(acc, value_of(term)))
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Watch {
+	pub id: WatchId,
+	/// The expression as it was written
+	pub text: String,
+	pub expr: WatchExpr,
+}
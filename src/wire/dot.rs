@@ -0,0 +1,89 @@
+//! Graphviz export. Each node is drawn as a box showing its type and label,
+//! and each connection as an edge labelled `out->in: value`. Connections
+//! into a bus in conflict are drawn red
+//!
+//! ```text
+//! wire --dot circuit.wire | dot -Tsvg > circuit.svg
+//! ```
+
+use wire::*;
+
+use std::io::{self, Write};
+
+/// A named group of nodes drawn inside a box of its own
+#[derive(Clone, Debug)]
+pub struct DotCluster {
+	pub name: String,
+	pub nodes: Vec<u32>,
+}
+
+fn escape(text: &str) -> String {
+	let mut escaped = String::new();
+
+	for c in text.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			'\n' => escaped.push_str("\\n"),
+			c => escaped.push(c),
+		}
+	}
+
+	escaped
+}
+
+fn write_node<W: Write>(out: &mut W, indent: &str, id: u32, node: &WireNode) -> io::Result<()> {
+	let label = node.get_label();
+
+	let text = if label.is_empty() {
+		format!("{} {}", id, node.get_type_name())
+	} else {
+		format!("{} {}\n{}", id, node.get_type_name(), label)
+	};
+
+	writeln!(out, "{}n{} [label=\"{}\"];", indent, id, escape(&text))
+}
+
+/// Writes the circuit as a DOT digraph, with the nodes in each cluster
+/// grouped together. Nodes in more than one cluster go in the first
+pub fn write_dot<W: Write>(ctx: &WireContext, clusters: &[DotCluster], out: &mut W) -> io::Result<()> {
+	writeln!(out, "digraph circuit {{")?;
+	writeln!(out, "\trankdir=LR;")?;
+	writeln!(out, "\tnode [shape=box];")?;
+
+	let cluster_of = |id: u32| clusters.iter().position(|c| c.nodes.contains(&id));
+
+	for (i, cluster) in clusters.iter().enumerate() {
+		writeln!(out, "\tsubgraph cluster_{} {{", i)?;
+		writeln!(out, "\t\tlabel=\"{}\";", escape(&cluster.name))?;
+
+		for &(id, ref node) in ctx.nodes.iter() {
+			if cluster_of(id) == Some(i) {
+				write_node(out, "\t\t", id, &**node)?;
+			}
+		}
+
+		writeln!(out, "\t}}")?;
+	}
+
+	for &(id, ref node) in ctx.nodes.iter() {
+		if cluster_of(id).is_none() {
+			write_node(out, "\t", id, &**node)?;
+		}
+	}
+
+	for c in ctx.connections.iter() {
+		let (from, to) = (c.from(), c.to());
+
+		let conflict = ctx.get_buses().iter()
+			.any(|b| b.to == to && b.in_conflict());
+
+		write!(out, "\tn{} -> n{} [label=\"{}->{}: {}\"", from.0, to.0, from.1, to.1, escape(&c.value().to_string()))?;
+		if conflict {
+			write!(out, ", color=red")?;
+		}
+		writeln!(out, "];")?;
+	}
+
+	writeln!(out, "}}")
+}
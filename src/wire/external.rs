@@ -0,0 +1,534 @@
+//! Nodes whose output comes from outside the circuit, for driving circuits
+//! from scripts and other processes. Values are written as `5`, `true`,
+//! `false` or `null`, see WireValue::parse
+
+use wire::*;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+#[derive(Debug)]
+pub enum ReplayLoadError {
+	Io(io::Error),
+	Parse { line: usize, message: String },
+}
+
+impl From<io::Error> for ReplayLoadError {
+	fn from(e: io::Error) -> Self { ReplayLoadError::Io(e) }
+}
+
+impl fmt::Display for ReplayLoadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ReplayLoadError::Io(ref e) => write!(f, "{}", e),
+			ReplayLoadError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+		}
+	}
+}
+
+/// Reads `<tick> <value>` lines, with ticks in increasing order. `#` starts
+/// a comment
+pub fn load_replay(path: &Path) -> Result<Vec<(u64, WireValue)>, ReplayLoadError> {
+	let mut text = String::new();
+	File::open(path)?.read_to_string(&mut text)?;
+
+	let mut entries: Vec<(u64, WireValue)> = Vec::new();
+
+	for (line_no, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') { continue }
+
+		let error = |message: &str| ReplayLoadError::Parse { line: line_no + 1, message: message.to_string() };
+
+		let mut parts = line.split_whitespace();
+		let tick: u64 = parts.next().and_then(|t| t.parse().ok()).ok_or(error("expected a tick"))?;
+		let value = parts.next().and_then(WireValue::parse).ok_or(error("expected a value"))?;
+
+		if parts.next().is_some() {
+			return Err(error("expected '<tick> <value>'"));
+		}
+
+		if entries.last().map_or(false, |e| e.0 > tick) {
+			return Err(error("ticks must be in increasing order"));
+		}
+
+		entries.push((tick, value));
+	}
+
+	Ok(entries)
+}
+
+
+/// Plays back values from a file of `(tick, value)` pairs. Ticks count the
+/// steps since the file was loaded, and the output holds the latest value
+/// whose tick has been reached
+pub struct ReplayNode {
+	path: String,
+	entries: Vec<(u64, WireValue)>,
+
+	/// How many ticks have been played back, counted by update rather than
+	/// taken from the context's tick so playback resumes where a snapshot
+	/// left it
+	played: u64,
+	next: usize,
+	value: WireValue,
+}
+
+impl ReplayNode {
+	pub fn new() -> Self {
+		ReplayNode {
+			path: String::new(),
+			entries: Vec::new(),
+
+			played: 0,
+			next: 0,
+			value: WireValue::Null,
+		}
+	}
+
+	pub fn from_file(path: &Path) -> Result<Self, ReplayLoadError> {
+		let mut node = ReplayNode::new();
+		node.entries = load_replay(path)?;
+		node.path = path.to_string_lossy().into_owned();
+		Ok(node)
+	}
+
+	/// Starts playback again from the first entry
+	pub fn rewind(&mut self) {
+		self.played = 0;
+		self.next = 0;
+		self.value = WireValue::Null;
+	}
+}
+
+impl WireNode for ReplayNode {
+	fn get_type_name(&self) -> &'static str { "Replay" }
+
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::External,
+			help: "Plays back values from a file of tick and value pairs",
+			inputs: Vec::new(),
+			outputs: vec![PortInfo::any("value", "The latest value whose tick has been reached")],
+		}
+	}
+
+	fn on_frob(&mut self) {
+		self.rewind();
+	}
+
+	fn update(&mut self, _tick: u64) {
+		while let Some(&(entry_tick, value)) = self.entries.get(self.next) {
+			if entry_tick > self.played { break }
+
+			self.value = value;
+			self.next += 1;
+		}
+
+		self.played += 1;
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { self.value } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		format!("replay {:?} @{}", self.value, self.played)
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		let mut state = vec![self.played as i32, (self.played >> 32) as i32, self.next as i32];
+		state.extend_from_slice(&self.value.to_state());
+		state
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if state.len() != 5 { return }
+
+		self.played = state[0] as u32 as u64 | (state[1] as u32 as u64) << 32;
+		self.next = (state[2] as usize).min(self.entries.len());
+		self.value = WireValue::from_state(&state[3..5]);
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::string("path", &self.path)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::String(path) = value {
+			self.entries = if path.is_empty() {
+				Vec::new()
+			} else {
+				load_replay(Path::new(&path))
+					.map_err(|e| ParamError::Invalid(format!("Failed to load '{}': {}", path, e)))?
+			};
+
+			self.path = path;
+			self.rewind();
+		}
+
+		Ok(())
+	}
+}
+
+
+type StdinSubscribers = Mutex<Vec<(String, Sender<WireValue>)>>;
+
+lazy_static! {
+	/// Every StdinNode shares one thread reading stdin, which hands each
+	/// line to the nodes it's addressed to. It's started by the first
+	/// node to subscribe
+	static ref STDIN_SUBSCRIBERS: StdinSubscribers = {
+		thread::spawn(read_stdin);
+		Mutex::new(Vec::new())
+	};
+}
+
+fn read_stdin() {
+	let stdin = io::stdin();
+
+	for line in stdin.lock().lines() {
+		let line = match line {
+			Ok(line) => line,
+			Err(_) => break,
+		};
+
+		let tokens: Vec<&str> = line.split_whitespace().collect();
+		let (name, value) = match *tokens.as_slice() {
+			[value] => (None, value),
+			[name, value] => (Some(name), value),
+			_ => continue,
+		};
+
+		let value = match WireValue::parse(value) {
+			Some(value) => value,
+			None => {
+				println!("stdin: can't parse '{}'", value);
+				continue
+			}
+		};
+
+		let mut subscribers = STDIN_SUBSCRIBERS.lock().unwrap();
+
+		// Sending fails once a node is dropped, so it's forgotten
+		subscribers.retain(|&(ref sub_name, ref sender)| {
+			if name.map_or(false, |n| n != sub_name) { return true }
+			sender.send(value).is_ok()
+		});
+	}
+}
+
+/// Takes its value from lines on stdin. A line holding just a value goes
+/// to every stdin node; `<name> <value>` only to nodes with that name
+pub struct StdinNode {
+	name: String,
+
+	/// Subscribed on the first update rather than when the node is made, so
+	/// that making one, e.g. to look at its info, doesn't start reading stdin
+	receiver: Option<Receiver<WireValue>>,
+
+	value: WireValue,
+}
+
+impl StdinNode {
+	pub fn new(name: &str) -> Self {
+		StdinNode {
+			name: name.to_string(),
+			receiver: None,
+			value: WireValue::Null,
+		}
+	}
+
+	fn subscribe(name: &str) -> Receiver<WireValue> {
+		let (sender, receiver) = mpsc::channel();
+		STDIN_SUBSCRIBERS.lock().unwrap().push((name.to_string(), sender));
+		receiver
+	}
+}
+
+impl WireNode for StdinNode {
+	fn get_type_name(&self) -> &'static str { "Stdin" }
+
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::External,
+			help: "Takes its value from lines on stdin",
+			inputs: Vec::new(),
+			outputs: vec![PortInfo::any("value", "The last value read")],
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		let name = &self.name;
+		let receiver = self.receiver.get_or_insert_with(|| StdinNode::subscribe(name));
+
+		if let Some(value) = receiver.try_iter().last() {
+			self.value = value;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { self.value } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		format!("{}:{:?}", self.name, self.value)
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		self.value.to_state().to_vec()
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		self.value = WireValue::from_state(state);
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::string("name", &self.name)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::String(name) = value {
+			self.receiver = None;
+			self.name = name;
+		}
+
+		Ok(())
+	}
+}
+
+
+#[cfg(unix)]
+pub use self::socket::*;
+
+#[cfg(unix)]
+mod socket {
+	use wire::*;
+
+	use std::fs;
+	use std::io::{self, Read};
+	use std::os::unix::fs::{FileTypeExt, MetadataExt};
+	use std::os::unix::net::{UnixListener, UnixStream};
+	use std::path::Path;
+
+	/// Longest line taken from a client. A client sending more without a
+	/// newline is dropped
+	const MAX_LINE_LENGTH: usize = 1 << 16;
+
+	/// Removes a socket left over from an earlier run, which would make
+	/// binding to `path` fail. Anything at `path` that isn't a socket is
+	/// left alone and reported as an error
+	pub fn remove_stale_socket(path: &Path) -> io::Result<()> {
+		match fs::symlink_metadata(path) {
+			Ok(ref meta) if meta.file_type().is_socket() => fs::remove_file(path),
+			Ok(_) => Err(io::Error::new(io::ErrorKind::AlreadyExists, "path exists and isn't a socket")),
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Inode of the socket at `path`, if there is one
+	pub fn socket_inode(path: &Path) -> Option<u64> {
+		let meta = fs::symlink_metadata(path).ok()?;
+		if meta.file_type().is_socket() { Some(meta.ino()) } else { None }
+	}
+
+	/// Listens on a Unix domain socket, taking its value from lines sent by
+	/// any number of clients. Nothing is read while the path is empty
+	pub struct SocketNode {
+		path: String,
+		listener: Option<UnixListener>,
+		/// Inode of the socket file bound, so it's only removed while it's
+		/// still ours
+		inode: Option<u64>,
+		clients: Vec<(UnixStream, Vec<u8>)>,
+		value: WireValue,
+	}
+
+	impl SocketNode {
+		pub fn new() -> Self {
+			SocketNode {
+				path: String::new(),
+				listener: None,
+				inode: None,
+				clients: Vec::new(),
+				value: WireValue::Null,
+			}
+		}
+
+		pub fn bind(path: &Path) -> io::Result<Self> {
+			let mut node = SocketNode::new();
+			node.listen(path)?;
+			Ok(node)
+		}
+
+		/// Listens on `path` instead. The current listener is only closed
+		/// once the new one is bound, so it keeps working if that fails
+		fn listen(&mut self, path: &Path) -> io::Result<()> {
+			remove_stale_socket(path)?;
+
+			let listener = UnixListener::bind(path)?;
+			listener.set_nonblocking(true)?;
+			let inode = socket_inode(path);
+
+			self.close();
+			self.listener = Some(listener);
+			self.inode = inode;
+			self.path = path.to_string_lossy().into_owned();
+			Ok(())
+		}
+
+		fn close(&mut self) {
+			// Another node may have bound the same path since, e.g. when a
+			// circuit is reloaded before this one is dropped
+			if self.listener.take().is_some() && self.inode.is_some() && socket_inode(Path::new(&self.path)) == self.inode {
+				let _ = fs::remove_file(&self.path);
+			}
+
+			self.inode = None;
+			self.clients.clear();
+			self.path.clear();
+		}
+
+		fn accept_clients(&mut self) {
+			let listener = match self.listener {
+				Some(ref listener) => listener,
+				None => return,
+			};
+
+			while let Ok((stream, _)) = listener.accept() {
+				if stream.set_nonblocking(true).is_ok() {
+					self.clients.push((stream, Vec::new()));
+				}
+			}
+		}
+
+		/// Reads what each client has sent so far, returning the last
+		/// complete value. Clients that hang up, error, or send too long a
+		/// line are dropped
+		fn read_clients(&mut self) -> Option<WireValue> {
+			let mut latest = None;
+			let mut chunk = [0u8; 256];
+
+			let mut i = 0;
+
+			while i < self.clients.len() {
+				let open = {
+					let (ref mut stream, ref mut buffer) = self.clients[i];
+
+					loop {
+						match stream.read(&mut chunk) {
+							Ok(0) => break false,
+							Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+							Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break true,
+							Err(_) => break false,
+						}
+
+						while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+							let line: Vec<u8> = buffer.drain(..end + 1).collect();
+							let line = String::from_utf8_lossy(&line);
+
+							match WireValue::parse(line.trim()) {
+								Some(value) => latest = Some(value),
+								None => println!("{}: can't parse '{}'", self.path, line.trim()),
+							}
+						}
+
+						if buffer.len() > MAX_LINE_LENGTH {
+							println!("{}: dropping a client sending a line longer than {} bytes", self.path, MAX_LINE_LENGTH);
+							break false;
+						}
+					}
+				};
+
+				if open {
+					i += 1;
+				} else {
+					self.clients.remove(i);
+				}
+			}
+
+			latest
+		}
+	}
+
+	impl Drop for SocketNode {
+		fn drop(&mut self) {
+			self.close();
+		}
+	}
+
+	impl WireNode for SocketNode {
+		fn get_type_name(&self) -> &'static str { "Socket" }
+
+		fn get_num_outputs(&self) -> u32 { 1 }
+
+		fn get_info(&self) -> NodeInfo {
+			NodeInfo {
+				type_name: self.get_type_name(),
+				category: Category::External,
+				help: "Takes its value from lines sent to a Unix socket",
+				inputs: Vec::new(),
+				outputs: vec![PortInfo::any("value", "The last value received")],
+			}
+		}
+
+		fn update(&mut self, _tick: u64) {
+			self.accept_clients();
+
+			if let Some(value) = self.read_clients() {
+				self.value = value;
+			}
+		}
+
+		fn get_output(&self, port: u32) -> WireValue {
+			if port == 0 { self.value } else { WireValue::Null }
+		}
+
+		fn get_label(&self) -> String {
+			format!("socket {:?} ({})", self.value, self.clients.len())
+		}
+
+		fn save_state(&self) -> Vec<i32> {
+			self.value.to_state().to_vec()
+		}
+
+		fn load_state(&mut self, state: &[i32]) {
+			self.value = WireValue::from_state(state);
+		}
+
+		fn get_params(&self) -> Vec<Param> {
+			vec![Param::string("path", &self.path)]
+		}
+
+		fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+			validate_param(&self.get_params(), name, &value)?;
+
+			if let ParamValue::String(path) = value {
+				if path.is_empty() {
+					self.close();
+				} else {
+					self.listen(Path::new(&path))
+						.map_err(|e| ParamError::Invalid(format!("Failed to listen on '{}': {}", path, e)))?;
+				}
+			}
+
+			Ok(())
+		}
+	}
+}
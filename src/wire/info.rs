@@ -0,0 +1,113 @@
+//! Descriptions of node types and their ports, for the HUD, the item
+//! palette and anything else that shows nodes to a person. Unlike labels
+//! these don't change with the values flowing through a node, only with
+//! its params
+
+use std::fmt;
+
+/// Groups of related node types, in the order the palette shows them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+	Source,
+	Logic,
+	Arithmetic,
+	Routing,
+	Sequential,
+	Memory,
+	Timing,
+	External,
+	Output,
+	Other,
+}
+
+param_enum!(Category {
+	Source => "Source",
+	Logic => "Logic",
+	Arithmetic => "Arithmetic",
+	Routing => "Routing",
+	Sequential => "Sequential",
+	Memory => "Memory",
+	Timing => "Timing",
+	External => "External",
+	Output => "Output",
+	Other => "Other",
+});
+
+impl fmt::Display for Category {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.name())
+	}
+}
+
+/// What a port is meant to carry. Any port can still be given any value,
+/// this only says what the node makes sense of
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PortType {
+	Int,
+	Bool,
+	/// Whatever the node is given, like the data through a Mux
+	Any,
+}
+
+impl fmt::Display for PortType {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			PortType::Int => write!(f, "int"),
+			PortType::Bool => write!(f, "bool"),
+			PortType::Any => write!(f, "any"),
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct PortInfo {
+	pub name: String,
+	pub ty: PortType,
+	pub description: &'static str,
+}
+
+impl PortInfo {
+	pub fn int(name: &str, description: &'static str) -> Self {
+		PortInfo { name: name.to_string(), ty: PortType::Int, description }
+	}
+
+	pub fn bool(name: &str, description: &'static str) -> Self {
+		PortInfo { name: name.to_string(), ty: PortType::Bool, description }
+	}
+
+	pub fn any(name: &str, description: &'static str) -> Self {
+		PortInfo { name: name.to_string(), ty: PortType::Any, description }
+	}
+
+	/// `count` ports of the same type, named `prefix` followed by their
+	/// index, for nodes with a variable number of ports
+	pub fn numbered(prefix: &str, ty: PortType, description: &'static str, count: u32) -> Vec<Self> {
+		(0..count)
+			.map(|i| PortInfo { name: format!("{}{}", prefix, i), ty, description })
+			.collect()
+	}
+}
+
+/// A node type and its ports, see WireNode::get_info. There's one PortInfo
+/// per port, in port order
+#[derive(Clone, Debug)]
+pub struct NodeInfo {
+	pub type_name: &'static str,
+	pub category: Category,
+
+	/// A sentence or two on what the node does
+	pub help: &'static str,
+
+	pub inputs: Vec<PortInfo>,
+	pub outputs: Vec<PortInfo>,
+}
+
+impl NodeInfo {
+	pub fn get_input(&self, port: u32) -> Option<&PortInfo> {
+		self.inputs.get(port as usize)
+	}
+
+	pub fn get_output(&self, port: u32) -> Option<&PortInfo> {
+		self.outputs.get(port as usize)
+	}
+}
@@ -1,41 +1,71 @@
 use wire::*;
 
 pub struct ButtonNode {
-	pressed_ticks: u32
+	pulse: PulseTimer
 }
 
 impl ButtonNode {
 	pub fn new() -> Self {
 		ButtonNode {
-			pressed_ticks: 0,
+			pulse: PulseTimer::new(2),
 		}
 	}
 }
 
 impl WireNode for ButtonNode {
+	fn get_type_name(&self) -> &'static str { "Button" }
+
 	fn get_num_outputs(&self) -> u32 { 1 }
 
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Source,
+			help: "Outputs true for a moment when frobbed",
+			inputs: Vec::new(),
+			outputs: vec![PortInfo::bool("pressed", "")],
+		}
+	}
+
 	fn get_output(&self, port: u32) -> WireValue {
-		if self.pressed_ticks > 0 { WireValue::Bool(true) } else { WireValue::Bool(false) }
+		WireValue::Bool(self.pulse.is_active())
 	}
 
 	fn on_frob(&mut self) {
-		self.pressed_ticks = 2;
+		self.pulse.trigger();
 	}
 
-	fn update(&mut self) {
-		if self.pressed_ticks > 0 {
-			self.pressed_ticks -= 1;
-		}
+	fn update(&mut self, _tick: u64) {
+		self.pulse.tick();
 	}
 
 	fn get_label(&self) -> String {
-		if self.pressed_ticks > 0 {
+		if self.pulse.is_active() {
 			"click".to_string()
 		} else {
 			String::new()
 		}
 	}
+
+	fn save_state(&self) -> Vec<i32> { vec![self.pulse.remaining as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [remaining] = *state { self.pulse.remaining = remaining as u32 }
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("ticks", self.pulse.width as i32, 1, 1000)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.pulse.width = v as u32;
+			self.pulse.remaining = self.pulse.remaining.min(self.pulse.width);
+		}
+
+		Ok(())
+	}
 }
 
 pub struct ToggleNode {
@@ -51,8 +81,20 @@ impl ToggleNode {
 }
 
 impl WireNode for ToggleNode {
+	fn get_type_name(&self) -> &'static str { "Toggle" }
+
 	fn get_num_outputs(&self) -> u32 { 1 }
 
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Source,
+			help: "Flips between false and true when frobbed",
+			inputs: Vec::new(),
+			outputs: vec![PortInfo::bool("state", "")],
+		}
+	}
+
 	fn get_output(&self, port: u32) -> WireValue {
 		if self.state { WireValue::Bool(true) } else { WireValue::Bool(false) }
 	}
@@ -68,4 +110,9 @@ impl WireNode for ToggleNode {
 			"off".to_string()
 		}
 	}
+
+	fn save_state(&self) -> Vec<i32> { vec![self.state as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [state] = *state { self.state = state != 0 }
+	}
 }
\ No newline at end of file
@@ -0,0 +1,651 @@
+use wire::*;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+pub const MAX_ADDRESS_WIDTH: u32 = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MemoryFormat {
+	/// Raw little-endian words, each as many bytes as the word width needs
+	Binary,
+	/// Whitespace separated hex words. `#` starts a comment
+	Hex,
+	/// Intel HEX records, byte addressed. Bytes are packed little-endian
+	/// into words
+	IntelHex,
+}
+
+impl MemoryFormat {
+	/// Guesses the format from a file extension, defaulting to binary
+	pub fn from_path(path: &Path) -> Self {
+		let ext = path.extension()
+			.and_then(|e| e.to_str())
+			.map(|e| e.to_lowercase());
+
+		match ext.as_ref().map(|e| e.as_str()) {
+			Some("hex") | Some("txt") => MemoryFormat::Hex,
+			Some("ihex") | Some("ihx") => MemoryFormat::IntelHex,
+			_ => MemoryFormat::Binary,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum MemoryLoadError {
+	Io(io::Error),
+	Parse { line: usize, message: String },
+	TooLarge { words: usize, capacity: usize },
+}
+
+impl From<io::Error> for MemoryLoadError {
+	fn from(e: io::Error) -> Self { MemoryLoadError::Io(e) }
+}
+
+impl fmt::Display for MemoryLoadError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			MemoryLoadError::Io(ref e) => write!(f, "{}", e),
+			MemoryLoadError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+			MemoryLoadError::TooLarge { words, capacity } =>
+				write!(f, "file holds {} words but memory only fits {}", words, capacity),
+		}
+	}
+}
+
+fn bytes_per_word(word_width: u32) -> usize {
+	((word_width + 7) / 8) as usize
+}
+
+fn pack_words(bytes: &[u8], word_width: u32) -> Vec<i32> {
+	bytes.chunks(bytes_per_word(word_width))
+		.map(|chunk| {
+			let word = chunk.iter().rev().fold(0u32, |acc, &b| acc << 8 | b as u32);
+			truncate_to_width(word as i32, word_width)
+		})
+		.collect()
+}
+
+fn parse_error<T>(line: usize, message: &str) -> Result<T, MemoryLoadError> {
+	Err(MemoryLoadError::Parse { line: line + 1, message: message.to_string() })
+}
+
+fn parse_hex_words(text: &str, word_width: u32) -> Result<Vec<i32>, MemoryLoadError> {
+	let mut words = Vec::new();
+
+	for (line_no, line) in text.lines().enumerate() {
+		let line = line.split('#').next().unwrap();
+
+		for token in line.split_whitespace() {
+			let token = token.trim_left_matches("0x");
+			match u32::from_str_radix(token, 16) {
+				Ok(word) => words.push(truncate_to_width(word as i32, word_width)),
+				Err(_) => return parse_error(line_no, &format!("'{}' is not a hex word", token)),
+			}
+		}
+	}
+
+	Ok(words)
+}
+
+fn parse_intel_hex(text: &str, word_width: u32, capacity: usize) -> Result<Vec<i32>, MemoryLoadError> {
+	let word_bytes = bytes_per_word(word_width);
+	let mut bytes = Vec::new();
+	let mut base = 0usize;
+
+	for (line_no, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() { continue }
+
+		if !line.is_ascii() || !line.starts_with(':') || line.len() < 11 || line.len() % 2 == 0 {
+			return parse_error(line_no, "malformed record");
+		}
+
+		let mut record = Vec::new();
+		for i in 0..(line.len()-1)/2 {
+			match u8::from_str_radix(&line[1+i*2..3+i*2], 16) {
+				Ok(b) => record.push(b),
+				Err(_) => return parse_error(line_no, "invalid hex digit"),
+			}
+		}
+
+		let checksum = record.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+		if checksum != 0 {
+			return parse_error(line_no, "checksum mismatch");
+		}
+
+		let count = record[0] as usize;
+		if record.len() != count + 5 {
+			return parse_error(line_no, "byte count doesn't match record length");
+		}
+
+		let offset = (record[1] as usize) << 8 | record[2] as usize;
+		let data = &record[4..4+count];
+
+		match record[3] {
+			0x00 => {
+				let start = base + offset;
+				if start + count > capacity * word_bytes {
+					let words = (start + count + word_bytes - 1) / word_bytes;
+					return Err(MemoryLoadError::TooLarge { words, capacity });
+				}
+
+				if bytes.len() < start + count {
+					bytes.resize(start + count, 0);
+				}
+
+				bytes[start..start+count].copy_from_slice(data);
+			}
+
+			0x01 => break,
+
+			0x02 if count == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) << 4,
+			0x04 if count == 2 => base = ((data[0] as usize) << 8 | data[1] as usize) << 16,
+
+			// Start addresses mean nothing here
+			0x03 | 0x05 => {}
+
+			t => return parse_error(line_no, &format!("unsupported record type {:02X}", t)),
+		}
+	}
+
+	Ok(pack_words(&bytes, word_width))
+}
+
+/// Reads memory contents from a file, truncating each word to `word_width`
+/// bits. Intel HEX records addressed past `capacity` words are rejected,
+/// other formats are checked when the words are stored
+pub fn load_memory(path: &Path, format: MemoryFormat, word_width: u32, capacity: usize) -> Result<Vec<i32>, MemoryLoadError> {
+	let mut file = File::open(path)?;
+
+	if format == MemoryFormat::Binary {
+		let mut bytes = Vec::new();
+		file.read_to_end(&mut bytes)?;
+		return Ok(pack_words(&bytes, word_width));
+	}
+
+	let mut text = String::new();
+	file.read_to_string(&mut text)?;
+
+	match format {
+		MemoryFormat::Hex => parse_hex_words(&text, word_width),
+		_ => parse_intel_hex(&text, word_width, capacity),
+	}
+}
+
+
+/// Shared storage and inspection for RomNode and RamNode
+struct MemoryBank {
+	address_width: u32,
+	word_width: u32,
+	contents: Vec<i32>,
+
+	/// Page of contents shown in the label, if any
+	inspect_page: Option<usize>,
+}
+
+const INSPECT_PAGE_SIZE: usize = 4;
+
+impl MemoryBank {
+	fn params(&self) -> Vec<Param> {
+		vec![
+			Param::int("address_width", self.address_width as i32, 1, MAX_ADDRESS_WIDTH as i32),
+			Param::int("word_width", self.word_width as i32, 1, 32),
+		]
+	}
+
+	/// Changing either width clears the contents
+	fn set_param(&mut self, name: &str, value: &ParamValue) {
+		match (name, value) {
+			("address_width", &ParamValue::Int(v)) => *self = MemoryBank::new(v as u32, self.word_width),
+			("word_width", &ParamValue::Int(v)) => *self = MemoryBank::new(self.address_width, v as u32),
+			_ => {}
+		}
+	}
+
+	fn new(address_width: u32, word_width: u32) -> Self {
+		assert!(address_width > 0 && address_width <= MAX_ADDRESS_WIDTH,
+			"Address width must be between 1 and {} bits", MAX_ADDRESS_WIDTH);
+		assert!(word_width > 0 && word_width <= 32, "Word width must be between 1 and 32 bits");
+
+		MemoryBank {
+			address_width,
+			word_width,
+			contents: vec![0; 1 << address_width],
+
+			inspect_page: None,
+		}
+	}
+
+	fn fill(&mut self, words: &[i32]) -> Result<(), MemoryLoadError> {
+		if words.len() > self.contents.len() {
+			return Err(MemoryLoadError::TooLarge { words: words.len(), capacity: self.contents.len() });
+		}
+
+		for (dst, &src) in self.contents.iter_mut().zip(words.iter()) {
+			*dst = truncate_to_width(src, self.word_width);
+		}
+
+		Ok(())
+	}
+
+	fn load(&mut self, path: &Path) -> Result<(), MemoryLoadError> {
+		self.load_as(path, MemoryFormat::from_path(path))
+	}
+
+	fn load_as(&mut self, path: &Path, format: MemoryFormat) -> Result<(), MemoryLoadError> {
+		let words = load_memory(path, format, self.word_width, self.contents.len())?;
+		self.fill(&words)
+	}
+
+	fn index(&self, address: i32) -> usize {
+		truncate_to_width(address, self.address_width) as usize
+	}
+
+	fn read(&self, address: i32) -> i32 {
+		self.contents[self.index(address)]
+	}
+
+	fn write(&mut self, address: i32, value: i32) {
+		let index = self.index(address);
+		self.contents[index] = truncate_to_width(value, self.word_width);
+	}
+
+	/// Steps through pages of contents, then back to the live view
+	fn next_inspect_page(&mut self) {
+		let num_pages = (self.contents.len() + INSPECT_PAGE_SIZE - 1) / INSPECT_PAGE_SIZE;
+
+		self.inspect_page = match self.inspect_page {
+			None => Some(0),
+			Some(p) if p + 1 < num_pages => Some(p + 1),
+			Some(_) => None,
+		};
+	}
+
+	fn label(&self, address: i32) -> String {
+		match self.inspect_page {
+			Some(page) => {
+				let start = page * INSPECT_PAGE_SIZE;
+				let words = self.contents[start..].iter().take(INSPECT_PAGE_SIZE)
+					.map(|w| format!("{:X}", w))
+					.collect::<Vec<_>>();
+
+				format!("{:X}: {}", start, words.join(" "))
+			}
+
+			None => format!("[{:X}]={:X}", self.index(address), self.read(address)),
+		}
+	}
+}
+
+
+/// Read-only memory. Input is Address; output is Data. Frob to page through
+/// the contents
+pub struct RomNode {
+	bank: MemoryBank,
+	address: i32,
+
+	/// Where the contents came from, so they can be reloaded
+	path: String,
+}
+
+impl RomNode {
+	pub fn new(address_width: u32, word_width: u32) -> Self {
+		RomNode {
+			bank: MemoryBank::new(address_width, word_width),
+			address: 0,
+
+			path: String::new(),
+		}
+	}
+
+	pub fn from_file(path: &Path, address_width: u32, word_width: u32) -> Result<Self, MemoryLoadError> {
+		let mut rom = RomNode::new(address_width, word_width);
+		rom.load(path, MemoryFormat::from_path(path))?;
+		rom.path = path.to_string_lossy().into_owned();
+		Ok(rom)
+	}
+
+	pub fn load(&mut self, path: &Path, format: MemoryFormat) -> Result<(), MemoryLoadError> {
+		self.bank.load_as(path, format)
+	}
+
+	pub fn contents(&self) -> &[i32] { &self.bank.contents }
+}
+
+impl WireNode for RomNode {
+	fn get_type_name(&self) -> &'static str { "Rom" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Memory,
+			help: "Read-only memory. Frob to page through the contents",
+			inputs: vec![PortInfo::int("Address", "")],
+			outputs: vec![PortInfo::int("Data", "The word at Address")],
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Int(0) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if let (0, WireValue::Int(val)) = (port, value) {
+			self.address = val;
+		}
+	}
+
+	fn on_frob(&mut self) {
+		self.bank.next_inspect_page();
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 {
+			WireValue::Int(self.bank.read(self.address))
+		} else {
+			WireValue::Null
+		}
+	}
+
+	fn get_label(&self) -> String {
+		self.bank.label(self.address)
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		let mut params = self.bank.params();
+		params.push(Param::string("path", &self.path));
+		params
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		// Load into a new bank, so a path or widths that fail to load leave
+		// the node as it was
+		let mut bank = MemoryBank::new(self.bank.address_width, self.bank.word_width);
+
+		let path = match value {
+			ParamValue::String(path) => path,
+			value => {
+				bank.set_param(name, &value);
+				self.path.clone()
+			}
+		};
+
+		if !path.is_empty() {
+			bank.load(Path::new(&path))
+				.map_err(|e| ParamError::Invalid(format!("Failed to load '{}': {}", path, e)))?;
+		}
+
+		self.bank = bank;
+		self.path = path;
+		Ok(())
+	}
+}
+
+
+/// Read/write memory. Inputs are Address, Data, Write Enable, Clock; output
+/// is the data at Address. Writes happen on the rising edge of Clock while
+/// Write Enable is high. Frob to page through the contents
+pub struct RamNode {
+	bank: MemoryBank,
+
+	address: i32,
+	data: i32,
+	write_enable: bool,
+	clock: EdgeDetector,
+}
+
+impl RamNode {
+	pub fn new(address_width: u32, word_width: u32) -> Self {
+		RamNode {
+			bank: MemoryBank::new(address_width, word_width),
+
+			address: 0,
+			data: 0,
+			write_enable: false,
+			clock: EdgeDetector::new(),
+		}
+	}
+
+	pub fn load(&mut self, path: &Path, format: MemoryFormat) -> Result<(), MemoryLoadError> {
+		self.bank.load_as(path, format)
+	}
+
+	pub fn contents(&self) -> &[i32] { &self.bank.contents }
+}
+
+impl WireNode for RamNode {
+	fn get_type_name(&self) -> &'static str { "Ram" }
+
+	fn get_num_inputs(&self) -> u32 { 4 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Memory,
+			help: "Read/write memory, written on the rising edge of Clock while Write Enable is high. Frob to page through the contents",
+			inputs: vec![
+				PortInfo::int("Address", ""),
+				PortInfo::int("Data", "Written to Address"),
+				PortInfo::bool("Write Enable", ""),
+				PortInfo::bool("Clock", ""),
+			],
+			outputs: vec![PortInfo::int("Data", "The word at Address")],
+		}
+	}
+
+	fn get_input_default(&self, port: u32) -> WireValue {
+		if port < 2 { WireValue::Int(0) } else { WireValue::Bool(false) }
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		match (port, value) {
+			(0, WireValue::Int(val)) => self.address = val,
+			(1, WireValue::Int(val)) => self.data = val,
+			(2, WireValue::Bool(val)) => self.write_enable = val,
+			(3, WireValue::Bool(val)) => self.clock.level = val,
+			_ => {}
+		}
+	}
+
+	fn on_frob(&mut self) {
+		self.bank.next_inspect_page();
+	}
+
+	fn update(&mut self, _tick: u64) {
+		if self.clock.rising() && self.write_enable {
+			self.bank.write(self.address, self.data);
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 {
+			WireValue::Int(self.bank.read(self.address))
+		} else {
+			WireValue::Null
+		}
+	}
+
+	fn get_label(&self) -> String {
+		self.bank.label(self.address)
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		let mut state = vec![self.clock.prev_level as i32];
+		state.extend_from_slice(&self.bank.contents);
+		state
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if state.len() != self.bank.contents.len() + 1 { return }
+
+		self.clock.prev_level = state[0] != 0;
+		self.bank.contents.copy_from_slice(&state[1..]);
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		self.bank.params()
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+		self.bank.set_param(name, &value);
+		Ok(())
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::env;
+	use std::fs;
+	use std::io::Write;
+
+	/// An Intel HEX record, with its byte count and checksum worked out
+	fn record(kind: u8, offset: u16, data: &[u8]) -> String {
+		let mut bytes = vec![data.len() as u8, (offset >> 8) as u8, offset as u8, kind];
+		bytes.extend_from_slice(data);
+
+		let sum = bytes.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+		bytes.push(sum.wrapping_neg());
+
+		let digits: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+		format!(":{}", digits.concat())
+	}
+
+	fn intel_hex(records: &[String], word_width: u32, capacity: usize) -> Result<Vec<i32>, MemoryLoadError> {
+		parse_intel_hex(&records.join("\n"), word_width, capacity)
+	}
+
+	fn expect_parse_error(result: Result<Vec<i32>, MemoryLoadError>, line: usize, message: &str) {
+		match result {
+			Err(MemoryLoadError::Parse { line: l, message: ref m }) if l == line && m == message => {}
+			other => panic!("expected '{}' on line {}, got {:?}", message, line, other),
+		}
+	}
+
+	fn expect_too_large(result: Result<Vec<i32>, MemoryLoadError>, words: usize, capacity: usize) {
+		match result {
+			Err(MemoryLoadError::TooLarge { words: w, capacity: c }) if w == words && c == capacity => {}
+			other => panic!("expected {} words over {}, got {:?}", words, capacity, other),
+		}
+	}
+
+	#[test]
+	fn hex_words() {
+		let words = parse_hex_words("0x1F 2 # comment\n\n ff 100", 4).unwrap();
+		assert_eq!(words, vec![0xF, 2, 0xF, 0]);
+
+		expect_parse_error(parse_hex_words("1 2\n3 zz", 8), 2, "'zz' is not a hex word");
+	}
+
+	#[test]
+	fn binary_words() {
+		assert_eq!(pack_words(&[0x34, 0x12, 0xFF], 16), vec![0x1234, 0xFF]);
+		assert_eq!(pack_words(&[0x34, 0x12], 12), vec![0x234]);
+		assert_eq!(pack_words(&[0x34, 0x12], 8), vec![0x34, 0x12]);
+		assert_eq!(pack_words(&[0xFF, 0xFF, 0xFF, 0xFF], 32), vec![-1]);
+		assert_eq!(pack_words(&[0x07, 0x05], 1), vec![1, 1]);
+	}
+
+	#[test]
+	fn intel_hex_data() {
+		let records = [record(0x00, 0, &[1, 2, 3, 4]), record(0x01, 0, &[])];
+		assert_eq!(intel_hex(&records, 8, 16).unwrap(), vec![1, 2, 3, 4]);
+		assert_eq!(intel_hex(&records, 16, 16).unwrap(), vec![0x0201, 0x0403]);
+		assert_eq!(intel_hex(&records, 2, 16).unwrap(), vec![1, 2, 3, 0]);
+
+		// Gaps are zero, and nothing is read past the end of file record
+		let records = [record(0x00, 2, &[9]), record(0x01, 0, &[]), record(0x00, 0, &[7])];
+		assert_eq!(intel_hex(&records, 8, 16).unwrap(), vec![0, 0, 9]);
+	}
+
+	#[test]
+	fn intel_hex_checksum() {
+		let mut bad = record(0x00, 0, &[1, 2]);
+		bad.pop();
+		bad.push('0');
+
+		expect_parse_error(intel_hex(&[record(0x00, 0, &[5]), bad], 8, 16), 2, "checksum mismatch");
+	}
+
+	#[test]
+	fn intel_hex_malformed() {
+		expect_parse_error(parse_intel_hex("00000001FF", 8, 16), 1, "malformed record");
+		expect_parse_error(parse_intel_hex(":000000", 8, 16), 1, "malformed record");
+		expect_parse_error(parse_intel_hex(":00000001FG", 8, 16), 1, "invalid hex digit");
+		expect_parse_error(parse_intel_hex(":0300000001FC", 8, 16), 1, "byte count doesn't match record length");
+
+		// Multibyte characters would otherwise be sliced through
+		expect_parse_error(parse_intel_hex(":00000001\u{e9}", 8, 16), 1, "malformed record");
+		expect_parse_error(parse_intel_hex(":\u{e9}\u{e9}\u{e9}\u{e9}\u{e9}", 8, 16), 1, "malformed record");
+	}
+
+	#[test]
+	fn intel_hex_extended_addresses() {
+		// Segment addresses are shifted by 4 bits
+		let records = [record(0x02, 0, &[0x00, 0x01]), record(0x00, 1, &[0xAB])];
+		let words = intel_hex(&records, 8, 32).unwrap();
+		assert_eq!(words.len(), 18);
+		assert_eq!(words[17], 0xAB);
+
+		// Linear addresses by 16, which puts this past the memory
+		let records = [record(0x04, 0, &[0x00, 0x01]), record(0x00, 0, &[0xAB])];
+		expect_too_large(intel_hex(&records, 8, 1 << MAX_ADDRESS_WIDTH), (1 << 16) + 1, 1 << MAX_ADDRESS_WIDTH);
+
+		// Back to the start
+		let records = [record(0x04, 0, &[0x00, 0x01]), record(0x04, 0, &[0x00, 0x00]), record(0x00, 0, &[0xAB])];
+		assert_eq!(intel_hex(&records, 8, 16).unwrap(), vec![0xAB]);
+
+		// Start addresses are ignored, other record types aren't understood
+		let records = [record(0x05, 0, &[0, 0, 0, 0]), record(0x00, 0, &[1])];
+		assert_eq!(intel_hex(&records, 8, 16).unwrap(), vec![1]);
+		expect_parse_error(intel_hex(&[record(0x06, 0, &[])], 8, 16), 1, "unsupported record type 06");
+	}
+
+	#[test]
+	fn intel_hex_capacity() {
+		assert_eq!(intel_hex(&[record(0x00, 13, &[1, 2, 3])], 8, 16).unwrap().len(), 16);
+		expect_too_large(intel_hex(&[record(0x00, 14, &[1, 2, 3])], 8, 16), 17, 16);
+
+		// Capacity is in words, so wider words fit more bytes
+		assert_eq!(intel_hex(&[record(0x00, 14, &[1, 2])], 16, 8).unwrap().len(), 8);
+		expect_too_large(intel_hex(&[record(0x00, 15, &[1, 2])], 16, 8), 9, 8);
+
+		// Far past the end is rejected before anything is allocated for it
+		let records = [record(0x04, 0, &[0xFF, 0xFF]), record(0x00, 0xFFFF, &[1])];
+		expect_too_large(intel_hex(&records, 8, 16), 1 << 32, 16);
+	}
+
+	#[test]
+	fn rom_keeps_contents_when_reload_fails() {
+		let path = env::temp_dir().join("wire-rom-reload-test.hex");
+		File::create(&path).unwrap().write_all(b"1 2 3 4 5").unwrap();
+
+		let mut rom = RomNode::from_file(&path, 3, 8).unwrap();
+		assert_eq!(&rom.contents()[..5], &[1, 2, 3, 4, 5]);
+
+		// Four words can't hold the file
+		assert!(rom.set_param("address_width", ParamValue::Int(2)).is_err());
+		assert_eq!(rom.get_params()[0].value, ParamValue::Int(3));
+		assert_eq!(&rom.contents()[..5], &[1, 2, 3, 4, 5]);
+
+		assert!(rom.set_param("path", ParamValue::String("/nonexistent/rom.hex".to_string())).is_err());
+		assert_eq!(rom.get_params()[2].value, ParamValue::String(path.to_string_lossy().into_owned()));
+		assert_eq!(&rom.contents()[..5], &[1, 2, 3, 4, 5]);
+
+		// Narrower words are reloaded from the file
+		rom.set_param("word_width", ParamValue::Int(2)).unwrap();
+		assert_eq!(&rom.contents()[..5], &[1, 2, 3, 0, 1]);
+
+		fs::remove_file(&path).unwrap();
+	}
+}
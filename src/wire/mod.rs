@@ -1,36 +1,197 @@
+#[macro_use]
+mod params;
 mod basic; 
 mod io; 
+mod arith;
+mod sequential;
+mod memory;
+mod routing;
+mod timing;
+mod random;
+mod save;
+mod sink;
+mod external;
+mod observer;
+mod bus;
+mod stats;
+mod debug;
+mod dot;
+mod verilog;
+mod netlist;
+mod optimize;
+mod compile;
+mod info;
 
+pub use self::params::*;
 pub use self::basic::*;
 pub use self::io::*;
+pub use self::arith::*;
+pub use self::sequential::*;
+pub use self::memory::*;
+pub use self::routing::*;
+pub use self::timing::*;
+pub use self::random::*;
+pub use self::save::*;
+pub use self::sink::*;
+pub use self::external::*;
+pub use self::observer::*;
+pub use self::bus::*;
+pub use self::stats::*;
+pub use self::debug::*;
+pub use self::dot::*;
+pub use self::verilog::*;
+pub use self::netlist::*;
+pub use self::optimize::*;
+pub use self::compile::*;
+pub use self::info::*;
 
 use std::borrow::BorrowMut;
+use std::fmt;
+use std::mem;
+use std::time::Instant;
 
+/// `Null` means there's no signal: an input that receives it, or that gets
+/// disconnected, takes the port's default instead (see
+/// WireNode::get_input_default). `Error` is output by nodes that can't
+/// compute a result, such as on a type mismatch or a division by zero, and
+/// is passed on by any node computing from it. Nodes that hold state ignore
+/// it and keep their state. `HighZ` is output by a driver that has let go
+/// of a bus, and is treated like Null everywhere else
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum WireValue {
 	Null,
 	Int(i32),
 	Bool(bool),
+	Error,
+	HighZ,
+}
+
+impl fmt::Display for WireValue {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			WireValue::Null => write!(f, "-"),
+			WireValue::Int(v) => write!(f, "{}", v),
+			WireValue::Bool(v) => write!(f, "{}", v),
+			WireValue::Error => write!(f, "ERR"),
+			WireValue::HighZ => write!(f, "Z"),
+		}
+	}
 }
 
 impl WireValue {
 	pub fn is_null(&self) -> bool {
 		match_enum!(*self, WireValue::Null)
 	}
+
+	/// Bools convert to 0 or 1
+	pub fn to_int(&self) -> Option<i32> {
+		match *self {
+			WireValue::Int(v) => Some(v),
+			WireValue::Bool(v) => Some(v as i32),
+			_ => None,
+		}
+	}
+
+	/// Packs the value into a pair of ints for WireNode::save_state
+	pub fn to_state(&self) -> [i32; 2] {
+		match *self {
+			WireValue::Null => [0, 0],
+			WireValue::Int(v) => [1, v],
+			WireValue::Bool(v) => [2, v as i32],
+			WireValue::Error => [3, 0],
+			WireValue::HighZ => [4, 0],
+		}
+	}
+
+	pub fn from_state(state: &[i32]) -> WireValue {
+		match *state {
+			[1, v] => WireValue::Int(v),
+			[2, v] => WireValue::Bool(v != 0),
+			[3, _] => WireValue::Error,
+			[4, _] => WireValue::HighZ,
+			_ => WireValue::Null,
+		}
+	}
+
+	/// Parses `null`, `error`, `z`, `true`, `false` or an integer
+	pub fn parse(text: &str) -> Option<WireValue> {
+		match text {
+			"null" => Some(WireValue::Null),
+			"error" => Some(WireValue::Error),
+			"z" => Some(WireValue::HighZ),
+			"true" => Some(WireValue::Bool(true)),
+			"false" => Some(WireValue::Bool(false)),
+			_ => text.parse().ok().map(WireValue::Int),
+		}
+	}
+
+	/// Ints are true when non-zero
+	pub fn to_bool(&self) -> Option<bool> {
+		match *self {
+			WireValue::Int(v) => Some(v != 0),
+			WireValue::Bool(v) => Some(v),
+			_ => None,
+		}
+	}
 }
 
 pub trait WireNode {
+	/// Stable name identifying the type of node in saved circuits
+	fn get_type_name(&self) -> &'static str;
+
 	fn get_num_inputs(&self) -> u32 { 0 }
 	fn get_num_outputs(&self) -> u32 { 0 }
 
+	/// What the node does and what its ports are for, with one PortInfo
+	/// per port. Nodes that don't say have their ports numbered
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Other,
+			help: "",
+			inputs: PortInfo::numbered("in", PortType::Any, "", self.get_num_inputs()),
+			outputs: PortInfo::numbered("out", PortType::Any, "", self.get_num_outputs()),
+		}
+	}
+
 	fn on_input_changed(&mut self, port: u32, value: WireValue) {}
 	fn on_frob(&mut self) {}
 
+	/// What an input is given in place of Null, including when it's
+	/// disconnected. Returning Null passes it through as is
+	fn get_input_default(&self, port: u32) -> WireValue { WireValue::Null }
+
+	/// Called with a seed derived from the context's global seed, if it has
+	/// one, when the node is added or the global seed changes
+	fn on_seed(&mut self, seed: u64) {}
+
 	fn get_output(&self, port: u32) -> WireValue { WireValue::Null }
 
+	/// Whether the outputs only depend on the inputs and params, so that
+	/// nodes fed the same values can be merged or folded into constants.
+	/// Nodes holding state, using the tick or reaching outside aren't
+	fn is_pure(&self) -> bool { false }
+
 	fn get_label(&self) -> String { String::new() }
 
-	fn update(&mut self) {}
+	/// Settings that can be changed while the node is in use. Anything
+	/// needed to recreate the node from its type name should be here
+	fn get_params(&self) -> Vec<Param> { Vec::new() }
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		Err(ParamError::Unknown(name.to_string()))
+	}
+
+	/// Called once per step with the context's tick count, which starts at 0
+	fn update(&mut self, tick: u64) {}
+
+	/// A value to report to one of the context's output sinks, taken once
+	/// per step after inputs have been delivered
+	fn take_output(&mut self) -> Option<PendingOutput> { None }
+
+	/// Internal state that isn't derived from inputs, for snapshots. Inputs
+	/// are redelivered on restore so they needn't be included
+	fn save_state(&self) -> Vec<i32> { Vec::new() }
+	fn load_state(&mut self, state: &[i32]) {}
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -46,32 +207,320 @@ pub struct WireConnection {
 	invalid: bool,
 }
 
+impl WireConnection {
+	/// The value last read from the source port
+	pub fn value(&self) -> WireValue { self.value }
+
+	pub fn from(&self) -> (u32, u32) { (self.input_node, self.input_port) }
+	pub fn to(&self) -> (u32, u32) { (self.output_node, self.output_port) }
+}
+
+/// Delivers a value to an input, substituting the port's default for Null
+/// and HighZ
+fn deliver(node: &mut WireNode, port: u32, value: WireValue) {
+	let value = match value {
+		WireValue::Null | WireValue::HighZ => node.get_input_default(port),
+		value => value,
+	};
+
+	node.on_input_changed(port, value);
+}
+
+/// The state of a WireContext at a point in time
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+	nodes: Vec<(u32, Vec<i32>)>,
+	connections: Vec<WireConnection>,
+	buses: Vec<Bus>,
+}
+
 pub struct WireContext {
 	pub nodes: Vec<(u32, Box<WireNode>)>,
 	pub connections: Vec<WireConnection>,
+	buses: Vec<Bus>,
 
 	pub next_id: u32,
+
+	seed: Option<u64>,
+
+	sinks: Vec<(String, Box<OutputSink>)>,
+	/// Sink names nodes have sent to that weren't registered, so each is
+	/// only reported once
+	unknown_sinks: Vec<String>,
+	ticks: u64,
+
+	observers: Vec<Observer>,
+	next_observer_id: ObserverId,
+	events: Vec<WireEvent>,
+
+	stats: Option<WireStats>,
+
+	breakpoints: Vec<Breakpoint>,
+	next_breakpoint_id: BreakpointId,
+	break_hit: Option<BreakHit>,
+
+	watches: Vec<Watch>,
+	next_watch_id: WatchId,
 }
 
 impl WireContext {
 	pub fn new() -> Self {
-		WireContext {
+		let mut ctx = WireContext {
 			nodes: Vec::new(),
 			connections: Vec::new(),
+			buses: Vec::new(),
 
 			next_id: 0,
+
+			seed: None,
+
+			sinks: Vec::new(),
+			unknown_sinks: Vec::new(),
+			ticks: 0,
+
+			observers: Vec::new(),
+			next_observer_id: 0,
+			events: Vec::new(),
+
+			stats: None,
+
+			breakpoints: Vec::new(),
+			next_breakpoint_id: 0,
+			break_hit: None,
+
+			watches: Vec::new(),
+			next_watch_id: 0,
+		};
+
+		ctx.add_sink(STDOUT_SINK, StdoutSink);
+		ctx
+	}
+
+	pub fn with_seed(seed: u64) -> Self {
+		let mut ctx = WireContext::new();
+		ctx.seed = Some(seed);
+		ctx
+	}
+
+	pub fn get_seed(&self) -> Option<u64> { self.seed }
+
+	/// How many times step has been called. Nodes are given this in update
+	pub fn get_ticks(&self) -> u64 { self.ticks }
+
+	/// Reseeds every node from `seed` and their ids
+	pub fn set_seed(&mut self, seed: u64) {
+		self.seed = Some(seed);
+
+		for &mut (id, ref mut node) in self.nodes.iter_mut() {
+			node.on_seed(derive_seed(seed, id));
+		}
+	}
+
+	/// Registers a sink for OutputNodes to send values to, replacing any
+	/// with the same name
+	pub fn add_sink<T: OutputSink + 'static>(&mut self, name: &str, sink: T) {
+		self.remove_sink(name);
+		self.unknown_sinks.retain(|n| n != name);
+		self.sinks.push((name.to_string(), box sink));
+	}
+
+	pub fn remove_sink(&mut self, name: &str) -> Option<Box<OutputSink>> {
+		let index = self.sinks.iter().position(|s| s.0 == name)?;
+		Some(self.sinks.remove(index).1)
+	}
+
+	/// Starts or stops timing each step. Stats are kept while enabled and
+	/// dropped when disabled
+	pub fn enable_stats(&mut self, enabled: bool) {
+		match (enabled, self.stats.is_some()) {
+			(true, false) => self.stats = Some(WireStats::new()),
+			(false, true) => self.stats = None,
+			_ => {}
+		}
+	}
+
+	pub fn get_stats(&self) -> Option<&WireStats> { self.stats.as_ref() }
+
+	pub fn reset_stats(&mut self) {
+		if self.stats.is_some() {
+			self.stats = Some(WireStats::new());
+		}
+	}
+
+	/// Watches an output port, recording a BreakHit when `condition`
+	/// triggers. Stepping carries on regardless, it's up to the caller to
+	/// check get_break
+	pub fn add_breakpoint(&mut self, port: (u32, u32), condition: BreakCondition) -> BreakpointId {
+		let id = self.next_breakpoint_id;
+		self.next_breakpoint_id += 1;
+
+		let last = self.get_node(port.0).map_or(WireValue::Null, |n| n.get_output(port.1));
+		self.breakpoints.push(Breakpoint { id, port, condition, last });
+		id
+	}
+
+	pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+		let count = self.breakpoints.len();
+		self.breakpoints.retain(|b| b.id != id);
+		self.breakpoints.len() != count
+	}
+
+	pub fn get_breakpoints(&self) -> &[Breakpoint] { &self.breakpoints }
+
+	/// The first breakpoint to trigger since the last clear_break
+	pub fn get_break(&self) -> Option<BreakHit> { self.break_hit }
+
+	pub fn clear_break(&mut self) {
+		self.break_hit = None;
+	}
+
+	fn check_breakpoints(&mut self) {
+		for breakpoint in self.breakpoints.iter_mut() {
+			let value = match self.nodes.binary_search_by_key(&breakpoint.port.0, |a| a.0) {
+				Ok(index) => self.nodes[index].1.get_output(breakpoint.port.1),
+				Err(_) => WireValue::Null,
+			};
+
+			let last = mem::replace(&mut breakpoint.last, value);
+
+			if self.break_hit.is_none() && breakpoint.condition.triggers(last, value) {
+				self.break_hit = Some(BreakHit {
+					breakpoint: breakpoint.id,
+					port: breakpoint.port,
+					value,
+					tick: self.ticks,
+				});
+			}
+		}
+	}
+
+	/// Adds a watch expression, see WatchExpr
+	pub fn add_watch(&mut self, text: &str) -> Result<WatchId, String> {
+		let expr = WatchExpr::parse(text)?;
+
+		let id = self.next_watch_id;
+		self.next_watch_id += 1;
+
+		self.watches.push(Watch { id, text: text.trim().to_string(), expr });
+		Ok(id)
+	}
+
+	pub fn remove_watch(&mut self, id: WatchId) -> bool {
+		let count = self.watches.len();
+		self.watches.retain(|w| w.id != id);
+		self.watches.len() != count
+	}
+
+	pub fn get_watches(&self) -> &[Watch] { &self.watches }
+
+	/// Calls `callback` with every event matching `filter`. Events are
+	/// queued as they happen and delivered at the end of each step
+	pub fn subscribe<F: FnMut(&WireEvent) + 'static>(&mut self, filter: WireFilter, callback: F) -> ObserverId {
+		let id = self.next_observer_id;
+		self.next_observer_id += 1;
+
+		self.observers.push(Observer { id, filter, callback: box callback });
+		id
+	}
+
+	pub fn unsubscribe(&mut self, id: ObserverId) {
+		self.observers.retain(|o| o.id != id);
+	}
+
+	fn emit(&mut self, event: WireEvent) {
+		if !self.observers.is_empty() {
+			self.events.push(event);
+		}
+	}
+
+	fn dispatch_events(&mut self) {
+		let events = mem::replace(&mut self.events, Vec::new());
+
+		for event in events.iter() {
+			for observer in self.observers.iter_mut() {
+				if observer.filter.matches(event) {
+					(observer.callback)(event);
+				}
+			}
+		}
+	}
+
+	/// Removes the connections `remove` returns true for, telling observers
+	fn remove_connections<F: Fn(&WireConnection) -> bool>(&mut self, remove: F) {
+		let mut removed = Vec::new();
+
+		self.connections.retain(|c| {
+			if remove(c) {
+				removed.push(WireEvent::Disconnected { from: c.from(), to: c.to() });
+				false
+			} else {
+				true
+			}
+		});
+
+		for event in removed {
+			if let WireEvent::Disconnected { to, .. } = event {
+				self.reset_input(to);
+			}
+
+			self.emit(event);
+		}
+	}
+
+	/// Gives an input its default if nothing is connected to it any more,
+	/// or has a bus resolve again without the driver that was removed
+	fn reset_input(&mut self, to: (u32, u32)) {
+		if self.connections.iter().any(|c| c.to() == to) {
+			if let Some(bus) = self.buses.iter_mut().find(|b| b.to == to) {
+				bus.dirty = true;
+			}
+
+			return
+		}
+
+		if let Some(node) = self.get_node_mut(to.0) {
+			deliver(node, to.1, WireValue::Null);
 		}
 	}
 
 	pub fn add_node<T: WireNode + 'static>(&mut self, node: T) -> u32 {
 		let id = self.next_id;
-		self.next_id += 1;
-		self.nodes.push((id, box node));
+		self.insert_node(id, box node);
 		id
 	}
 
+	/// Adds a node under a specific id, e.g. when loading a saved circuit.
+	/// Panics if the id is already in use
+	pub fn insert_node(&mut self, id: u32, mut node: Box<WireNode>) {
+		let index = match self.nodes.binary_search_by_key(&id, |a| a.0) {
+			Ok(_) => panic!("Node id {} is already in use", id),
+			Err(index) => index,
+		};
+
+		if let Some(seed) = self.seed {
+			node.on_seed(derive_seed(seed, id));
+		}
+
+		self.nodes.insert(index, (id, node));
+		self.next_id = self.next_id.max(id + 1);
+
+		self.emit(WireEvent::NodeAdded(id));
+	}
+
 	pub fn remove_node(&mut self, node_id: u32) {
+		let count = self.nodes.len();
 		self.nodes.retain(|n| n.0 != node_id);
+		self.buses.retain(|b| b.to.0 != node_id);
+
+		self.breakpoints.retain(|b| b.port.0 != node_id);
+
+		if let Some(ref mut stats) = self.stats {
+			stats.remove_node(node_id);
+		}
+
+		if self.nodes.len() != count {
+			self.emit(WireEvent::NodeRemoved(node_id));
+		}
 	}
 
 	pub fn get_node(&self, node_id: u32) -> Option<&WireNode> {
@@ -84,6 +533,101 @@ impl WireContext {
 			.map(move |n| self.nodes[n].1.borrow_mut())
 	}
 
+	/// Sets a param on a node, dropping any connections to ports the node
+	/// no longer has afterwards
+	pub fn set_param(&mut self, node_id: u32, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		let before = match self.get_node_mut(node_id) {
+			Some(node) => {
+				let before = node.get_info();
+				node.set_param(name, value)?;
+				before
+			}
+
+			None => return Err(ParamError::Invalid(format!("no node with id {}", node_id))),
+		};
+
+		self.remap_ports(node_id, &before);
+		Ok(())
+	}
+
+	/// Moves connections and buses on `node_id` to the ports with the same
+	/// names they had in `before`, e.g. a Mux's Select as channels are added,
+	/// and removes those on ports that are gone
+	fn remap_ports(&mut self, node_id: u32, before: &NodeInfo) {
+		let after = match self.get_node(node_id) {
+			Some(node) => node.get_info(),
+			None => return,
+		};
+
+		let port_map = |before: &[PortInfo], after: &[PortInfo]| -> Vec<Option<u32>> {
+			before.iter()
+				.map(|p| after.iter().position(|q| q.name == p.name).map(|i| i as u32))
+				.collect()
+		};
+
+		let inputs = port_map(&before.inputs, &after.inputs);
+		let outputs = port_map(&before.outputs, &after.outputs);
+
+		let new_input = |port: u32| inputs.get(port as usize).and_then(|&p| p);
+		let new_output = |port: u32| outputs.get(port as usize).and_then(|&p| p);
+
+		self.buses.retain(|b| b.to.0 != node_id || new_input(b.to.1).is_some());
+		self.remove_connections(|c| {
+			(c.output_node == node_id && new_input(c.output_port).is_none()) ||
+			(c.input_node == node_id && new_output(c.input_port).is_none())
+		});
+
+		let mut vacated = Vec::new();
+		let mut moved = Vec::new();
+
+		for c in self.connections.iter_mut() {
+			let (from, to) = (c.from(), c.to());
+
+			if c.output_node == node_id {
+				c.output_port = new_input(c.output_port).unwrap();
+			}
+
+			if c.input_node == node_id {
+				c.input_port = new_output(c.input_port).unwrap();
+			}
+
+			if (c.from(), c.to()) != (from, to) {
+				c.changed = true;
+				vacated.push(to);
+				moved.push((from, to, c.from(), c.to()));
+			}
+		}
+
+		for bus in self.buses.iter_mut().filter(|b| b.to.0 == node_id) {
+			bus.to.1 = new_input(bus.to.1).unwrap();
+			bus.dirty = true;
+		}
+
+		for (from, to, new_from, new_to) in moved {
+			self.emit(WireEvent::Disconnected { from, to });
+			self.emit(WireEvent::Connected { from: new_from, to: new_to });
+		}
+
+		for to in vacated {
+			self.reset_input(to);
+		}
+	}
+
+	/// Checks that a connection could be made, describing the problem if
+	/// not. add_connection panics in the same situations
+	pub fn check_connection(&self, from: (u32, u32), to: (u32, u32)) -> Result<(), String> {
+		let from_ok = self.get_node(from.0).map(|n| from.1 < n.get_num_outputs());
+		let to_ok = self.get_node(to.0).map(|n| to.1 < n.get_num_inputs());
+
+		match (from_ok, to_ok) {
+			(Some(true), Some(true)) => Ok(()),
+			(None, _) => Err(format!("unknown node {}", from.0)),
+			(_, None) => Err(format!("unknown node {}", to.0)),
+			(Some(false), _) => Err(format!("node {} has no output #{}", from.0, from.1)),
+			(_, Some(false)) => Err(format!("node {} has no input #{}", to.0, to.1)),
+		}
+	}
+
 	pub fn add_connection(&mut self, from_node: (u32, u32), to_node: (u32, u32)) {
 		let input = self.nodes.binary_search_by_key(&from_node.0, |a| a.0);
 		let output = self.nodes.binary_search_by_key(&to_node.0, |a| a.0);
@@ -103,8 +647,12 @@ impl WireContext {
 			panic!("Can't connect to target node on port #{}", to_node.1);
 		}
 
-		// Only one input per port
-		self.connections.retain(|c| c.output_node != to_node.0 || c.output_port != to_node.1);
+		// Only one input per port, unless it's a bus
+		if self.get_bus(to_node).is_some() {
+			self.remove_connections(|c| c.to() == to_node && c.from() == from_node);
+		} else {
+			self.remove_connections(|c| c.to() == to_node);
+		}
 
 		self.connections.push(WireConnection {
 			input_node: from_node.0,
@@ -117,9 +665,164 @@ impl WireContext {
 			changed: false,
 			invalid: false,
 		});
+
+		self.emit(WireEvent::Connected { from: from_node, to: to_node });
+	}
+
+	/// Makes an input accept any number of drivers, combined according to
+	/// `resolution`. With None, the input goes back to a single driver and
+	/// all but the most recently connected are removed
+	pub fn set_bus(&mut self, to: (u32, u32), resolution: Option<BusResolution>) {
+		match resolution {
+			Some(resolution) => {
+				self.buses.retain(|b| b.to != to);
+				self.buses.push(Bus { to, resolution, conflict: false, dirty: true });
+			}
+
+			None => {
+				self.buses.retain(|b| b.to != to);
+
+				let last = self.connections.iter().rposition(|c| c.to() == to);
+				if let Some(last) = last {
+					let keep = self.connections[last].from();
+					self.remove_connections(|c| c.to() == to && c.from() != keep);
+					self.connections.iter_mut().find(|c| c.to() == to).unwrap().changed = true;
+				}
+			}
+		}
+	}
+
+	pub fn get_bus(&self, to: (u32, u32)) -> Option<BusResolution> {
+		self.buses.iter().find(|b| b.to == to).map(|b| b.resolution)
+	}
+
+	pub fn get_buses(&self) -> &[Bus] { &self.buses }
+
+	/// Resolves the buses whose drivers have changed, delivering the result
+	fn resolve_buses(&mut self) {
+		let mut conflicts = Vec::new();
+
+		for bus in self.buses.iter_mut() {
+			if !bus.dirty { continue }
+			bus.dirty = false;
+
+			let drivers = self.connections.iter()
+				.filter(|c| c.to() == bus.to)
+				.map(|c| c.value);
+
+			let (value, conflict) = bus.resolution.resolve(drivers);
+
+			if conflict && !bus.conflict {
+				conflicts.push(WireEvent::BusConflict { to: bus.to });
+			}
+
+			bus.conflict = conflict;
+
+			if let Ok(index) = self.nodes.binary_search_by_key(&bus.to.0, |a| a.0) {
+				deliver(&mut *self.nodes[index].1, bus.to.1, value);
+			}
+		}
+
+		for event in conflicts {
+			self.emit(event);
+		}
+	}
+
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot {
+			nodes: self.nodes.iter()
+				.map(|&(id, ref node)| (id, node.save_state()))
+				.collect(),
+
+			connections: self.connections.clone(),
+			buses: self.buses.clone(),
+		}
+	}
+
+	/// Rewinds to a snapshot. Nodes added since the snapshot was taken are
+	/// left as they are, but all connections are replaced
+	pub fn restore(&mut self, snapshot: &Snapshot) {
+		for &(id, ref state) in snapshot.nodes.iter() {
+			if let Some(node) = self.get_node_mut(id) {
+				node.load_state(state);
+			}
+		}
+
+		let old_connections = mem::replace(&mut self.connections, snapshot.connections.clone());
+
+		let same = |a: &WireConnection, b: &WireConnection| a.from() == b.from() && a.to() == b.to();
+
+		let removed: Vec<_> = old_connections.iter()
+			.filter(|c| !self.connections.iter().any(|n| same(n, c)))
+			.map(|c| WireEvent::Disconnected { from: c.from(), to: c.to() })
+			.collect();
+
+		let added: Vec<_> = self.connections.iter()
+			.filter(|c| !old_connections.iter().any(|o| same(o, c)))
+			.map(|c| WireEvent::Connected { from: c.from(), to: c.to() })
+			.collect();
+
+		for event in removed.into_iter().chain(added) {
+			if let WireEvent::Disconnected { to, .. } = event {
+				self.reset_input(to);
+			}
+
+			self.emit(event);
+		}
+
+		self.buses = snapshot.buses.clone();
+
+		// Nodes may have seen different inputs since, so make sure they
+		// receive the snapshotted ones on the next step
+		for bus in self.buses.iter_mut() {
+			bus.dirty = true;
+		}
+
+		for connection in self.connections.iter_mut() {
+			connection.changed = true;
+		}
 	}
 
 	pub fn step(&mut self) {
+		self.step_counting_changes();
+	}
+
+	/// Steps `ticks` times
+	pub fn run(&mut self, ticks: u64) {
+		for _ in 0..ticks {
+			self.step();
+		}
+	}
+
+	/// Steps until `predicate` holds, checking before each step. Returns how
+	/// many steps that took, or None if it still didn't hold after
+	/// `max_ticks` steps
+	pub fn run_until<F: FnMut(&WireContext) -> bool>(&mut self, mut predicate: F, max_ticks: u64) -> Option<u64> {
+		for ticks in 0..max_ticks {
+			if predicate(self) { return Some(ticks) }
+			self.step();
+		}
+
+		if predicate(self) { Some(max_ticks) } else { None }
+	}
+
+	/// Steps until a step leaves every connection's value as it was.
+	/// Returns how many steps that took, including the quiet one, or None if
+	/// the circuit was still changing after `max_ticks` steps
+	pub fn run_until_stable(&mut self, max_ticks: u64) -> Option<u64> {
+		for ticks in 0..max_ticks {
+			if self.step_counting_changes() == 0 {
+				return Some(ticks + 1)
+			}
+		}
+
+		None
+	}
+
+	/// Returns how many connections picked up a new value
+	fn step_counting_changes(&mut self) -> usize {
+		let start = self.stats.as_ref().map(|_| Instant::now());
+
 		for connection in self.connections.iter_mut() {
 			let output = self.nodes.binary_search_by_key(&connection.output_node, |a| a.0);
 			if output.is_err() {
@@ -128,17 +831,35 @@ impl WireContext {
 			}
 
 			if connection.changed {
-				let output = &mut self.nodes[output.unwrap()].1;
-
-				output.on_input_changed(connection.output_port, connection.value);
 				connection.changed = false;
+
+				// Buses are delivered to once all their drivers are known
+				if let Some(bus) = self.buses.iter_mut().find(|b| b.to == connection.to()) {
+					bus.dirty = true;
+					continue
+				}
+
+				let output = &mut self.nodes[output.unwrap()].1;
+				deliver(&mut **output, connection.output_port, connection.value);
 			}
 		}
 
-		self.connections.retain(|c| !c.invalid);
+		self.remove_connections(|c| c.invalid);
+		self.resolve_buses();
+
+		self.send_outputs();
+
+		let ticks = self.ticks;
+		match self.stats {
+			Some(ref mut stats) => for &mut (id, ref mut node) in self.nodes.iter_mut() {
+				let update_start = Instant::now();
+				node.update(ticks);
+				stats.record_update(id, node.get_type_name(), update_start.elapsed());
+			},
 
-		for node in self.nodes.iter_mut() {
-			node.1.update();
+			None => for node in self.nodes.iter_mut() {
+				node.1.update(ticks);
+			},
 		}
 
 		for connection in self.connections.iter_mut() {
@@ -147,9 +868,9 @@ impl WireContext {
 				if !connection.value.is_null() {
 					connection.value = WireValue::Null;
 					connection.changed = true;
-					connection.invalid = true;
 				}
 
+				connection.invalid = true;
 				continue
 			}
 
@@ -161,5 +882,179 @@ impl WireContext {
 				connection.changed = true;
 			}
 		}
+
+		let changes: Vec<_> = self.connections.iter()
+			.filter(|c| c.changed && !c.invalid)
+			.map(|c| WireEvent::ValueChanged { from: c.from(), to: c.to(), value: c.value })
+			.collect();
+
+		let change_count = changes.len();
+		self.check_breakpoints();
+
+		for event in changes {
+			self.emit(event);
+		}
+
+		self.ticks += 1;
+		self.dispatch_events();
+
+		if let (Some(start), Some(stats)) = (start, self.stats.as_mut()) {
+			stats.record_step(start.elapsed(), change_count);
+		}
+
+		change_count
+	}
+
+	fn send_outputs(&mut self) {
+		let mut failed = Vec::new();
+
+		for &mut (node_id, ref mut node) in self.nodes.iter_mut() {
+			let output = match node.take_output() {
+				Some(output) => output,
+				None => continue,
+			};
+
+			let sink = match self.sinks.iter_mut().find(|s| s.0 == output.sink) {
+				Some(sink) => sink,
+				None => {
+					if !self.unknown_sinks.contains(&output.sink) {
+						println!("No output sink named '{}', dropping values sent to it", output.sink);
+						self.unknown_sinks.push(output.sink);
+					}
+
+					continue
+				}
+			};
+
+			let event = OutputEvent {
+				tick: self.ticks,
+				node_id,
+				name: output.name,
+				value: output.value,
+			};
+
+			if let Err(e) = sink.1.on_output(&event) {
+				println!("Output sink '{}' failed, removing it: {}", sink.0, e);
+				failed.push(sink.0.clone());
+			}
+		}
+
+		for name in failed {
+			self.remove_sink(&name);
+		}
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn connections(ctx: &WireContext) -> Vec<((u32, u32), (u32, u32))> {
+		let mut connections: Vec<_> = ctx.connections.iter().map(|c| (c.from(), c.to())).collect();
+		connections.sort();
+		connections
+	}
+
+	fn settle(ctx: &mut WireContext) {
+		for _ in 0..4 { ctx.step() }
+	}
+
+	fn output(ctx: &WireContext, node_id: u32) -> WireValue {
+		ctx.get_node(node_id).unwrap().get_output(0)
+	}
+
+	#[test]
+	fn shrinking_drops_removed_ports() {
+		let mut ctx = WireContext::new();
+		let terms: Vec<u32> = (1..5).map(|v| ctx.add_node(ConstantNode { value: v })).collect();
+		let add = ctx.add_node(AddNode::new(4));
+		let out = ctx.add_node(OutputNode::new("sum"));
+
+		for (port, &term) in terms.iter().enumerate() {
+			ctx.add_connection((term, 0), (add, port as u32));
+		}
+
+		ctx.add_connection((add, 0), (out, 0));
+		ctx.set_bus((add, 3), Some(BusResolution::WiredOr));
+		settle(&mut ctx);
+		assert_eq!(output(&ctx, add), WireValue::Int(10));
+
+		ctx.set_param(add, "inputs", ParamValue::Int(2)).unwrap();
+		assert_eq!(connections(&ctx), vec![
+			((terms[0], 0), (add, 0)),
+			((terms[1], 0), (add, 1)),
+			((add, 0), (out, 0)),
+		]);
+		assert!(ctx.get_buses().is_empty());
+
+		settle(&mut ctx);
+		assert_eq!(output(&ctx, add), WireValue::Int(3));
+	}
+
+	#[test]
+	fn growing_keeps_ports() {
+		let mut ctx = WireContext::new();
+		let a = ctx.add_node(BoolConstantNode { value: true });
+		let b = ctx.add_node(BoolConstantNode { value: true });
+		let and = ctx.add_node(AndNode::new(2));
+		let not = ctx.add_node(NotNode::new());
+
+		ctx.add_connection((a, 0), (and, 0));
+		ctx.add_connection((b, 0), (and, 1));
+		ctx.add_connection((and, 0), (not, 0));
+		let before = connections(&ctx);
+
+		ctx.set_param(and, "inputs", ParamValue::Int(4)).unwrap();
+		assert_eq!(connections(&ctx), before);
+
+		// The new inputs are at their defaults
+		settle(&mut ctx);
+		assert_eq!(output(&ctx, and), WireValue::Bool(false));
+
+		ctx.add_connection((a, 0), (and, 2));
+		ctx.add_connection((b, 0), (and, 3));
+		settle(&mut ctx);
+		assert_eq!(output(&ctx, and), WireValue::Bool(true));
+		assert_eq!(output(&ctx, not), WireValue::Bool(false));
+	}
+
+	#[test]
+	fn connections_follow_port_names() {
+		let mut ctx = WireContext::new();
+		let channels: Vec<u32> = (0..3).map(|v| ctx.add_node(ConstantNode { value: 10 + v })).collect();
+		let select = ctx.add_node(ConstantNode { value: 1 });
+		let mux = ctx.add_node(MuxNode::new(2));
+
+		ctx.add_connection((channels[0], 0), (mux, 0));
+		ctx.add_connection((channels[1], 0), (mux, 1));
+		ctx.add_connection((select, 0), (mux, 2));
+		ctx.set_bus((mux, 2), Some(BusResolution::WiredAnd));
+		settle(&mut ctx);
+		assert_eq!(output(&ctx, mux), WireValue::Int(11));
+
+		// Select moves along as a channel is added
+		ctx.set_param(mux, "channels", ParamValue::Int(3)).unwrap();
+		assert_eq!(connections(&ctx), vec![
+			((channels[0], 0), (mux, 0)),
+			((channels[1], 0), (mux, 1)),
+			((select, 0), (mux, 3)),
+		]);
+		assert_eq!(ctx.get_buses()[0].to, (mux, 3));
+
+		ctx.add_connection((channels[2], 0), (mux, 2));
+		settle(&mut ctx);
+		assert_eq!(output(&ctx, mux), WireValue::Int(11));
+
+		// And back, dropping the channel that's gone
+		ctx.set_param(mux, "channels", ParamValue::Int(1)).unwrap();
+		assert_eq!(connections(&ctx), vec![
+			((channels[0], 0), (mux, 0)),
+			((select, 0), (mux, 1)),
+		]);
+		assert_eq!(ctx.get_buses()[0].to, (mux, 1));
+
+		settle(&mut ctx);
+		assert_eq!(output(&ctx, mux), WireValue::Null);
 	}
 }
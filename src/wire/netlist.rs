@@ -0,0 +1,635 @@
+//! Importing gate-level netlists written by Yosys with `write_json`. Only
+//! single-bit gate cells are understood, so designs should go through
+//! `synth` (or at least `techmap`) first:
+//!
+//! ```text
+//! yosys -p "synth -top top; write_json top.json" top.v
+//! ```
+//!
+//! Each bit of an input port becomes a Toggle, and each bit of an output
+//! port an Output named after it, with `[i]` on the end for wider ports.
+//! Constant bits come from a BoolConstant, with `x` and `z` bits taken as
+//! false.
+
+use wire::*;
+use json::Json;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum NetlistError {
+	Io(io::Error),
+	Json(String),
+	Invalid(String),
+}
+
+impl From<io::Error> for NetlistError {
+	fn from(e: io::Error) -> Self { NetlistError::Io(e) }
+}
+
+impl fmt::Display for NetlistError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			NetlistError::Io(ref e) => write!(f, "{}", e),
+			NetlistError::Json(ref e) => write!(f, "invalid JSON: {}", e),
+			NetlistError::Invalid(ref e) => write!(f, "{}", e),
+		}
+	}
+}
+
+fn invalid<T>(message: String) -> Result<T, NetlistError> {
+	Err(NetlistError::Invalid(message))
+}
+
+/// Where auto_place put a node, in a grid with signals flowing along
+/// increasing columns
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Placement {
+	pub node_id: u32,
+	pub column: u32,
+	pub row: u32,
+}
+
+/// Nodes whose outputs only change on a clock, so that loops through them
+/// don't push everything after them further along
+fn is_clocked(type_name: &str) -> bool {
+	match type_name {
+		"DFlipFlop" | "TFlipFlop" | "JkFlipFlop" | "DLatch" | "SrLatch" | "Register" => true,
+		_ => false,
+	}
+}
+
+/// Lays nodes out in columns by how many nodes lie between them and a
+/// source, with clocked nodes starting new paths
+pub fn auto_place(ctx: &WireContext) -> Vec<Placement> {
+	let mut columns: HashMap<u32, u32> = ctx.nodes.iter().map(|n| (n.0, 0)).collect();
+
+	// Combinational loops would grow forever, so give up once every path
+	// could have been walked
+	for _ in 0..ctx.nodes.len() {
+		let mut changed = false;
+
+		for c in ctx.connections.iter() {
+			let clocked = ctx.get_node(c.to().0).map_or(true, |n| is_clocked(n.get_type_name()));
+			if clocked { continue }
+
+			let column = columns[&c.from().0] + 1;
+			let to = columns.get_mut(&c.to().0).unwrap();
+
+			if column > *to {
+				*to = column;
+				changed = true;
+			}
+		}
+
+		if !changed { break }
+	}
+
+	let mut rows = HashMap::new();
+
+	ctx.nodes.iter()
+		.map(|&(node_id, _)| {
+			let column = columns[&node_id];
+			let row = rows.entry(column).or_insert(0);
+			*row += 1;
+
+			Placement { node_id, column, row: *row - 1 }
+		})
+		.collect()
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Bit {
+	Net(i64),
+	Zero,
+	One,
+}
+
+fn parse_bit(bit: &Json) -> Option<Bit> {
+	match *bit {
+		Json::Number(_) => bit.as_i64().map(Bit::Net),
+		Json::String(ref s) => match s.as_str() {
+			"1" => Some(Bit::One),
+			"0" | "x" | "z" => Some(Bit::Zero),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+fn parse_bits(bits: &Json) -> Option<Vec<Bit>> {
+	bits.as_array()?.iter().map(parse_bit).collect()
+}
+
+struct Importer {
+	ctx: WireContext,
+
+	drivers: HashMap<Bit, (u32, u32)>,
+	/// Nets that are just another net, from `$_BUF_` cells
+	aliases: HashMap<Bit, Bit>,
+	/// Inputs to connect once every net's driver is known
+	pending: Vec<((u32, u32), Bit)>,
+
+	constants: Option<((u32, u32), (u32, u32))>,
+}
+
+impl Importer {
+	fn drive(&mut self, bit: Bit, from: (u32, u32)) -> Result<(), NetlistError> {
+		if let Bit::Net(n) = bit {
+			if self.drivers.insert(bit, from).is_some() || self.aliases.contains_key(&bit) {
+				return invalid(format!("net {} has more than one driver", n));
+			}
+		}
+
+		Ok(())
+	}
+
+	fn feed(&mut self, to: (u32, u32), bit: Bit) {
+		self.pending.push((to, bit));
+	}
+
+	/// Adds a Not fed by `bit`, returning its output
+	fn invert(&mut self, bit: Bit) -> (u32, u32) {
+		let id = self.ctx.add_node(NotNode::new());
+		self.feed((id, 0), bit);
+		(id, 0)
+	}
+
+	/// Outputs that are always false and true
+	fn constants(&mut self) -> ((u32, u32), (u32, u32)) {
+		if let Some(constants) = self.constants {
+			return constants;
+		}
+
+		let zero = self.ctx.add_node(BoolConstantNode { value: false });
+		let one = self.ctx.add_node(BoolConstantNode { value: true });
+
+		self.constants = Some(((zero, 0), (one, 0)));
+		self.constants.unwrap()
+	}
+
+	fn source(&mut self, bit: Bit) -> Result<(u32, u32), NetlistError> {
+		let mut bit = bit;
+
+		// Following more aliases than there are means they go round in a loop
+		for _ in 0..self.aliases.len() + 1 {
+			match bit {
+				Bit::Zero => return Ok(self.constants().0),
+				Bit::One => return Ok(self.constants().1),
+				Bit::Net(n) => match self.aliases.get(&bit) {
+					Some(&alias) => bit = alias,
+					None => return self.drivers.get(&bit).cloned()
+						.ok_or(NetlistError::Invalid(format!("net {} has no driver", n))),
+				},
+			}
+		}
+
+		invalid("buffers form a loop".to_string())
+	}
+
+	fn add_port(&mut self, name: &str, port: &Json) -> Result<(), NetlistError> {
+		let direction = port.get("direction").and_then(Json::as_str);
+		let bits = port.get("bits").and_then(parse_bits)
+			.ok_or(NetlistError::Invalid(format!("port '{}' has invalid bits", name)))?;
+
+		for (i, &bit) in bits.iter().enumerate() {
+			let bit_name = if bits.len() == 1 { name.to_string() } else { format!("{}[{}]", name, i) };
+
+			match direction {
+				Some("input") => {
+					let id = self.ctx.add_node(ToggleNode::new());
+					self.drive(bit, (id, 0))?;
+				}
+
+				Some("output") => {
+					let id = self.ctx.add_node(OutputNode::new(&bit_name));
+					self.feed((id, 0), bit);
+				}
+
+				_ => return invalid(format!("port '{}' must be an input or output", name)),
+			}
+		}
+
+		Ok(())
+	}
+
+	fn add_cell(&mut self, name: &str, cell: &Json) -> Result<(), NetlistError> {
+		let cell_type = cell.get("type").and_then(Json::as_str)
+			.ok_or(NetlistError::Invalid(format!("cell '{}' has no type", name)))?;
+
+		let connections = cell.get("connections");
+		let pin = |pin: &str| -> Result<Bit, NetlistError> {
+			match connections.and_then(|c| c.get(pin)).and_then(parse_bits) {
+				Some(ref bits) if bits.len() == 1 => Ok(bits[0]),
+				_ => invalid(format!("cell '{}' ({}) needs a one bit '{}' connection", name, cell_type, pin)),
+			}
+		};
+
+		match cell_type {
+			"$_BUF_" => {
+				let (a, y) = (pin("A")?, pin("Y")?);
+				if self.drivers.contains_key(&y) || self.aliases.insert(y, a).is_some() {
+					return invalid(format!("cell '{}' drives a net that already has a driver", name));
+				}
+			}
+
+			"$_NOT_" => {
+				let (a, y) = (pin("A")?, pin("Y")?);
+				let out = self.invert(a);
+				self.drive(y, out)?;
+			}
+
+			"$_AND_" | "$_OR_" | "$_XOR_" | "$_NAND_" | "$_NOR_" | "$_XNOR_" | "$_ANDNOT_" | "$_ORNOT_" => {
+				let op = match cell_type {
+					"$_OR_" | "$_ORNOT_" => Some(GateOp::Or),
+					"$_XOR_" => Some(GateOp::Xor),
+					"$_NAND_" => Some(GateOp::Nand),
+					"$_NOR_" => Some(GateOp::Nor),
+					"$_XNOR_" => Some(GateOp::Xnor),
+					_ => None,
+				};
+
+				let (a, b, y) = (pin("A")?, pin("B")?, pin("Y")?);
+				let id = match op {
+					Some(op) => self.ctx.add_node(GateNode::new(op, 2)),
+					None => self.ctx.add_node(AndNode::new(2)),
+				};
+				self.feed((id, 0), a);
+
+				// The B input is inverted for the NOT variants
+				if cell_type.ends_with("NOT_") {
+					let inverted = self.invert(b);
+					self.ctx.add_connection(inverted, (id, 1));
+				} else {
+					self.feed((id, 1), b);
+				}
+
+				self.drive(y, (id, 0))?;
+			}
+
+			"$_MUX_" | "$_NMUX_" => {
+				let (a, b, s, y) = (pin("A")?, pin("B")?, pin("S")?, pin("Y")?);
+
+				let id = self.ctx.add_node(MuxNode::new(2));
+				self.feed((id, 0), a);
+				self.feed((id, 1), b);
+				self.feed((id, 2), s);
+
+				let out = if cell_type == "$_NMUX_" {
+					let not = self.ctx.add_node(NotNode::new());
+					self.ctx.add_connection((id, 0), (not, 0));
+					(not, 0)
+				} else {
+					(id, 0)
+				};
+
+				self.drive(y, out)?;
+			}
+
+			"$_DFF_P_" | "$_DFF_N_" | "$_DFF_PP0_" | "$_DFF_PN0_" | "$_DFF_NP0_" | "$_DFF_NN0_" => {
+				let (d, c, q) = (pin("D")?, pin("C")?, pin("Q")?);
+				let polarity = &cell_type[6..cell_type.len() - 1];
+
+				let id = self.ctx.add_node(DFlipFlopNode::new());
+				self.feed((id, 0), d);
+
+				if polarity.starts_with('N') {
+					let clock = self.invert(c);
+					self.ctx.add_connection(clock, (id, 1));
+				} else {
+					self.feed((id, 1), c);
+				}
+
+				if polarity.len() > 1 {
+					let r = pin("R")?;
+
+					if polarity.as_bytes()[1] == b'N' {
+						let reset = self.invert(r);
+						self.ctx.add_connection(reset, (id, 2));
+					} else {
+						self.feed((id, 2), r);
+					}
+				}
+
+				self.drive(q, (id, 0))?;
+			}
+
+			"$_DLATCH_P_" | "$_DLATCH_N_" => {
+				let (d, e, q) = (pin("D")?, pin("E")?, pin("Q")?);
+
+				let id = self.ctx.add_node(DLatchNode::new());
+				self.feed((id, 0), d);
+
+				if cell_type == "$_DLATCH_N_" {
+					let enable = self.invert(e);
+					self.ctx.add_connection(enable, (id, 1));
+				} else {
+					self.feed((id, 1), e);
+				}
+
+				self.drive(q, (id, 0))?;
+			}
+
+			"$_DFF_PP1_" | "$_DFF_PN1_" | "$_DFF_NP1_" | "$_DFF_NN1_" => return invalid(format!(
+				"cell '{}' ({}) resets to 1, but DFlipFlop can only reset to 0", name, cell_type)),
+
+			_ if !cell_type.starts_with('$') => return invalid(format!(
+				"cell '{}' is an instance of module '{}', flatten the design first", name, cell_type)),
+
+			_ if !cell_type.starts_with("$_") => return invalid(format!(
+				"cell '{}' has word-level type '{}', run synth or techmap first", name, cell_type)),
+
+			_ => return invalid(format!("cell '{}' has unsupported type '{}'", name, cell_type)),
+		}
+
+		Ok(())
+	}
+}
+
+fn is_top(module: &Json) -> bool {
+	match module.get("attributes").and_then(|a| a.get("top")) {
+		Some(&Json::Number(v)) => v != 0.0,
+		Some(&Json::String(ref v)) => v.contains('1'),
+		_ => false,
+	}
+}
+
+/// Builds a circuit from the top module of a Yosys JSON netlist
+pub fn read_yosys_json(text: &str) -> Result<WireContext, NetlistError> {
+	let json = Json::parse(text).map_err(NetlistError::Json)?;
+
+	let modules = json.get("modules").and_then(Json::as_object)
+		.ok_or(NetlistError::Invalid("no modules".to_string()))?;
+
+	let module = match modules.iter().filter(|m| is_top(&m.1)).count() {
+		1 => &modules.iter().find(|m| is_top(&m.1)).unwrap().1,
+		0 if modules.len() == 1 => &modules[0].1,
+		_ => return invalid("can't tell which module is the top one, mark one with the top attribute".to_string()),
+	};
+
+	let mut importer = Importer {
+		ctx: WireContext::new(),
+		drivers: HashMap::new(),
+		aliases: HashMap::new(),
+		pending: Vec::new(),
+		constants: None,
+	};
+
+	let ports = module.get("ports").and_then(Json::as_object).unwrap_or(&[]);
+	for &(ref name, ref port) in ports.iter() {
+		importer.add_port(name, port)?;
+	}
+
+	let cells = module.get("cells").and_then(Json::as_object).unwrap_or(&[]);
+	for &(ref name, ref cell) in cells.iter() {
+		importer.add_cell(name, cell)?;
+	}
+
+	for (to, bit) in importer.pending.split_off(0) {
+		let from = importer.source(bit)?;
+		importer.ctx.add_connection(from, to);
+	}
+
+	Ok(importer.ctx)
+}
+
+pub fn load_netlist(path: &Path) -> Result<WireContext, NetlistError> {
+	let mut text = String::new();
+	File::open(path)?.read_to_string(&mut text)?;
+	read_yosys_json(&text)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A netlist with one module holding `ports` and `cells`, given as the
+	/// inside of their JSON objects
+	fn netlist(ports: &str, cells: &str) -> String {
+		format!(r#"{{"modules": {{"top": {{"ports": {{{}}}, "cells": {{{}}}}}}}}}"#, ports, cells)
+	}
+
+	fn error(text: &str) -> String {
+		match read_yosys_json(text) {
+			Ok(_) => panic!("expected an error importing {}", text),
+			Err(e) => e.to_string(),
+		}
+	}
+
+	/// The Toggles standing for input bits, in port order
+	fn inputs(ctx: &WireContext) -> Vec<u32> {
+		ctx.nodes.iter().filter(|n| n.1.get_type_name() == "Toggle").map(|n| n.0).collect()
+	}
+
+	fn set_inputs(ctx: &mut WireContext, values: &[bool]) {
+		for (id, &value) in inputs(ctx).into_iter().zip(values.iter()) {
+			let node = ctx.get_node_mut(id).unwrap();
+			if node.get_output(0) != WireValue::Bool(value) {
+				node.on_frob();
+			}
+		}
+	}
+
+	/// What's driving the Output named `name`
+	fn output(ctx: &WireContext, name: &str) -> WireValue {
+		let id = ctx.nodes.iter()
+			.find(|n| n.1.get_type_name() == "Output" && n.1.get_params()[0].value == ParamValue::String(name.to_string()))
+			.unwrap_or_else(|| panic!("no output named '{}'", name)).0;
+
+		let from = ctx.connections.iter().find(|c| c.to() == (id, 0)).unwrap().from();
+		ctx.get_node(from.0).unwrap().get_output(from.1)
+	}
+
+	const HALF_ADDER: &str = r#"{
+		"creator": "Yosys",
+		"modules": {
+			"unused": {
+				"ports": {"x": {"direction": "input", "bits": [2]}},
+				"cells": {}
+			},
+			"half_adder": {
+				"attributes": {"top": "00000000000000000000000000000001"},
+				"ports": {
+					"a": {"direction": "input", "bits": [2]},
+					"b": {"direction": "input", "bits": [3]},
+					"s": {"direction": "output", "bits": [4]},
+					"c": {"direction": "output", "bits": [5]},
+					"k": {"direction": "output", "bits": ["1", "0"]}
+				},
+				"cells": {
+					"$1": {"type": "$_XOR_", "connections": {"A": [2], "B": [3], "Y": [4]}},
+					"$2": {"type": "$_AND_", "connections": {"A": [2], "B": [3], "Y": [6]}},
+					"$3": {"type": "$_BUF_", "connections": {"A": [7], "Y": [5]}},
+					"$4": {"type": "$_BUF_", "connections": {"A": [6], "Y": [7]}}
+				}
+			}
+		}
+	}"#;
+
+	#[test]
+	fn half_adder() {
+		let mut ctx = read_yosys_json(HALF_ADDER).unwrap();
+		assert_eq!(inputs(&ctx).len(), 2);
+
+		for &(a, b) in [(false, false), (true, false), (false, true), (true, true)].iter() {
+			set_inputs(&mut ctx, &[a, b]);
+			ctx.run(5);
+
+			assert_eq!(output(&ctx, "s"), WireValue::Bool(a != b), "{} + {}", a, b);
+			assert_eq!(output(&ctx, "c"), WireValue::Bool(a && b), "{} + {}", a, b);
+		}
+
+		assert_eq!(output(&ctx, "k[0]"), WireValue::Bool(true));
+		assert_eq!(output(&ctx, "k[1]"), WireValue::Bool(false));
+	}
+
+	#[test]
+	fn inverted_inputs() {
+		let text = netlist(
+			r#""a": {"direction": "input", "bits": [2]},
+			"b": {"direction": "input", "bits": [3]},
+			"and_not": {"direction": "output", "bits": [4]},
+			"or_not": {"direction": "output", "bits": [5]},
+			"nmux": {"direction": "output", "bits": [6]}"#,
+			r#""$1": {"type": "$_ANDNOT_", "connections": {"A": [2], "B": [3], "Y": [4]}},
+			"$2": {"type": "$_ORNOT_", "connections": {"A": [2], "B": [3], "Y": [5]}},
+			"$3": {"type": "$_NMUX_", "connections": {"A": [2], "B": "1", "S": [3], "Y": [6]}}"#);
+
+		// Bits are given as a list, even for one bit
+		assert_eq!(error(&text), "cell '$3' ($_NMUX_) needs a one bit 'B' connection");
+
+		let mut ctx = read_yosys_json(&text.replace(r#""B": "1""#, r#""B": ["1"]"#)).unwrap();
+
+		for &(a, b) in [(false, false), (true, false), (false, true), (true, true)].iter() {
+			set_inputs(&mut ctx, &[a, b]);
+			ctx.run(5);
+
+			assert_eq!(output(&ctx, "and_not"), WireValue::Bool(a && !b), "{} {}", a, b);
+			assert_eq!(output(&ctx, "or_not"), WireValue::Bool(a || !b), "{} {}", a, b);
+			assert_eq!(output(&ctx, "nmux"), WireValue::Bool(!(if b { true } else { a })), "{} {}", a, b);
+		}
+	}
+
+	#[test]
+	fn flip_flops() {
+		// A falling edge flip-flop with an active low reset, next to a rising
+		// edge one without a reset
+		let text = netlist(
+			r#""d": {"direction": "input", "bits": [2]},
+			"clk": {"direction": "input", "bits": [3]},
+			"rst_n": {"direction": "input", "bits": [4]},
+			"q": {"direction": "output", "bits": [5, 6]}"#,
+			r#""$1": {"type": "$_DFF_NN0_", "connections": {"D": [2], "C": [3], "R": [4], "Q": [5]}},
+			"$2": {"type": "$_DFF_P_", "connections": {"D": [2], "C": [3], "Q": [6]}}"#);
+
+		let mut ctx = read_yosys_json(&text).unwrap();
+		let q = |ctx: &WireContext| (output(ctx, "q[0]"), output(ctx, "q[1]"));
+		let (low, high) = (WireValue::Bool(false), WireValue::Bool(true));
+
+		// Held in reset, while the other one clocks in D
+		set_inputs(&mut ctx, &[true, false, false]);
+		ctx.run(5);
+		set_inputs(&mut ctx, &[true, true, false]);
+		ctx.run(5);
+		assert_eq!(q(&ctx), (low, high));
+
+		set_inputs(&mut ctx, &[true, false, false]);
+		ctx.run(5);
+		assert_eq!(q(&ctx), (low, high));
+
+		// Out of reset, D is only taken as the clock falls
+		set_inputs(&mut ctx, &[true, false, true]);
+		ctx.run(5);
+		set_inputs(&mut ctx, &[true, true, true]);
+		ctx.run(5);
+		assert_eq!(q(&ctx), (low, high));
+
+		set_inputs(&mut ctx, &[true, false, true]);
+		ctx.run(5);
+		assert_eq!(q(&ctx), (high, high));
+
+		set_inputs(&mut ctx, &[false, false, true]);
+		ctx.run(5);
+		assert_eq!(q(&ctx), (high, high));
+
+		set_inputs(&mut ctx, &[false, true, true]);
+		ctx.run(5);
+		assert_eq!(q(&ctx), (high, low));
+
+		// Reset clears Q without a clock
+		set_inputs(&mut ctx, &[false, true, false]);
+		ctx.run(5);
+		assert_eq!(q(&ctx), (low, low));
+	}
+
+	#[test]
+	fn driver_errors() {
+		let ports = r#""a": {"direction": "input", "bits": [2]}, "y": {"direction": "output", "bits": [4]}"#;
+
+		assert_eq!(error(&netlist(ports, r#"
+			"$1": {"type": "$_NOT_", "connections": {"A": [2], "Y": [4]}},
+			"$2": {"type": "$_NOT_", "connections": {"A": [2], "Y": [4]}}"#)),
+			"net 4 has more than one driver");
+
+		assert_eq!(error(&netlist(ports, r#"
+			"$1": {"type": "$_NOT_", "connections": {"A": [2], "Y": [2]}}"#)),
+			"net 2 has more than one driver");
+
+		assert_eq!(error(&netlist(ports, r#"
+			"$1": {"type": "$_NOT_", "connections": {"A": [2], "Y": [4]}},
+			"$2": {"type": "$_BUF_", "connections": {"A": [2], "Y": [4]}}"#)),
+			"cell '$2' drives a net that already has a driver");
+
+		assert_eq!(error(&netlist(ports, r#"
+			"$1": {"type": "$_BUF_", "connections": {"A": [5], "Y": [4]}},
+			"$2": {"type": "$_BUF_", "connections": {"A": [4], "Y": [5]}}"#)),
+			"buffers form a loop");
+
+		assert_eq!(error(&netlist(ports, r#"
+			"$1": {"type": "$_BUF_", "connections": {"A": [5], "Y": [4]}}"#)),
+			"net 5 has no driver");
+	}
+
+	#[test]
+	fn cell_errors() {
+		let ports = r#""a": {"direction": "input", "bits": [2, 3]}, "y": {"direction": "output", "bits": [4]}"#;
+		let cell = |cell: &str| error(&netlist(ports, cell));
+
+		assert_eq!(cell(r#""$1": {"type": "$_AND_", "connections": {"A": [2, 3], "B": [3], "Y": [4]}}"#),
+			"cell '$1' ($_AND_) needs a one bit 'A' connection");
+		assert_eq!(cell(r#""$1": {"type": "$_AND_", "connections": {"A": [2], "Y": [4]}}"#),
+			"cell '$1' ($_AND_) needs a one bit 'B' connection");
+		assert_eq!(cell(r#""$1": {"type": "$_DFF_PN0_", "connections": {"D": [2], "C": [3], "Q": [4]}}"#),
+			"cell '$1' ($_DFF_PN0_) needs a one bit 'R' connection");
+		assert_eq!(cell(r#""$1": {"type": "$_DFF_PP1_", "connections": {}}"#),
+			"cell '$1' ($_DFF_PP1_) resets to 1, but DFlipFlop can only reset to 0");
+
+		assert_eq!(cell(r#""$add$top.v:3$1": {"type": "$add", "connections": {"A": [2], "B": [3], "Y": [4]}}"#),
+			"cell '$add$top.v:3$1' has word-level type '$add', run synth or techmap first");
+		assert_eq!(cell(r#""u_sub": {"type": "sub", "connections": {"A": [2], "Y": [4]}}"#),
+			"cell 'u_sub' is an instance of module 'sub', flatten the design first");
+		assert_eq!(cell(r#""$1": {"type": "$_SR_PP_", "connections": {}}"#),
+			"cell '$1' has unsupported type '$_SR_PP_'");
+		assert_eq!(cell(r#""$1": {"connections": {}}"#), "cell '$1' has no type");
+	}
+
+	#[test]
+	fn module_errors() {
+		assert!(match read_yosys_json("{\"modules\": ") { Err(NetlistError::Json(_)) => true, _ => false });
+		assert_eq!(error("{}"), "no modules");
+
+		let two = r#"{"modules": {"a": {"ports": {}}, "b": {"ports": {}}}}"#;
+		assert_eq!(error(two), "can't tell which module is the top one, mark one with the top attribute");
+
+		assert_eq!(error(&netlist(r#""a": {"direction": "inout", "bits": [2]}"#, "")),
+			"port 'a' must be an input or output");
+		assert_eq!(error(&netlist(r#""a": {"direction": "input", "bits": ["q"]}"#, "")),
+			"port 'a' has invalid bits");
+	}
+}
@@ -0,0 +1,65 @@
+use wire::*;
+
+pub type ObserverId = u32;
+
+/// Something that happened in a WireContext. Ports are `(node, port)`, with
+/// `from` being an output and `to` an input
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WireEvent {
+	/// A connection picked up a new value from its source
+	ValueChanged { from: (u32, u32), to: (u32, u32), value: WireValue },
+
+	NodeAdded(u32),
+	NodeRemoved(u32),
+
+	Connected { from: (u32, u32), to: (u32, u32) },
+	Disconnected { from: (u32, u32), to: (u32, u32) },
+
+	/// The drivers of a bus started disagreeing
+	BusConflict { to: (u32, u32) },
+}
+
+/// Which events an observer is interested in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WireFilter {
+	All,
+	/// Value changes on connections from an output port
+	Port(u32, u32),
+	/// Value changes on one connection
+	Connection { from: (u32, u32), to: (u32, u32) },
+	/// Nodes being added or removed
+	Nodes,
+	/// Connections being made or broken
+	Connections,
+	/// Buses going into conflict
+	Conflicts,
+}
+
+impl WireFilter {
+	pub fn matches(&self, event: &WireEvent) -> bool {
+		match (*self, *event) {
+			(WireFilter::All, _) => true,
+
+			(WireFilter::Port(node, port), WireEvent::ValueChanged { from, .. }) => from == (node, port),
+
+			(WireFilter::Connection { from, to }, WireEvent::ValueChanged { from: f, to: t, .. }) =>
+				from == f && to == t,
+
+			(WireFilter::Nodes, WireEvent::NodeAdded(_)) |
+			(WireFilter::Nodes, WireEvent::NodeRemoved(_)) => true,
+
+			(WireFilter::Connections, WireEvent::Connected { .. }) |
+			(WireFilter::Connections, WireEvent::Disconnected { .. }) => true,
+
+			(WireFilter::Conflicts, WireEvent::BusConflict { .. }) => true,
+
+			_ => false,
+		}
+	}
+}
+
+pub struct Observer {
+	pub id: ObserverId,
+	pub filter: WireFilter,
+	pub callback: Box<FnMut(&WireEvent)>,
+}
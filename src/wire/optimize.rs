@@ -0,0 +1,444 @@
+//! Simplifying a circuit without changing what it outputs. Passes run until
+//! none of them finds anything more to do:
+//!
+//! - outputs of pure nodes fed only by constants are replaced by constants
+//! - pure nodes with the same type and params, fed from the same ports, are
+//!   merged into one
+//! - nodes whose outputs can't reach a node without outputs (like Output),
+//!   or a port used by a breakpoint, watch or observer, are removed
+//!
+//! Values move along one connection per step, so with fewer nodes in the
+//! way a folded circuit can settle a few ticks sooner, but it behaves the
+//! same from then on. Nodes that are left keep their ids and state.
+
+use wire::*;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A circuit after optimize, along with what happened to the nodes
+pub struct Optimized {
+	pub context: WireContext,
+
+	/// Where each node that's left ended up: itself, or the node it was
+	/// merged into. Nodes that were removed aren't included
+	pub ids: HashMap<u32, u32>,
+
+	/// How many outputs were replaced by constants
+	pub folded: usize,
+	pub merged: usize,
+	pub removed: usize,
+}
+
+impl fmt::Display for Optimized {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} nodes after folding {} outputs, merging {} nodes and removing {}",
+			self.context.nodes.len(), self.folded, self.merged, self.removed)
+	}
+}
+
+/// Whether a node's ports are used by something outside the circuit
+fn is_root(ctx: &WireContext, node_id: u32) -> bool {
+	let watched = |port: (u32, u32)| port.0 == node_id;
+
+	ctx.get_node(node_id).map_or(false, |n| n.get_num_outputs() == 0)
+		|| ctx.breakpoints.iter().any(|b| watched(b.port))
+		|| ctx.watches.iter().any(|w| {
+			Some(&w.expr.first).into_iter().chain(w.expr.rest.iter().map(|r| &r.1))
+				.any(|term| match *term {
+					WatchTerm::Port(node, port) => watched((node, port)),
+					WatchTerm::Value(_) => false,
+				})
+		})
+		|| ctx.observers.iter().any(|o| match o.filter {
+			WireFilter::Port(node, port) => watched((node, port)),
+			WireFilter::Connection { from, to } => watched(from) || watched(to),
+			_ => false,
+		})
+}
+
+fn has_bus_input(ctx: &WireContext, node_id: u32, num_inputs: u32) -> bool {
+	(0..num_inputs).any(|port| ctx.get_bus((node_id, port)).is_some())
+}
+
+/// Moves every connection from one output port to another. Does nothing if
+/// that would leave two connections between the same ports, as can happen
+/// with buses
+fn rewire(ctx: &mut WireContext, from: (u32, u32), to: (u32, u32)) -> bool {
+	let targets: Vec<_> = ctx.connections.iter()
+		.filter(|c| c.from() == from)
+		.map(|c| c.to())
+		.collect();
+
+	if targets.is_empty() || ctx.connections.iter().any(|c| c.from() == to && targets.contains(&c.to())) {
+		return false;
+	}
+
+	for c in ctx.connections.iter_mut().filter(|c| c.from() == from) {
+		c.input_node = to.0;
+		c.input_port = to.1;
+	}
+
+	for target in targets {
+		ctx.emit(WireEvent::Disconnected { from, to: target });
+		ctx.emit(WireEvent::Connected { from: to, to: target });
+	}
+
+	true
+}
+
+/// Replaces the used outputs of pure nodes whose inputs all come from
+/// constants, or have nothing connected, with constants. Returns how many
+/// outputs were replaced
+fn fold_constants(ctx: &mut WireContext) -> usize {
+	let mut folded = 0;
+	let ids: Vec<u32> = ctx.nodes.iter().map(|n| n.0).collect();
+
+	for node_id in ids {
+		let (num_inputs, num_outputs) = match ctx.get_node(node_id) {
+			Some(node) if node.is_pure() && node.get_num_inputs() > 0 =>
+				(node.get_num_inputs(), node.get_num_outputs()),
+			_ => continue,
+		};
+
+		if has_bus_input(ctx, node_id, num_inputs) { continue }
+
+		let mut inputs = Vec::new();
+		for port in 0..num_inputs {
+			let value = match ctx.connections.iter().find(|c| c.to() == (node_id, port)) {
+				None => WireValue::Null,
+				Some(c) => match ctx.get_node(c.input_node) {
+					Some(source) if source.is_pure() && source.get_num_inputs() == 0 =>
+						source.get_output(c.input_port),
+					_ => break,
+				},
+			};
+
+			inputs.push(value);
+		}
+
+		if inputs.len() != num_inputs as usize { continue }
+
+		let ticks = ctx.get_ticks();
+		let outputs: Vec<_> = {
+			let node = ctx.get_node_mut(node_id).unwrap();
+
+			for (port, &value) in inputs.iter().enumerate() {
+				deliver(node, port as u32, value);
+			}
+
+			node.update(ticks);
+			(0..num_outputs).map(|port| node.get_output(port)).collect()
+		};
+
+		for (port, &value) in outputs.iter().enumerate() {
+			let from = (node_id, port as u32);
+			if !ctx.connections.iter().any(|c| c.from() == from) { continue }
+
+			let constant = match value {
+				WireValue::Int(value) => ctx.add_node(ConstantNode { value }),
+				WireValue::Bool(value) => ctx.add_node(BoolConstantNode { value }),
+				_ => continue,
+			};
+
+			if rewire(ctx, from, (constant, 0)) {
+				folded += 1;
+			} else {
+				ctx.remove_node(constant);
+			}
+		}
+	}
+
+	folded
+}
+
+/// Merges pure nodes that would always output the same values into the
+/// one with the lowest id, recording where each went in `merges`. Returns
+/// how many were merged
+fn merge_duplicates(ctx: &mut WireContext, merges: &mut HashMap<u32, u32>) -> usize {
+	let mut merged = 0;
+	let mut seen: HashMap<String, u32> = HashMap::new();
+
+	let node_ids: Vec<u32> = ctx.nodes.iter().map(|n| n.0).collect();
+
+	for node_id in node_ids {
+		let (key, num_outputs) = match ctx.get_node(node_id) {
+			Some(node) if node.is_pure() && !has_bus_input(ctx, node_id, node.get_num_inputs()) => {
+				let params: Vec<_> = node.get_params().iter().map(|p| p.format_value()).collect();
+				let sources: Vec<_> = (0..node.get_num_inputs())
+					.map(|port| ctx.connections.iter().find(|c| c.to() == (node_id, port)).map(|c| c.from()))
+					.collect();
+
+				(format!("{} {:?} {:?}", node.get_type_name(), params, sources), node.get_num_outputs())
+			}
+
+			_ => continue,
+		};
+
+		let keep = match seen.get(&key) {
+			Some(&keep) => keep,
+			None => {
+				seen.insert(key, node_id);
+				continue;
+			}
+		};
+
+		if is_root(ctx, node_id) { continue }
+
+		// Both have the same outputs, so either all of them move or none do
+		let movable = (0..num_outputs).all(|port| {
+			!ctx.connections.iter().any(|c| c.from() == (node_id, port) &&
+				ctx.connections.iter().any(|k| k.from() == (keep, port) && k.to() == c.to()))
+		});
+
+		if !movable { continue }
+
+		for port in 0..num_outputs {
+			rewire(ctx, (node_id, port), (keep, port));
+		}
+
+		ctx.remove_node(node_id);
+		merges.insert(node_id, keep);
+		merged += 1;
+	}
+
+	merged
+}
+
+/// Removes nodes that don't lead to a root, returning how many there were
+fn remove_unused(ctx: &mut WireContext) -> usize {
+	let mut used: HashSet<u32> = ctx.nodes.iter()
+		.map(|n| n.0)
+		.filter(|&id| is_root(ctx, id))
+		.collect();
+
+	loop {
+		let count = used.len();
+
+		for c in ctx.connections.iter() {
+			if used.contains(&c.output_node) {
+				used.insert(c.input_node);
+			}
+		}
+
+		if used.len() == count { break }
+	}
+
+	let unused: Vec<u32> = ctx.nodes.iter()
+		.map(|n| n.0)
+		.filter(|id| !used.contains(id))
+		.collect();
+
+	ctx.remove_connections(|c| !used.contains(&c.input_node) || !used.contains(&c.output_node));
+
+	for &node_id in unused.iter() {
+		ctx.remove_node(node_id);
+	}
+
+	unused.len()
+}
+
+/// Simplifies a circuit, see the module docs. Sinks, observers, breakpoints
+/// and watches stay with the context
+pub fn optimize(ctx: WireContext) -> Optimized {
+	let mut ctx = ctx;
+	let old_ids: Vec<u32> = ctx.nodes.iter().map(|n| n.0).collect();
+	let mut merges = HashMap::new();
+
+	let mut removed = remove_unused(&mut ctx);
+	let mut folded = 0;
+	let mut merged = 0;
+
+	loop {
+		let changes = (fold_constants(&mut ctx), merge_duplicates(&mut ctx, &mut merges));
+		if changes == (0, 0) { break }
+
+		folded += changes.0;
+		merged += changes.1;
+	}
+
+	removed += remove_unused(&mut ctx);
+
+	// Nodes can be merged into nodes that are merged later themselves
+	let mut ids = HashMap::new();
+	for old in old_ids {
+		let mut new = old;
+		while let Some(&next) = merges.get(&new) {
+			new = next;
+		}
+
+		if ctx.get_node(new).is_some() {
+			ids.insert(old, new);
+		}
+	}
+
+	Optimized { context: ctx, ids, folded, merged, removed }
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// What each Output node last received, by id
+	fn outputs(ctx: &WireContext) -> Vec<(u32, String)> {
+		ctx.nodes.iter()
+			.filter(|n| n.1.get_type_name() == "Output")
+			.map(|n| (n.0, n.1.get_label()))
+			.collect()
+	}
+
+	/// Optimizes the circuit `build` makes, checking that once settled it
+	/// outputs the same as the original for a while
+	fn check_equivalent(build: fn() -> WireContext) -> Optimized {
+		let mut original = build();
+		let mut optimized = optimize(build());
+
+		for tick in 0..30 {
+			original.step();
+			optimized.context.step();
+
+			if tick >= 10 {
+				assert_eq!(outputs(&optimized.context), outputs(&original), "tick {}", tick);
+			}
+		}
+
+		optimized
+	}
+
+	fn folding_circuit() -> WireContext {
+		let mut ctx = WireContext::new();
+		let two = ctx.add_node(ConstantNode { value: 2 });
+		let three = ctx.add_node(ConstantNode { value: 3 });
+		let add = ctx.add_node(AddNode::new(2));
+		let neg = ctx.add_node(UnaryOpNode::new(UnaryOp::Neg));
+		let counter = ctx.add_node(CounterNode::new());
+		let mul = ctx.add_node(BinaryOpNode::new(BinaryOp::Mul));
+		let out = ctx.add_node(OutputNode::new("out"));
+
+		ctx.add_connection((two, 0), (add, 0));
+		ctx.add_connection((three, 0), (add, 1));
+		ctx.add_connection((add, 0), (neg, 0));
+		ctx.add_connection((neg, 0), (mul, 0));
+		ctx.add_connection((counter, 0), (mul, 1));
+		ctx.add_connection((mul, 0), (out, 0));
+		ctx
+	}
+
+	#[test]
+	fn folds_constants() {
+		let optimized = check_equivalent(folding_circuit);
+		assert_eq!(optimized.folded, 2);
+
+		// Add and Negate are replaced by a -5 feeding the Multiply
+		let ctx = &optimized.context;
+		assert!(ctx.get_node(2).is_none() && ctx.get_node(3).is_none());
+		assert!(!optimized.ids.contains_key(&2) && !optimized.ids.contains_key(&3));
+
+		let source = ctx.connections.iter().find(|c| c.to() == (5, 0)).unwrap().input_node;
+		assert_eq!(ctx.get_node(source).unwrap().get_output(0), WireValue::Int(-5));
+		assert_eq!(optimized.ids[&5], 5);
+	}
+
+	/// Three Nots in a row of outputs, fed by two identical Compares. The
+	/// ids put the Nots first, so merging the Compares lets the Nots merge
+	/// a pass later than they otherwise would
+	fn merging_circuit() -> WireContext {
+		let mut ctx = WireContext::new();
+		ctx.insert_node(10, box CounterNode::new());
+		ctx.insert_node(11, box ConstantNode { value: 3 });
+
+		for &id in [4, 5].iter() {
+			ctx.insert_node(id, box CompareNode::new(CompareOp::Gt));
+			ctx.add_connection((10, 0), (id, 0));
+			ctx.add_connection((11, 0), (id, 1));
+		}
+
+		for &(id, compare) in [(1, 4), (2, 5), (3, 5)].iter() {
+			ctx.insert_node(id, box NotNode::new());
+			ctx.insert_node(20 + id, box OutputNode::new(&format!("out{}", id)));
+			ctx.add_connection((compare, 0), (id, 0));
+			ctx.add_connection((id, 0), (20 + id, 0));
+		}
+
+		ctx
+	}
+
+	#[test]
+	fn merges_duplicates() {
+		let optimized = check_equivalent(merging_circuit);
+		assert_eq!(optimized.merged, 3);
+
+		// 3 went into 2, and then 2 into 1
+		assert_eq!(optimized.ids[&1], 1);
+		assert_eq!(optimized.ids[&2], 1);
+		assert_eq!(optimized.ids[&3], 1);
+		assert_eq!(optimized.ids[&4], 4);
+		assert_eq!(optimized.ids[&5], 4);
+
+		let mut ids: Vec<u32> = optimized.context.nodes.iter().map(|n| n.0).collect();
+		ids.sort();
+		assert_eq!(ids, vec![1, 4, 10, 11, 21, 22, 23]);
+
+		let targets: Vec<_> = optimized.context.connections.iter()
+			.filter(|c| c.from() == (1, 0))
+			.map(|c| c.to().0)
+			.collect();
+		assert_eq!(targets.len(), 3);
+	}
+
+	fn dead_branch_circuit() -> WireContext {
+		let mut ctx = WireContext::new();
+		let counter = ctx.add_node(CounterNode::new());
+		let used = ctx.add_node(UnaryOpNode::new(UnaryOp::Neg));
+		let out = ctx.add_node(OutputNode::new("out"));
+		ctx.add_connection((counter, 0), (used, 0));
+		ctx.add_connection((used, 0), (out, 0));
+
+		// Leads nowhere
+		let dead = ctx.add_node(UnaryOpNode::new(UnaryOp::Abs));
+		let deader = ctx.add_node(CounterNode::new());
+		ctx.add_connection((counter, 0), (dead, 0));
+		ctx.add_connection((deader, 1), (dead, 0));
+		ctx
+	}
+
+	#[test]
+	fn removes_dead_branches() {
+		let optimized = check_equivalent(dead_branch_circuit);
+		assert_eq!((optimized.folded, optimized.merged, optimized.removed), (0, 0, 2));
+
+		let mut ids: Vec<(u32, u32)> = optimized.ids.iter().map(|(&a, &b)| (a, b)).collect();
+		ids.sort();
+		assert_eq!(ids, vec![(0, 0), (1, 1), (2, 2)]);
+		assert_eq!(optimized.context.connections.len(), 2);
+	}
+
+	#[test]
+	fn keeps_watched_nodes() {
+		let mut ctx = WireContext::new();
+		let counter = ctx.add_node(CounterNode::new());
+
+		let nodes: Vec<u32> = (0..4).map(|_| {
+			let id = ctx.add_node(UnaryOpNode::new(UnaryOp::Neg));
+			ctx.add_connection((counter, 0), (id, 0));
+			id
+		}).collect();
+
+		// Each of these would be merged into the first, or removed, if
+		// nothing looked at them
+		ctx.add_breakpoint((nodes[1], 0), BreakCondition::Changes);
+		ctx.add_watch(&format!("{}:0 > 3", nodes[2])).unwrap();
+		ctx.subscribe(WireFilter::Port(nodes[3], 0), |_| {});
+
+		let optimized = optimize(ctx);
+		assert_eq!((optimized.merged, optimized.removed), (0, 1));
+
+		assert!(optimized.context.get_node(nodes[0]).is_none());
+		for &id in nodes[1..].iter() {
+			assert_eq!(optimized.ids[&id], id);
+		}
+
+		assert_eq!(optimized.ids[&counter], counter);
+	}
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+
+/// Gives a fieldless enum a list of display names and conversions to and
+/// from indices into it, for use as a ParamType::Enum
+#[macro_export]
+macro_rules! param_enum {
+	($ty:ident { $($variant:ident => $name:expr),* $(,)* }) => {
+		impl $ty {
+			pub const NAMES: &'static [&'static str] = &[$($name),*];
+			const ALL: &'static [$ty] = &[$($ty::$variant),*];
+
+			pub fn to_index(&self) -> usize {
+				$ty::ALL.iter().position(|v| v == self).unwrap()
+			}
+
+			pub fn from_index(index: usize) -> Option<$ty> {
+				$ty::ALL.get(index).cloned()
+			}
+
+			pub fn name(&self) -> &'static str {
+				$ty::NAMES[self.to_index()]
+			}
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ParamType {
+	Int { min: i32, max: i32 },
+	Bool,
+	/// Values are indices into the list of names
+	Enum(&'static [&'static str]),
+	String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamValue {
+	Int(i32),
+	Bool(bool),
+	Enum(usize),
+	String(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Param {
+	pub name: &'static str,
+	pub ty: ParamType,
+	pub value: ParamValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamError {
+	Unknown(String),
+	WrongType,
+	OutOfRange,
+	/// The value was the right shape but the node couldn't use it
+	Invalid(String),
+}
+
+impl fmt::Display for ParamError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ParamError::Unknown(ref name) => write!(f, "no parameter named '{}'", name),
+			ParamError::WrongType => write!(f, "wrong type for parameter"),
+			ParamError::OutOfRange => write!(f, "value out of range"),
+			ParamError::Invalid(ref msg) => write!(f, "{}", msg),
+		}
+	}
+}
+
+impl Param {
+	pub fn int(name: &'static str, value: i32, min: i32, max: i32) -> Self {
+		Param { name, ty: ParamType::Int { min, max }, value: ParamValue::Int(value) }
+	}
+
+	pub fn bool(name: &'static str, value: bool) -> Self {
+		Param { name, ty: ParamType::Bool, value: ParamValue::Bool(value) }
+	}
+
+	pub fn enumeration(name: &'static str, value: usize, names: &'static [&'static str]) -> Self {
+		Param { name, ty: ParamType::Enum(names), value: ParamValue::Enum(value) }
+	}
+
+	pub fn string(name: &'static str, value: &str) -> Self {
+		Param { name, ty: ParamType::String, value: ParamValue::String(value.to_string()) }
+	}
+
+	/// Steps the value by `amount`: ints by that much, enums cycle through
+	/// their options, and bools flip. Strings are left unchanged
+	pub fn adjusted(&self, amount: i32) -> ParamValue {
+		match (self.ty, &self.value) {
+			(ParamType::Int { min, max }, &ParamValue::Int(v)) =>
+				ParamValue::Int((v as i64 + amount as i64).max(min as i64).min(max as i64) as i32),
+
+			(ParamType::Bool, &ParamValue::Bool(v)) =>
+				ParamValue::Bool(if amount % 2 != 0 { !v } else { v }),
+
+			(ParamType::Enum(names), &ParamValue::Enum(v)) => {
+				let n = names.len() as i32;
+				ParamValue::Enum(((v as i32 + amount) % n + n) as usize % names.len())
+			}
+
+			(_, value) => value.clone(),
+		}
+	}
+
+	/// Parses a value of this parameter's type from text. Enums are given by
+	/// name
+	pub fn parse(&self, text: &str) -> Result<ParamValue, ParamError> {
+		match self.ty {
+			ParamType::Int { .. } => text.parse().map(ParamValue::Int).map_err(|_| ParamError::WrongType),
+			ParamType::Bool => text.parse().map(ParamValue::Bool).map_err(|_| ParamError::WrongType),
+			ParamType::Enum(names) => names.iter().position(|&n| n == text)
+				.map(ParamValue::Enum)
+				.ok_or(ParamError::OutOfRange),
+			ParamType::String => Ok(ParamValue::String(text.to_string())),
+		}
+	}
+
+	pub fn format_value(&self) -> String {
+		match (self.ty, &self.value) {
+			(ParamType::Enum(names), &ParamValue::Enum(v)) =>
+				names.get(v).cloned().unwrap_or("?").to_string(),
+
+			(_, &ParamValue::Int(v)) => v.to_string(),
+			(_, &ParamValue::Bool(v)) => v.to_string(),
+			(_, &ParamValue::String(ref v)) => v.clone(),
+			_ => "?".to_string(),
+		}
+	}
+}
+
+impl ParamType {
+	pub fn validate(&self, value: &ParamValue) -> Result<(), ParamError> {
+		match (*self, value) {
+			(ParamType::Int { min, max }, &ParamValue::Int(v)) =>
+				if v >= min && v <= max { Ok(()) } else { Err(ParamError::OutOfRange) },
+
+			(ParamType::Enum(names), &ParamValue::Enum(v)) =>
+				if v < names.len() { Ok(()) } else { Err(ParamError::OutOfRange) },
+
+			(ParamType::Bool, &ParamValue::Bool(_)) => Ok(()),
+			(ParamType::String, &ParamValue::String(_)) => Ok(()),
+
+			_ => Err(ParamError::WrongType),
+		}
+	}
+}
+
+/// Checks `value` against the schema of the parameter called `name`. Nodes
+/// call this at the top of set_param so they only need to handle valid
+/// values
+pub fn validate_param(params: &[Param], name: &str, value: &ParamValue) -> Result<(), ParamError> {
+	match params.iter().find(|p| p.name == name) {
+		Some(param) => param.ty.validate(value),
+		None => Err(ParamError::Unknown(name.to_string())),
+	}
+}
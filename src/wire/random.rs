@@ -0,0 +1,206 @@
+use wire::*;
+
+use std::i32;
+
+/// Seed used by random nodes that have neither their own seed nor one
+/// derived from the context
+pub const DEFAULT_SEED: u64 = 0x5eed;
+
+/// Small deterministic generator (splitmix64). Used instead of a library
+/// so sequences never change out from under saved seeds
+#[derive(Copy, Clone, Debug)]
+pub struct Rng { state: u64 }
+
+impl Rng {
+	pub fn new(seed: u64) -> Self {
+		Rng { state: seed }
+	}
+
+	pub fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		z ^ (z >> 31)
+	}
+
+	/// Uniform in `min..=max`
+	pub fn range(&mut self, min: i32, max: i32) -> i32 {
+		let (min, max) = (min.min(max) as i64, min.max(max) as i64);
+		let span = (max - min + 1) as u64;
+		(min + (self.next_u64() % span) as i64) as i32
+	}
+}
+
+/// Mixes a context wide seed with a node id, so that every node gets an
+/// independent but reproducible sequence
+pub fn derive_seed(seed: u64, node_id: u32) -> u64 {
+	Rng::new(seed ^ (node_id as u64).wrapping_mul(0x2545f4914f6cdd1d)).next_u64()
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RandomOutput {
+	Int { min: i32, max: i32 },
+	Bool,
+}
+
+/// Emits a new pseudo-random value on each rising edge of its clock input.
+/// With no seed of its own it takes one from the context, see
+/// WireContext::set_seed
+pub struct RandomNode {
+	pub output: RandomOutput,
+	seed: Option<u32>,
+	context_seed: u64,
+
+	rng: Rng,
+	clock: EdgeDetector,
+	value: WireValue,
+}
+
+const RANDOM_KINDS: &[&str] = &["int", "bool"];
+
+impl RandomNode {
+	pub fn new(output: RandomOutput, seed: Option<u32>) -> Self {
+		let mut node = RandomNode {
+			output,
+			seed,
+			context_seed: DEFAULT_SEED,
+
+			rng: Rng::new(DEFAULT_SEED),
+			clock: EdgeDetector::new(),
+			value: WireValue::Null,
+		};
+
+		node.reseed();
+		node
+	}
+
+	/// Restarts the sequence from whichever seed applies
+	fn reseed(&mut self) {
+		self.rng = Rng::new(self.seed.map(|s| s as u64).unwrap_or(self.context_seed));
+		self.generate();
+	}
+
+	fn generate(&mut self) {
+		self.value = match self.output {
+			RandomOutput::Int { min, max } => WireValue::Int(self.rng.range(min, max)),
+			RandomOutput::Bool => WireValue::Bool(self.rng.next_u64() & 1 != 0),
+		};
+	}
+}
+
+impl WireNode for RandomNode {
+	fn get_type_name(&self) -> &'static str { "Random" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Source,
+			help: "Emits a new pseudo-random value on each rising edge of Clock",
+			inputs: vec![PortInfo::bool("Clock", "")],
+			outputs: vec![PortInfo::any("value", "An Int or a Bool, depending on the kind param")],
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port != 0 { return }
+		if let Some(val) = value.to_bool() {
+			self.clock.level = val;
+		}
+	}
+
+	fn on_seed(&mut self, seed: u64) {
+		self.context_seed = seed;
+
+		if self.seed.is_none() {
+			self.reseed();
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		if self.clock.rising() {
+			self.generate();
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { self.value } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		match self.value {
+			WireValue::Int(v) => format!("rand {}", v),
+			WireValue::Bool(v) => format!("rand {}", v),
+			_ => "rand".to_string(),
+		}
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		let mut state = vec![
+			self.rng.state as i32,
+			(self.rng.state >> 32) as i32,
+			self.clock.prev_level as i32,
+		];
+
+		state.extend_from_slice(&self.value.to_state());
+		state
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if state.len() != 5 { return }
+
+		self.rng.state = state[0] as u32 as u64 | (state[1] as u32 as u64) << 32;
+		self.clock.prev_level = state[2] != 0;
+		self.value = WireValue::from_state(&state[3..5]);
+	}
+
+	/// A seed of 0 means the seed comes from the context
+	fn get_params(&self) -> Vec<Param> {
+		let (kind, min, max) = match self.output {
+			RandomOutput::Int { min, max } => (0, min, max),
+			RandomOutput::Bool => (1, 0, 1),
+		};
+
+		vec![
+			Param::enumeration("kind", kind, RANDOM_KINDS),
+			Param::int("min", min, i32::MIN, i32::MAX),
+			Param::int("max", max, i32::MIN, i32::MAX),
+			Param::int("seed", self.seed.unwrap_or(0) as i32, 0, i32::MAX),
+		]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		// The range only applies to ints, so is ignored for bools
+		match (name, value) {
+			("kind", ParamValue::Enum(0)) => if self.output == RandomOutput::Bool {
+				self.output = RandomOutput::Int { min: 0, max: 1 };
+			},
+
+			("kind", _) => self.output = RandomOutput::Bool,
+
+			("min", ParamValue::Int(v)) => if let RandomOutput::Int { ref mut min, .. } = self.output {
+				*min = v;
+			},
+
+			("max", ParamValue::Int(v)) => if let RandomOutput::Int { ref mut max, .. } = self.output {
+				*max = v;
+			},
+
+			("seed", ParamValue::Int(0)) => self.seed = None,
+			("seed", ParamValue::Int(v)) => self.seed = Some(v as u32),
+			_ => {}
+		}
+
+		self.reseed();
+		Ok(())
+	}
+}
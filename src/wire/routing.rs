@@ -0,0 +1,493 @@
+use wire::*;
+
+pub const MAX_CHANNELS: u32 = 32;
+
+fn check_channels(channels: u32) {
+	assert!(channels > 0 && channels <= MAX_CHANNELS,
+		"Channel count must be between 1 and {}", MAX_CHANNELS);
+}
+
+
+/// Passes one of N channels through to its output. Inputs are the channels
+/// followed by Select; output is the selected channel, Null if Select is
+/// out of range, or Error if Select is
+pub struct MuxNode {
+	channels: Vec<WireValue>,
+	select: i32,
+	/// Whether Select is Error
+	error: bool,
+}
+
+impl MuxNode {
+	pub fn new(channels: u32) -> Self {
+		check_channels(channels);
+
+		MuxNode {
+			channels: vec![WireValue::Null; channels as usize],
+			select: 0,
+			error: false,
+		}
+	}
+
+	fn selected(&self) -> WireValue {
+		if self.error { return WireValue::Error }
+		if self.select < 0 { return WireValue::Null }
+		self.channels.get(self.select as usize).cloned().unwrap_or(WireValue::Null)
+	}
+}
+
+impl WireNode for MuxNode {
+	fn get_type_name(&self) -> &'static str { "Mux" }
+
+	fn get_num_inputs(&self) -> u32 { self.channels.len() as u32 + 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Routing,
+			help: "Passes the selected channel through",
+			inputs: {
+				let mut inputs = PortInfo::numbered("in", PortType::Any, "A channel", self.channels.len() as u32);
+				inputs.push(PortInfo::int("Select", "Index of the channel to pass through"));
+				inputs
+			},
+			outputs: vec![PortInfo::any("out", "The selected channel, Null if Select is out of range, or Error if Select is")],
+		}
+	}
+
+	/// Disconnected channels pass Null through
+	fn get_input_default(&self, port: u32) -> WireValue {
+		if port == self.channels.len() as u32 { WireValue::Int(0) } else { WireValue::Null }
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		let num_channels = self.channels.len() as u32;
+
+		if port < num_channels {
+			self.channels[port as usize] = value;
+		} else if port == num_channels {
+			if value == WireValue::Error {
+				self.error = true;
+			} else if let Some(select) = value.to_int() {
+				self.select = select;
+				self.error = false;
+			}
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { self.selected() } else { WireValue::Null }
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		if self.error { return "mux error".to_string() }
+		format!("mux #{}", self.select)
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("channels", self.channels.len() as i32, 1, MAX_CHANNELS as i32)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.channels.resize(v as usize, WireValue::Null);
+		}
+
+		Ok(())
+	}
+
+	fn save_state(&self) -> Vec<i32> { vec![self.select, self.error as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		match *state {
+			[select] => self.select = select,
+			[select, error] => {
+				self.select = select;
+				self.error = error != 0;
+			}
+			_ => {}
+		}
+	}
+}
+
+
+/// Routes its input to one of N outputs. Inputs are Data, Select. Outputs
+/// that aren't selected carry the zero value of the input's type, and every
+/// output is Error while Select is
+pub struct DemuxNode {
+	num_channels: u32,
+	input: WireValue,
+	select: i32,
+	/// Whether Select is Error
+	error: bool,
+}
+
+impl DemuxNode {
+	pub fn new(channels: u32) -> Self {
+		check_channels(channels);
+
+		DemuxNode {
+			num_channels: channels,
+			input: WireValue::Null,
+			select: 0,
+			error: false,
+		}
+	}
+}
+
+impl WireNode for DemuxNode {
+	fn get_type_name(&self) -> &'static str { "Demux" }
+
+	fn get_num_inputs(&self) -> u32 { 2 }
+	fn get_num_outputs(&self) -> u32 { self.num_channels }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Routing,
+			help: "Routes Data to the selected output. The others carry the zero value of its type, and all are Error if Select is",
+			inputs: vec![
+				PortInfo::any("Data", ""),
+				PortInfo::int("Select", "Index of the output to route to"),
+			],
+			outputs: PortInfo::numbered("out", PortType::Any, "", self.num_channels),
+		}
+	}
+
+	fn get_input_default(&self, port: u32) -> WireValue {
+		if port == 1 { WireValue::Int(0) } else { WireValue::Null }
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		match port {
+			0 => self.input = value,
+			1 => if value == WireValue::Error {
+				self.error = true;
+			} else if let Some(select) = value.to_int() {
+				self.select = select;
+				self.error = false;
+			},
+			_ => {}
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port >= self.num_channels { return WireValue::Null }
+		if self.error { return WireValue::Error }
+
+		if port as i32 == self.select {
+			self.input
+		} else {
+			match self.input {
+				WireValue::Int(_) => WireValue::Int(0),
+				WireValue::Bool(_) => WireValue::Bool(false),
+				_ => WireValue::Null,
+			}
+		}
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		if self.error { return "demux error".to_string() }
+		format!("demux #{}", self.select)
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("channels", self.num_channels as i32, 1, MAX_CHANNELS as i32)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.num_channels = v as u32;
+		}
+
+		Ok(())
+	}
+
+	fn save_state(&self) -> Vec<i32> { vec![self.select, self.error as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		match *state {
+			[select] => self.select = select,
+			[select, error] => {
+				self.select = select;
+				self.error = error != 0;
+			}
+			_ => {}
+		}
+	}
+}
+
+
+/// Binary to one-hot. Input is an index; output N is true when the index is N
+pub struct DecoderNode {
+	num_outputs: u32,
+	input: i32,
+	/// Whether the input is Error, which every output then is too
+	error: bool,
+}
+
+impl DecoderNode {
+	pub fn new(outputs: u32) -> Self {
+		check_channels(outputs);
+
+		DecoderNode {
+			num_outputs: outputs,
+			input: 0,
+			error: false,
+		}
+	}
+}
+
+impl WireNode for DecoderNode {
+	fn get_type_name(&self) -> &'static str { "Decoder" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { self.num_outputs }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Routing,
+			help: "Binary to one-hot",
+			inputs: vec![PortInfo::int("in", "")],
+			outputs: PortInfo::numbered("out", PortType::Bool, "True when the input is its index, Error when the input is", self.num_outputs),
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Int(0) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port != 0 { return }
+
+		if value == WireValue::Error {
+			self.error = true;
+		} else if let Some(val) = value.to_int() {
+			self.input = val;
+			self.error = false;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port >= self.num_outputs { return WireValue::Null }
+		if self.error { return WireValue::Error }
+		WireValue::Bool(port as i32 == self.input)
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		if self.error { return "decode error".to_string() }
+		format!("decode {}", self.input)
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("outputs", self.num_outputs as i32, 1, MAX_CHANNELS as i32)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.num_outputs = v as u32;
+		}
+
+		Ok(())
+	}
+
+	fn save_state(&self) -> Vec<i32> { vec![self.input] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [input] = *state { self.input = input }
+	}
+}
+
+
+/// One-hot to binary. Outputs are the index of the high input, and whether
+/// exactly one input is high. The index is 0 when the input isn't one-hot
+pub struct EncoderNode {
+	/// None while an input is Error
+	inputs: Vec<Option<bool>>,
+}
+
+impl EncoderNode {
+	pub fn new(inputs: u32) -> Self {
+		check_channels(inputs);
+
+		EncoderNode {
+			inputs: vec![Some(false); inputs as usize],
+		}
+	}
+
+	fn encode(&self) -> Option<i32> {
+		let mut high = self.inputs.iter().enumerate().filter(|&(_, &v)| v == Some(true));
+
+		match (high.next(), high.next()) {
+			(Some((index, _)), None) => Some(index as i32),
+			_ => None,
+		}
+	}
+}
+
+impl WireNode for EncoderNode {
+	fn get_type_name(&self) -> &'static str { "Encoder" }
+
+	fn get_num_inputs(&self) -> u32 { self.inputs.len() as u32 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Routing,
+			help: "One-hot to binary",
+			inputs: PortInfo::numbered("in", PortType::Bool, "", self.get_num_inputs()),
+			outputs: vec![
+				PortInfo::int("index", "Index of the high input, or 0 if the inputs aren't one-hot"),
+				PortInfo::bool("valid", "Whether exactly one input is high"),
+			],
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port >= self.get_num_inputs() { return }
+
+		if value == WireValue::Error {
+			self.inputs[port as usize] = None;
+		} else if let Some(val) = value.to_bool() {
+			self.inputs[port as usize] = Some(val);
+		}
+	}
+
+	/// Both outputs are Error while any input is
+	fn get_output(&self, port: u32) -> WireValue {
+		if port < 2 && self.inputs.contains(&None) { return WireValue::Error }
+
+		match port {
+			0 => WireValue::Int(self.encode().unwrap_or(0)),
+			1 => WireValue::Bool(self.encode().is_some()),
+			_ => WireValue::Null,
+		}
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		if self.inputs.contains(&None) { return "encode error".to_string() }
+
+		match self.encode() {
+			Some(index) => format!("encode {}", index),
+			None => "encode ?".to_string(),
+		}
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("inputs", self.inputs.len() as i32, 1, MAX_CHANNELS as i32)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.inputs.resize(v as usize, Some(false));
+		}
+
+		Ok(())
+	}
+}
+
+
+/// Outputs the index of the highest numbered high input, and whether any
+/// input is high. The index is 0 when no input is high
+pub struct PriorityEncoderNode {
+	/// None while an input is Error
+	inputs: Vec<Option<bool>>,
+}
+
+impl PriorityEncoderNode {
+	pub fn new(inputs: u32) -> Self {
+		check_channels(inputs);
+
+		PriorityEncoderNode {
+			inputs: vec![Some(false); inputs as usize],
+		}
+	}
+
+	fn encode(&self) -> Option<i32> {
+		self.inputs.iter().rposition(|&v| v == Some(true)).map(|i| i as i32)
+	}
+}
+
+impl WireNode for PriorityEncoderNode {
+	fn get_type_name(&self) -> &'static str { "PriorityEncoder" }
+
+	fn get_num_inputs(&self) -> u32 { self.inputs.len() as u32 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Routing,
+			help: "Finds the highest numbered high input",
+			inputs: PortInfo::numbered("in", PortType::Bool, "", self.get_num_inputs()),
+			outputs: vec![
+				PortInfo::int("index", "Index of the highest high input, or 0 if none are"),
+				PortInfo::bool("valid", "Whether any input is high"),
+			],
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port >= self.get_num_inputs() { return }
+
+		if value == WireValue::Error {
+			self.inputs[port as usize] = None;
+		} else if let Some(val) = value.to_bool() {
+			self.inputs[port as usize] = Some(val);
+		}
+	}
+
+	/// Both outputs are Error while any input is
+	fn get_output(&self, port: u32) -> WireValue {
+		if port < 2 && self.inputs.contains(&None) { return WireValue::Error }
+
+		match port {
+			0 => WireValue::Int(self.encode().unwrap_or(0)),
+			1 => WireValue::Bool(self.encode().is_some()),
+			_ => WireValue::Null,
+		}
+	}
+
+	fn is_pure(&self) -> bool { true }
+
+	fn get_label(&self) -> String {
+		if self.inputs.contains(&None) { return "priority error".to_string() }
+
+		match self.encode() {
+			Some(index) => format!("priority {}", index),
+			None => "priority -".to_string(),
+		}
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("inputs", self.inputs.len() as i32, 1, MAX_CHANNELS as i32)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.inputs.resize(v as usize, Some(false));
+		}
+
+		Ok(())
+	}
+}
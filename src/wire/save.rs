@@ -0,0 +1,403 @@
+//! Saved circuits are plain text, one item per line:
+//!
+//! ```text
+//! seed 1234
+//! node 0 Constant value=5
+//! node 1 Output name="sum out"
+//! bus 1:0 conflict
+//! connect 0:0 1:0
+//! ```
+//!
+//! Nodes are recreated from their type name and then have their params
+//! applied in order. Lines starting with `#` are comments, and lines with
+//! any other keyword are handed back to the caller, so the game can store
+//! its own data alongside the circuit.
+
+use wire::*;
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Creates a node with default settings from its type name
+pub fn create_node(type_name: &str) -> Option<Box<WireNode>> {
+	let node: Box<WireNode> = match type_name {
+		"Constant" => box ConstantNode { value: 0 },
+		"BoolConstant" => box BoolConstantNode { value: false },
+		"Output" => box OutputNode::new("output"),
+		"Counter" => box CounterNode::new(),
+		"Add" => box AddNode::new(2),
+		"And" => box AndNode::new(2),
+		"Gate" => box GateNode::new(GateOp::Or, 2),
+		"Not" => box NotNode::new(),
+		"BinaryOp" => box BinaryOpNode::new(BinaryOp::Sub),
+		"UnaryOp" => box UnaryOpNode::new(UnaryOp::Neg),
+		"Compare" => box CompareNode::new(CompareOp::Eq),
+
+		"SrLatch" => box SrLatchNode::new(),
+		"DLatch" => box DLatchNode::new(),
+		"DFlipFlop" => box DFlipFlopNode::new(),
+		"TFlipFlop" => box TFlipFlopNode::new(),
+		"JkFlipFlop" => box JkFlipFlopNode::new(),
+		"Register" => box RegisterNode::new(8),
+		"Rom" => box RomNode::new(8, 8),
+		"Ram" => box RamNode::new(8, 8),
+
+		"Mux" => box MuxNode::new(4),
+		"Demux" => box DemuxNode::new(4),
+		"Decoder" => box DecoderNode::new(4),
+		"Encoder" => box EncoderNode::new(4),
+		"PriorityEncoder" => box PriorityEncoderNode::new(4),
+
+		"Delay" => box DelayNode::new(1),
+		"Pulse" => box PulseNode::new(1),
+		"EdgeDetector" => box EdgeDetectorNode::new(EdgeMode::Rising),
+		"Debounce" => box DebounceNode::new(1),
+		"Watchdog" => box WatchdogNode::new(10),
+		"Random" => box RandomNode::new(RandomOutput::Int { min: 0, max: 9 }, None),
+
+		"Button" => box ButtonNode::new(),
+		"Toggle" => box ToggleNode::new(),
+		"TriState" => box TriStateNode::new(),
+		"Replay" => box ReplayNode::new(),
+		"Stdin" => box StdinNode::new(""),
+		#[cfg(unix)]
+		"Socket" => box SocketNode::new(),
+
+		_ => return None,
+	};
+
+	Some(node)
+}
+
+#[derive(Debug)]
+pub enum CircuitError {
+	Io(io::Error),
+	Parse { line: usize, message: String },
+}
+
+impl From<io::Error> for CircuitError {
+	fn from(e: io::Error) -> Self { CircuitError::Io(e) }
+}
+
+impl fmt::Display for CircuitError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			CircuitError::Io(ref e) => write!(f, "{}", e),
+			CircuitError::Parse { line, ref message } => write!(f, "line {}: {}", line, message),
+		}
+	}
+}
+
+pub struct Circuit {
+	pub context: WireContext,
+
+	/// Tokenised lines the loader didn't recognise, with their line numbers
+	pub extra_lines: Vec<(usize, Vec<String>)>,
+}
+
+fn quote(text: &str) -> String {
+	let mut quoted = String::from("\"");
+
+	for c in text.chars() {
+		if c == '"' || c == '\\' {
+			quoted.push('\\');
+		}
+
+		quoted.push(c);
+	}
+
+	quoted.push('"');
+	quoted
+}
+
+/// Splits on whitespace, treating double quoted sections as part of the
+/// surrounding token. Quotes are removed and `\` escapes the next character
+pub fn tokenize(line: &str) -> Result<Vec<String>, String> {
+	let mut tokens = Vec::new();
+	let mut token = String::new();
+	let mut in_token = false;
+	let mut in_quotes = false;
+	let mut chars = line.chars();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'"' => {
+				in_quotes = !in_quotes;
+				in_token = true;
+			}
+
+			'\\' if in_quotes => match chars.next() {
+				Some(c) => token.push(c),
+				None => return Err("unfinished escape".to_string()),
+			},
+
+			c if c.is_whitespace() && !in_quotes => {
+				if in_token {
+					tokens.push(token.clone());
+					token.clear();
+					in_token = false;
+				}
+			}
+
+			c => {
+				token.push(c);
+				in_token = true;
+			}
+		}
+	}
+
+	if in_quotes {
+		return Err("unterminated string".to_string());
+	}
+
+	if in_token {
+		tokens.push(token);
+	}
+
+	Ok(tokens)
+}
+
+pub fn write_circuit<W: Write>(ctx: &WireContext, out: &mut W) -> io::Result<()> {
+	if let Some(seed) = ctx.get_seed() {
+		writeln!(out, "seed {}", seed)?;
+	}
+
+	for &(id, ref node) in ctx.nodes.iter() {
+		write!(out, "node {} {}", id, node.get_type_name())?;
+
+		for param in node.get_params() {
+			let value = match param.value {
+				ParamValue::String(ref v) => quote(v),
+				_ => param.format_value(),
+			};
+
+			write!(out, " {}={}", param.name, value)?;
+		}
+
+		writeln!(out, "")?;
+	}
+
+	// Buses come first so that loading doesn't drop their extra drivers
+	for bus in ctx.get_buses() {
+		writeln!(out, "bus {}:{} {}", bus.to.0, bus.to.1, bus.resolution.name())?;
+	}
+
+	for c in ctx.connections.iter() {
+		writeln!(out, "connect {}:{} {}:{}", c.input_node, c.input_port, c.output_node, c.output_port)?;
+	}
+
+	Ok(())
+}
+
+pub(super) fn parse_port(token: &str) -> Option<(u32, u32)> {
+	let mut parts = token.splitn(2, ':');
+	let node = parts.next()?.parse().ok()?;
+	let port = parts.next()?.parse().ok()?;
+	Some((node, port))
+}
+
+fn parse_node(ctx: &mut WireContext, tokens: &[String]) -> Result<(), String> {
+	if tokens.len() < 3 {
+		return Err("expected 'node <id> <type> [param=value]...'".to_string());
+	}
+
+	let id: u32 = tokens[1].parse().map_err(|_| format!("invalid node id '{}'", tokens[1]))?;
+	if ctx.get_node(id).is_some() {
+		return Err(format!("node {} defined twice", id));
+	}
+
+	let mut node = create_node(&tokens[2]).ok_or(format!("unknown node type '{}'", tokens[2]))?;
+
+	for token in tokens[3..].iter() {
+		let mut parts = token.splitn(2, '=');
+		let name = parts.next().unwrap();
+		let text = parts.next().ok_or(format!("expected '{}=<value>'", name))?;
+
+		let value = match node.get_params().into_iter().find(|p| p.name == name) {
+			Some(param) => param.parse(text),
+			None => Err(ParamError::Unknown(name.to_string())),
+		};
+
+		value.and_then(|v| node.set_param(name, v))
+			.map_err(|e| format!("{}: {}", name, e))?;
+	}
+
+	ctx.insert_node(id, node);
+	Ok(())
+}
+
+fn parse_connection(ctx: &mut WireContext, tokens: &[String]) -> Result<(), String> {
+	let ports = if tokens.len() == 3 {
+		parse_port(&tokens[1]).and_then(|from| parse_port(&tokens[2]).map(|to| (from, to)))
+	} else {
+		None
+	};
+
+	let (from, to) = ports.ok_or("expected 'connect <node>:<port> <node>:<port>'".to_string())?;
+
+	ctx.check_connection(from, to)?;
+	ctx.add_connection(from, to);
+	Ok(())
+}
+
+fn parse_bus(ctx: &mut WireContext, tokens: &[String]) -> Result<(), String> {
+	let to = if tokens.len() == 3 { parse_port(&tokens[1]) } else { None };
+	let to = to.ok_or("expected 'bus <node>:<port> <resolution>'".to_string())?;
+
+	let resolution = BusResolution::NAMES.iter().position(|&n| n == tokens[2])
+		.and_then(BusResolution::from_index)
+		.ok_or(format!("unknown bus resolution '{}'", tokens[2]))?;
+
+	match ctx.get_node(to.0).map(|n| to.1 < n.get_num_inputs()) {
+		Some(true) => {
+			ctx.set_bus(to, Some(resolution));
+			Ok(())
+		}
+
+		Some(false) => Err(format!("node {} has no input #{}", to.0, to.1)),
+		None => Err(format!("unknown node {}", to.0)),
+	}
+}
+
+pub fn read_circuit(text: &str) -> Result<Circuit, CircuitError> {
+	let mut circuit = Circuit {
+		context: WireContext::new(),
+		extra_lines: Vec::new(),
+	};
+
+	for (line_no, line) in text.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') { continue }
+
+		let error = |message| CircuitError::Parse { line: line_no + 1, message };
+		let tokens = tokenize(line).map_err(&error)?;
+
+		let result = match tokens[0].as_str() {
+			"seed" => match tokens.get(1).and_then(|s| s.parse().ok()) {
+				Some(seed) => { circuit.context.set_seed(seed); Ok(()) }
+				None => Err("expected 'seed <number>'".to_string()),
+			},
+
+			"node" => parse_node(&mut circuit.context, &tokens),
+			"bus" => parse_bus(&mut circuit.context, &tokens),
+			"connect" => parse_connection(&mut circuit.context, &tokens),
+
+			_ => {
+				circuit.extra_lines.push((line_no + 1, tokens));
+				Ok(())
+			}
+		};
+
+		result.map_err(&error)?;
+	}
+
+	Ok(circuit)
+}
+
+pub fn save_circuit(ctx: &WireContext, path: &Path) -> io::Result<()> {
+	let mut file = File::create(path)?;
+	write_circuit(ctx, &mut file)
+}
+
+pub fn load_circuit(path: &Path) -> Result<Circuit, CircuitError> {
+	let mut text = String::new();
+	File::open(path)?.read_to_string(&mut text)?;
+	read_circuit(&text)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn params(node: &WireNode) -> Vec<(&'static str, ParamValue)> {
+		node.get_params().into_iter().map(|p| (p.name, p.value)).collect()
+	}
+
+	fn connections(ctx: &WireContext) -> Vec<((u32, u32), (u32, u32))> {
+		ctx.connections.iter().map(|c| (c.from(), c.to())).collect()
+	}
+
+	#[test]
+	fn round_trip() {
+		let mut ctx = WireContext::new();
+		ctx.set_seed(1234);
+
+		ctx.insert_node(3, box ConstantNode { value: -5 });
+		ctx.insert_node(7, box GateNode::new(GateOp::Nand, 3));
+		ctx.insert_node(10, box OutputNode::new("say \"hi\"  to \\ them"));
+		ctx.insert_node(12, box OutputNode::new(""));
+		ctx.insert_node(20, box MuxNode::new(2));
+		ctx.set_param(20, "channels", ParamValue::Int(3)).unwrap();
+
+		ctx.add_connection((3, 0), (20, 0));
+		ctx.add_connection((7, 0), (20, 3));
+		ctx.add_connection((20, 0), (10, 0));
+		ctx.set_bus((12, 0), Some(BusResolution::WiredOr));
+		ctx.add_connection((7, 0), (12, 0));
+		ctx.add_connection((3, 0), (12, 0));
+
+		let mut text = Vec::new();
+		write_circuit(&ctx, &mut text).unwrap();
+		let text = String::from_utf8(text).unwrap();
+
+		let circuit = read_circuit(&text).unwrap();
+		let loaded = &circuit.context;
+		assert!(circuit.extra_lines.is_empty());
+
+		assert_eq!(loaded.get_seed(), Some(1234));
+
+		let ids: Vec<u32> = loaded.nodes.iter().map(|n| n.0).collect();
+		assert_eq!(ids, vec![3, 7, 10, 12, 20]);
+
+		for &(id, ref node) in ctx.nodes.iter() {
+			let other = loaded.get_node(id).unwrap();
+			assert_eq!(other.get_type_name(), node.get_type_name());
+			assert_eq!(params(other), params(&**node));
+		}
+
+		assert_eq!(params(loaded.get_node(10).unwrap())[0].1, ParamValue::String("say \"hi\"  to \\ them".to_string()));
+		assert_eq!(connections(loaded), connections(&ctx));
+
+		let buses: Vec<_> = loaded.get_buses().iter().map(|b| (b.to, b.resolution)).collect();
+		assert_eq!(buses, vec![((12, 0), BusResolution::WiredOr)]);
+
+		// Saving again gives the same text
+		let mut again = Vec::new();
+		write_circuit(loaded, &mut again).unwrap();
+		assert_eq!(String::from_utf8(again).unwrap(), text);
+	}
+
+	#[test]
+	fn quoting() {
+		for text in ["", "plain", "two  spaces", "\"quoted\"", "back\\slash", "\\\"", " edges "].iter() {
+			assert_eq!(tokenize(&quote(text)).unwrap(), vec![text.to_string()]);
+		}
+
+		assert_eq!(tokenize("  a \"b c\"  d\"e f\"g ").unwrap(), vec!["a", "b c", "de fg"]);
+		assert_eq!(tokenize("name=\"x \\\"y\\\"\"").unwrap(), vec!["name=x \"y\""]);
+		assert_eq!(tokenize("a\\b").unwrap(), vec!["a\\b"]);
+
+		assert_eq!(tokenize("a \"b"), Err("unterminated string".to_string()));
+		assert_eq!(tokenize("a \"b\\"), Err("unfinished escape".to_string()));
+	}
+
+	#[test]
+	fn extra_lines_and_errors() {
+		let circuit = read_circuit("# comment\nnode 1 Constant value=2\nplayer 1.5 \"x y\"\n").unwrap();
+		assert_eq!(circuit.extra_lines, vec![(3, vec!["player".to_string(), "1.5".to_string(), "x y".to_string()])]);
+
+		let line_of = |text: &str| match read_circuit(text) {
+			Err(CircuitError::Parse { line, .. }) => line,
+			_ => panic!("'{}' should fail to load", text),
+		};
+
+		assert_eq!(line_of("node 1 Constant\nnode 1 Constant"), 2);
+		assert_eq!(line_of("node 1 Nonsense"), 1);
+		assert_eq!(line_of("\nnode 1 Constant value=x"), 2);
+		assert_eq!(line_of("node 1 Constant\nconnect 1:0 2:0"), 2);
+		assert_eq!(line_of("node 1 Output name=\"open"), 1);
+	}
+}
@@ -0,0 +1,494 @@
+use wire::*;
+
+/// Tracks a clock input between updates so nodes can act on edges rather
+/// than levels
+#[derive(Copy, Clone, Debug)]
+pub struct EdgeDetector {
+	pub level: bool,
+	pub prev_level: bool,
+}
+
+impl EdgeDetector {
+	pub fn new() -> Self {
+		EdgeDetector {
+			level: false,
+			prev_level: false,
+		}
+	}
+
+	/// Should be called exactly once per update. Returns (rising, falling)
+	pub fn poll(&mut self) -> (bool, bool) {
+		let edges = (self.level && !self.prev_level, !self.level && self.prev_level);
+		self.prev_level = self.level;
+		edges
+	}
+
+	pub fn rising(&mut self) -> bool {
+		self.poll().0
+	}
+}
+
+/// Keeps only the low `width` bits of `value`
+pub fn truncate_to_width(value: i32, width: u32) -> i32 {
+	if width >= 32 {
+		value
+	} else {
+		value & ((1 << width) - 1)
+	}
+}
+
+fn bool_output(port: u32, q: bool) -> WireValue {
+	match port {
+		0 => WireValue::Bool(q),
+		1 => WireValue::Bool(!q),
+		_ => WireValue::Null,
+	}
+}
+
+fn bool_label(q: bool) -> String {
+	if q { "Q=1".to_string() } else { "Q=0".to_string() }
+}
+
+fn q_outputs() -> Vec<PortInfo> {
+	vec![PortInfo::bool("Q", ""), PortInfo::bool("!Q", "The inverse of Q")]
+}
+
+
+/// Level sensitive set/reset latch. Inputs are S, R; outputs are Q, !Q.
+/// Reset wins if both inputs are high
+pub struct SrLatchNode { set: bool, reset: bool, q: bool }
+
+impl SrLatchNode {
+	pub fn new() -> Self {
+		SrLatchNode { set: false, reset: false, q: false }
+	}
+}
+
+impl WireNode for SrLatchNode {
+	fn get_type_name(&self) -> &'static str { "SrLatch" }
+
+	fn get_num_inputs(&self) -> u32 { 2 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Sequential,
+			help: "Set/reset latch. Reset wins if both are high",
+			inputs: vec![
+				PortInfo::bool("S", "Sets Q while high"),
+				PortInfo::bool("R", "Clears Q while high"),
+			],
+			outputs: q_outputs(),
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if let WireValue::Bool(val) = value {
+			match port {
+				0 => self.set = val,
+				1 => self.reset = val,
+				_ => {}
+			}
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		if self.reset {
+			self.q = false;
+		} else if self.set {
+			self.q = true;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue { bool_output(port, self.q) }
+	fn get_label(&self) -> String { bool_label(self.q) }
+
+	fn save_state(&self) -> Vec<i32> { vec![self.q as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [q] = *state { self.q = q != 0 }
+	}
+}
+
+
+/// Transparent latch. Inputs are D, Enable; outputs are Q, !Q
+pub struct DLatchNode { d: bool, enable: bool, q: bool }
+
+impl DLatchNode {
+	pub fn new() -> Self {
+		DLatchNode { d: false, enable: false, q: false }
+	}
+}
+
+impl WireNode for DLatchNode {
+	fn get_type_name(&self) -> &'static str { "DLatch" }
+
+	fn get_num_inputs(&self) -> u32 { 2 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Sequential,
+			help: "Q follows D while Enable is high",
+			inputs: vec![
+				PortInfo::bool("D", ""),
+				PortInfo::bool("Enable", ""),
+			],
+			outputs: q_outputs(),
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if let WireValue::Bool(val) = value {
+			match port {
+				0 => self.d = val,
+				1 => self.enable = val,
+				_ => {}
+			}
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		if self.enable {
+			self.q = self.d;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue { bool_output(port, self.q) }
+	fn get_label(&self) -> String { bool_label(self.q) }
+
+	fn save_state(&self) -> Vec<i32> { vec![self.q as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [q] = *state { self.q = q != 0 }
+	}
+}
+
+
+/// Rising edge triggered D flip-flop. Inputs are D, Clock, Reset; outputs
+/// are Q, !Q. Reset is asynchronous
+pub struct DFlipFlopNode { d: bool, clock: EdgeDetector, reset: bool, q: bool }
+
+impl DFlipFlopNode {
+	pub fn new() -> Self {
+		DFlipFlopNode { d: false, clock: EdgeDetector::new(), reset: false, q: false }
+	}
+}
+
+impl WireNode for DFlipFlopNode {
+	fn get_type_name(&self) -> &'static str { "DFlipFlop" }
+
+	fn get_num_inputs(&self) -> u32 { 3 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Sequential,
+			help: "Stores D on the rising edge of Clock",
+			inputs: vec![
+				PortInfo::bool("D", ""),
+				PortInfo::bool("Clock", ""),
+				PortInfo::bool("Reset", "Clears Q straight away"),
+			],
+			outputs: q_outputs(),
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if let WireValue::Bool(val) = value {
+			match port {
+				0 => self.d = val,
+				1 => self.clock.level = val,
+				2 => self.reset = val,
+				_ => {}
+			}
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		let rising = self.clock.rising();
+
+		if self.reset {
+			self.q = false;
+		} else if rising {
+			self.q = self.d;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue { bool_output(port, self.q) }
+	fn get_label(&self) -> String { bool_label(self.q) }
+
+	fn save_state(&self) -> Vec<i32> { vec![self.q as i32, self.clock.prev_level as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [q, prev_clock] = *state {
+			self.q = q != 0;
+			self.clock.prev_level = prev_clock != 0;
+		}
+	}
+}
+
+
+/// Rising edge triggered toggle flip-flop. Inputs are T, Clock, Reset;
+/// outputs are Q, !Q. Reset is asynchronous
+pub struct TFlipFlopNode { t: bool, clock: EdgeDetector, reset: bool, q: bool }
+
+impl TFlipFlopNode {
+	pub fn new() -> Self {
+		TFlipFlopNode { t: false, clock: EdgeDetector::new(), reset: false, q: false }
+	}
+}
+
+impl WireNode for TFlipFlopNode {
+	fn get_type_name(&self) -> &'static str { "TFlipFlop" }
+
+	fn get_num_inputs(&self) -> u32 { 3 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Sequential,
+			help: "Flips Q on the rising edge of Clock while T is high",
+			inputs: vec![
+				PortInfo::bool("T", ""),
+				PortInfo::bool("Clock", ""),
+				PortInfo::bool("Reset", "Clears Q straight away"),
+			],
+			outputs: q_outputs(),
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if let WireValue::Bool(val) = value {
+			match port {
+				0 => self.t = val,
+				1 => self.clock.level = val,
+				2 => self.reset = val,
+				_ => {}
+			}
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		let rising = self.clock.rising();
+
+		if self.reset {
+			self.q = false;
+		} else if rising && self.t {
+			self.q = !self.q;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue { bool_output(port, self.q) }
+	fn get_label(&self) -> String { bool_label(self.q) }
+
+	fn save_state(&self) -> Vec<i32> { vec![self.q as i32, self.clock.prev_level as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [q, prev_clock] = *state {
+			self.q = q != 0;
+			self.clock.prev_level = prev_clock != 0;
+		}
+	}
+}
+
+
+/// Rising edge triggered JK flip-flop. Inputs are J, K, Clock, Reset;
+/// outputs are Q, !Q. Reset is asynchronous
+pub struct JkFlipFlopNode { j: bool, k: bool, clock: EdgeDetector, reset: bool, q: bool }
+
+impl JkFlipFlopNode {
+	pub fn new() -> Self {
+		JkFlipFlopNode { j: false, k: false, clock: EdgeDetector::new(), reset: false, q: false }
+	}
+}
+
+impl WireNode for JkFlipFlopNode {
+	fn get_type_name(&self) -> &'static str { "JkFlipFlop" }
+
+	fn get_num_inputs(&self) -> u32 { 4 }
+	fn get_num_outputs(&self) -> u32 { 2 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Sequential,
+			help: "On the rising edge of Clock, J sets, K clears and both flip Q",
+			inputs: vec![
+				PortInfo::bool("J", ""),
+				PortInfo::bool("K", ""),
+				PortInfo::bool("Clock", ""),
+				PortInfo::bool("Reset", "Clears Q straight away"),
+			],
+			outputs: q_outputs(),
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if let WireValue::Bool(val) = value {
+			match port {
+				0 => self.j = val,
+				1 => self.k = val,
+				2 => self.clock.level = val,
+				3 => self.reset = val,
+				_ => {}
+			}
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		let rising = self.clock.rising();
+
+		if self.reset {
+			self.q = false;
+		} else if rising {
+			self.q = match (self.j, self.k) {
+				(false, false) => self.q,
+				(true, false) => true,
+				(false, true) => false,
+				(true, true) => !self.q,
+			};
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue { bool_output(port, self.q) }
+	fn get_label(&self) -> String { bool_label(self.q) }
+
+	fn save_state(&self) -> Vec<i32> { vec![self.q as i32, self.clock.prev_level as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [q, prev_clock] = *state {
+			self.q = q != 0;
+			self.clock.prev_level = prev_clock != 0;
+		}
+	}
+}
+
+
+/// Stores an N-bit unsigned integer on the rising edge of its clock while
+/// enabled. Inputs are D, Clock, Enable, Reset; output is Q. Enable is high
+/// until driven otherwise, values wider than the register are truncated, and
+/// reset is asynchronous
+pub struct RegisterNode {
+	width: u32,
+
+	d: i32,
+	clock: EdgeDetector,
+	enable: bool,
+	reset: bool,
+
+	q: i32,
+}
+
+impl RegisterNode {
+	pub fn new(width: u32) -> Self {
+		assert!(width > 0 && width <= 32, "Register width must be between 1 and 32 bits");
+
+		RegisterNode {
+			width,
+
+			d: 0,
+			clock: EdgeDetector::new(),
+			enable: true,
+			reset: false,
+
+			q: 0,
+		}
+	}
+
+	pub fn get_width(&self) -> u32 { self.width }
+}
+
+impl WireNode for RegisterNode {
+	fn get_type_name(&self) -> &'static str { "Register" }
+
+	fn get_num_inputs(&self) -> u32 { 4 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Sequential,
+			help: "Stores an unsigned Int on the rising edge of Clock while enabled",
+			inputs: vec![
+				PortInfo::int("D", "Truncated to the register's width"),
+				PortInfo::bool("Clock", ""),
+				PortInfo::bool("Enable", "High until driven otherwise"),
+				PortInfo::bool("Reset", "Clears Q straight away"),
+			],
+			outputs: vec![PortInfo::int("Q", "")],
+		}
+	}
+
+	fn get_input_default(&self, port: u32) -> WireValue {
+		match port {
+			0 => WireValue::Int(0),
+			2 => WireValue::Bool(true),
+			_ => WireValue::Bool(false),
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		match (port, value) {
+			(0, WireValue::Int(val)) => self.d = val,
+			(1, WireValue::Bool(val)) => self.clock.level = val,
+			(2, WireValue::Bool(val)) => self.enable = val,
+			(3, WireValue::Bool(val)) => self.reset = val,
+			_ => {}
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		let rising = self.clock.rising();
+
+		if self.reset {
+			self.q = 0;
+		} else if rising && self.enable {
+			self.q = truncate_to_width(self.d, self.width);
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 {
+			WireValue::Int(self.q)
+		} else {
+			WireValue::Null
+		}
+	}
+
+	fn get_label(&self) -> String {
+		format!("reg{}={}", self.width, self.q)
+	}
+
+	fn save_state(&self) -> Vec<i32> { vec![self.q, self.clock.prev_level as i32] }
+	fn load_state(&mut self, state: &[i32]) {
+		if let [q, prev_clock] = *state {
+			self.q = q;
+			self.clock.prev_level = prev_clock != 0;
+		}
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("width", self.width as i32, 1, 32)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.width = v as u32;
+			self.q = truncate_to_width(self.q, self.width);
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,146 @@
+use wire::*;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Name of the sink every WireContext starts with, and the one OutputNodes
+/// use by default
+pub const STDOUT_SINK: &str = "stdout";
+
+/// A value a node wants reported, before the context has stamped it
+#[derive(Clone, Debug)]
+pub struct PendingOutput {
+	/// Name of the sink registered on the context to send it to
+	pub sink: String,
+	pub name: String,
+	pub value: WireValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputEvent {
+	/// Number of steps taken before the value arrived
+	pub tick: u64,
+	pub node_id: u32,
+	pub name: String,
+	pub value: WireValue,
+}
+
+/// Somewhere output values end up. Sinks are registered on a WireContext by
+/// name, see WireContext::add_sink
+pub trait OutputSink {
+	fn on_output(&mut self, event: &OutputEvent) -> io::Result<()>;
+}
+
+/// Callbacks can be used as sinks directly
+impl<F: FnMut(&OutputEvent)> OutputSink for F {
+	fn on_output(&mut self, event: &OutputEvent) -> io::Result<()> {
+		self(event);
+		Ok(())
+	}
+}
+
+
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+	fn on_output(&mut self, event: &OutputEvent) -> io::Result<()> {
+		println!("'{}': {:?}", event.name, event.value);
+		Ok(())
+	}
+}
+
+
+/// Writes one line per value, prefixed with the tick it arrived on
+pub struct LogSink<W: Write> { out: W }
+
+impl<W: Write> LogSink<W> {
+	pub fn new(out: W) -> Self {
+		LogSink { out }
+	}
+}
+
+impl LogSink<BufWriter<File>> {
+	pub fn create(path: &Path) -> io::Result<Self> {
+		Ok(LogSink::new(BufWriter::new(File::create(path)?)))
+	}
+}
+
+impl<W: Write> OutputSink for LogSink<W> {
+	fn on_output(&mut self, event: &OutputEvent) -> io::Result<()> {
+		writeln!(self.out, "[{}] '{}': {:?}", event.tick, event.name, event.value)?;
+		self.out.flush()
+	}
+}
+
+
+/// Writes values as `tick,node,name,value` rows. Null values are left empty
+/// and errors are written as `error`
+pub struct CsvSink<W: Write> {
+	out: W,
+	wrote_header: bool,
+}
+
+impl<W: Write> CsvSink<W> {
+	pub fn new(out: W) -> Self {
+		CsvSink { out, wrote_header: false }
+	}
+}
+
+impl CsvSink<BufWriter<File>> {
+	pub fn create(path: &Path) -> io::Result<Self> {
+		Ok(CsvSink::new(BufWriter::new(File::create(path)?)))
+	}
+}
+
+fn csv_field(text: &str) -> String {
+	if text.contains(|c| c == ',' || c == '"' || c == '\n') {
+		format!("\"{}\"", text.replace('"', "\"\""))
+	} else {
+		text.to_string()
+	}
+}
+
+pub fn csv_value(value: WireValue) -> String {
+	match value {
+		WireValue::Int(v) => v.to_string(),
+		WireValue::Bool(v) => v.to_string(),
+		WireValue::Null => String::new(),
+		WireValue::Error => "error".to_string(),
+		WireValue::HighZ => "z".to_string(),
+	}
+}
+
+impl<W: Write> OutputSink for CsvSink<W> {
+	fn on_output(&mut self, event: &OutputEvent) -> io::Result<()> {
+		if !self.wrote_header {
+			writeln!(self.out, "tick,node,name,value")?;
+			self.wrote_header = true;
+		}
+
+		writeln!(self.out, "{},{},{},{}", event.tick, event.node_id,
+			csv_field(&event.name), csv_value(event.value))?;
+
+		self.out.flush()
+	}
+}
+
+
+/// Sends values to a Receiver, for collecting results in process. Clones
+/// send to the same Receiver
+#[derive(Clone)]
+pub struct ChannelSink { sender: Sender<OutputEvent> }
+
+pub fn channel_sink() -> (ChannelSink, Receiver<OutputEvent>) {
+	let (sender, receiver) = mpsc::channel();
+	(ChannelSink { sender }, receiver)
+}
+
+impl OutputSink for ChannelSink {
+	fn on_output(&mut self, event: &OutputEvent) -> io::Result<()> {
+		// Nobody listening isn't an error worth dropping the sink over
+		let _ = self.sender.send(event.clone());
+		Ok(())
+	}
+}
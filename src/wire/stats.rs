@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// How many nodes the report lists
+pub const REPORT_NODES: usize = 5;
+
+#[derive(Copy, Clone, Debug)]
+pub struct NodeStats {
+	pub node_id: u32,
+	pub type_name: &'static str,
+	pub updates: u64,
+	pub time: Duration,
+}
+
+/// What WireContext::step has been up to since stats were enabled, see
+/// WireContext::enable_stats
+#[derive(Clone, Debug)]
+pub struct WireStats {
+	pub steps: u64,
+	pub total_time: Duration,
+	pub last_step_time: Duration,
+	pub max_step_time: Duration,
+
+	/// Calls to WireNode::update
+	pub updates: u64,
+	/// Connections that picked up a new value
+	pub value_changes: u64,
+
+	nodes: HashMap<u32, NodeStats>,
+}
+
+pub fn millis(duration: Duration) -> f64 {
+	duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1.0e6
+}
+
+impl WireStats {
+	pub fn new() -> Self {
+		WireStats {
+			steps: 0,
+			total_time: Duration::new(0, 0),
+			last_step_time: Duration::new(0, 0),
+			max_step_time: Duration::new(0, 0),
+
+			updates: 0,
+			value_changes: 0,
+
+			nodes: HashMap::new(),
+		}
+	}
+
+	pub fn average_step_time(&self) -> Duration {
+		if self.steps == 0 { return Duration::new(0, 0) }
+
+		let nanos = (self.total_time.as_secs() * 1_000_000_000 + self.total_time.subsec_nanos() as u64) / self.steps;
+		Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32)
+	}
+
+	pub fn get_node(&self, node_id: u32) -> Option<&NodeStats> {
+		self.nodes.get(&node_id)
+	}
+
+	/// The `count` nodes that have spent the most time in update, slowest first
+	pub fn hottest(&self, count: usize) -> Vec<NodeStats> {
+		let mut nodes: Vec<_> = self.nodes.values().cloned().collect();
+		nodes.sort_by(|a, b| b.time.cmp(&a.time).then(a.node_id.cmp(&b.node_id)));
+		nodes.truncate(count);
+		nodes
+	}
+
+	pub(super) fn record_update(&mut self, node_id: u32, type_name: &'static str, time: Duration) {
+		self.updates += 1;
+
+		let node = self.nodes.entry(node_id).or_insert(NodeStats {
+			node_id, type_name,
+			updates: 0,
+			time: Duration::new(0, 0),
+		});
+
+		node.updates += 1;
+		node.time += time;
+	}
+
+	pub(super) fn record_step(&mut self, time: Duration, value_changes: usize) {
+		self.steps += 1;
+		self.total_time += time;
+		self.last_step_time = time;
+		self.max_step_time = self.max_step_time.max(time);
+		self.value_changes += value_changes as u64;
+	}
+
+	pub(super) fn remove_node(&mut self, node_id: u32) {
+		self.nodes.remove(&node_id);
+	}
+}
+
+/// A few lines summing up the stats, with the hottest nodes last
+impl fmt::Display for WireStats {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "steps: {}", self.steps)?;
+		writeln!(f, "step time: {:.3}ms avg, {:.3}ms last, {:.3}ms max",
+			millis(self.average_step_time()), millis(self.last_step_time), millis(self.max_step_time))?;
+		writeln!(f, "updates: {}", self.updates)?;
+		write!(f, "value changes: {}", self.value_changes)?;
+
+		for node in self.hottest(REPORT_NODES) {
+			write!(f, "\n  node {} {}: {:.3}ms in {} updates",
+				node.node_id, node.type_name, millis(node.time), node.updates)?;
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,502 @@
+use wire::*;
+
+use std::collections::VecDeque;
+
+/// Stays active for a fixed number of updates after being triggered.
+/// Triggering while active restarts the pulse
+#[derive(Copy, Clone, Debug)]
+pub struct PulseTimer {
+	pub width: u32,
+	pub remaining: u32,
+}
+
+impl PulseTimer {
+	pub fn new(width: u32) -> Self {
+		PulseTimer {
+			width,
+			remaining: 0,
+		}
+	}
+
+	pub fn trigger(&mut self) {
+		self.remaining = self.width;
+	}
+
+	/// Should be called once per update, after any triggers
+	pub fn tick(&mut self) {
+		if self.remaining > 0 {
+			self.remaining -= 1;
+		}
+	}
+
+	pub fn is_active(&self) -> bool {
+		self.remaining > 0
+	}
+}
+
+
+/// Outputs its input as it was N ticks ago. Starts out outputting Null
+pub struct DelayNode {
+	input: WireValue,
+	output: WireValue,
+	history: VecDeque<WireValue>,
+}
+
+impl DelayNode {
+	pub fn new(ticks: u32) -> Self {
+		DelayNode {
+			input: WireValue::Null,
+			output: WireValue::Null,
+			history: vec![WireValue::Null; ticks as usize].into_iter().collect(),
+		}
+	}
+
+	pub fn get_delay(&self) -> u32 { self.history.len() as u32 }
+}
+
+impl WireNode for DelayNode {
+	fn get_type_name(&self) -> &'static str { "Delay" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Timing,
+			help: "Outputs its input as it was N ticks ago",
+			inputs: vec![PortInfo::any("in", "")],
+			outputs: vec![PortInfo::any("out", "Null until N ticks have passed")],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port == 0 {
+			self.input = value;
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		self.history.push_back(self.input);
+		self.output = self.history.pop_front().unwrap();
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { self.output } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		format!("delay {}", self.get_delay())
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		self.history.iter().chain(Some(&self.output))
+			.flat_map(|v| v.to_state().to_vec())
+			.collect()
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if state.len() != (self.history.len() + 1) * 2 { return }
+
+		let mut values = state.chunks(2).map(WireValue::from_state).collect::<VecDeque<_>>();
+		self.output = values.pop_back().unwrap();
+		self.history = values;
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("ticks", self.get_delay() as i32, 0, 1000)]
+	}
+
+	/// Changing the delay forgets the history
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.history = vec![WireValue::Null; v as usize].into_iter().collect();
+		}
+
+		Ok(())
+	}
+}
+
+
+/// Monostable. A rising edge on the input holds the output high for a fixed
+/// number of ticks
+pub struct PulseNode {
+	trigger: EdgeDetector,
+	pulse: PulseTimer,
+}
+
+impl PulseNode {
+	pub fn new(width: u32) -> Self {
+		PulseNode {
+			trigger: EdgeDetector::new(),
+			pulse: PulseTimer::new(width),
+		}
+	}
+}
+
+impl WireNode for PulseNode {
+	fn get_type_name(&self) -> &'static str { "Pulse" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Timing,
+			help: "Holds its output high for N ticks after a rising edge on its input",
+			inputs: vec![PortInfo::bool("trigger", "")],
+			outputs: vec![PortInfo::bool("out", "")],
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port != 0 { return }
+		if let Some(val) = value.to_bool() {
+			self.trigger.level = val;
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		self.pulse.tick();
+
+		if self.trigger.rising() {
+			self.pulse.trigger();
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { WireValue::Bool(self.pulse.is_active()) } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		format!("pulse {}/{}", self.pulse.remaining, self.pulse.width)
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		vec![self.pulse.remaining as i32, self.trigger.prev_level as i32]
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if let [remaining, prev_trigger] = *state {
+			self.pulse.remaining = remaining as u32;
+			self.trigger.prev_level = prev_trigger != 0;
+		}
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("width", self.pulse.width as i32, 1, 1000)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.pulse.width = v as u32;
+			self.pulse.remaining = self.pulse.remaining.min(self.pulse.width);
+		}
+
+		Ok(())
+	}
+}
+
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EdgeMode {
+	Rising, Falling, Any,
+}
+
+param_enum!(EdgeMode {
+	Rising => "rising", Falling => "falling", Any => "any",
+});
+
+/// Outputs true for a single tick when its input changes in the chosen
+/// direction
+pub struct EdgeDetectorNode {
+	pub mode: EdgeMode,
+	input: EdgeDetector,
+	detected: bool,
+}
+
+impl EdgeDetectorNode {
+	pub fn new(mode: EdgeMode) -> Self {
+		EdgeDetectorNode {
+			mode,
+			input: EdgeDetector::new(),
+			detected: false,
+		}
+	}
+}
+
+impl WireNode for EdgeDetectorNode {
+	fn get_type_name(&self) -> &'static str { "EdgeDetector" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Timing,
+			help: "True for a single tick when its input changes in the chosen direction",
+			inputs: vec![PortInfo::bool("in", "")],
+			outputs: vec![PortInfo::bool("edge", "")],
+		}
+	}
+
+	fn get_input_default(&self, _port: u32) -> WireValue { WireValue::Bool(false) }
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port != 0 { return }
+		if let Some(val) = value.to_bool() {
+			self.input.level = val;
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		let (rising, falling) = self.input.poll();
+
+		self.detected = match self.mode {
+			EdgeMode::Rising => rising,
+			EdgeMode::Falling => falling,
+			EdgeMode::Any => rising || falling,
+		};
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { WireValue::Bool(self.detected) } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		match self.mode {
+			EdgeMode::Rising => "rising".to_string(),
+			EdgeMode::Falling => "falling".to_string(),
+			EdgeMode::Any => "edge".to_string(),
+		}
+	}
+
+	fn save_state(&self) -> Vec<i32> {
+		vec![self.detected as i32, self.input.prev_level as i32]
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if let [detected, prev_input] = *state {
+			self.detected = detected != 0;
+			self.input.prev_level = prev_input != 0;
+		}
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::enumeration("mode", self.mode.to_index(), EdgeMode::NAMES)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Enum(v) = value {
+			self.mode = EdgeMode::from_index(v).unwrap();
+		}
+
+		Ok(())
+	}
+}
+
+
+/// Only passes on a new input once it has held steady for N ticks
+pub struct DebounceNode {
+	pub ticks: u32,
+
+	input: WireValue,
+	stable_ticks: u32,
+	output: WireValue,
+}
+
+impl DebounceNode {
+	pub fn new(ticks: u32) -> Self {
+		DebounceNode {
+			ticks,
+
+			input: WireValue::Null,
+			stable_ticks: 0,
+			output: WireValue::Null,
+		}
+	}
+}
+
+impl WireNode for DebounceNode {
+	fn get_type_name(&self) -> &'static str { "Debounce" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Timing,
+			help: "Only passes on a new input once it has held steady for N ticks",
+			inputs: vec![PortInfo::any("in", "")],
+			outputs: vec![PortInfo::any("out", "")],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port != 0 || value == self.input { return }
+
+		self.input = value;
+		self.stable_ticks = 0;
+	}
+
+	fn update(&mut self, _tick: u64) {
+		if self.input == self.output { return }
+
+		self.stable_ticks += 1;
+		if self.stable_ticks >= self.ticks {
+			self.output = self.input;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { self.output } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		format!("debounce {}", self.ticks)
+	}
+
+	// The input is included so that redelivering it on restore doesn't
+	// restart the count
+	fn save_state(&self) -> Vec<i32> {
+		let mut state = vec![self.stable_ticks as i32];
+		state.extend_from_slice(&self.input.to_state());
+		state.extend_from_slice(&self.output.to_state());
+		state
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if state.len() != 5 { return }
+
+		self.stable_ticks = state[0] as u32;
+		self.input = WireValue::from_state(&state[1..3]);
+		self.output = WireValue::from_state(&state[3..5]);
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("ticks", self.ticks as i32, 0, 1000)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.ticks = v as u32;
+		}
+
+		Ok(())
+	}
+}
+
+
+/// Raises its output when its input hasn't changed for N ticks. Any change
+/// of the input restarts the countdown and clears the output
+pub struct WatchdogNode {
+	pub timeout: u32,
+
+	input: WireValue,
+	kicked: bool,
+	elapsed: u32,
+}
+
+impl WatchdogNode {
+	pub fn new(timeout: u32) -> Self {
+		WatchdogNode {
+			timeout,
+
+			input: WireValue::Null,
+			kicked: false,
+			elapsed: 0,
+		}
+	}
+
+	fn expired(&self) -> bool {
+		self.elapsed >= self.timeout
+	}
+}
+
+impl WireNode for WatchdogNode {
+	fn get_type_name(&self) -> &'static str { "Watchdog" }
+
+	fn get_num_inputs(&self) -> u32 { 1 }
+	fn get_num_outputs(&self) -> u32 { 1 }
+
+	fn get_info(&self) -> NodeInfo {
+		NodeInfo {
+			type_name: self.get_type_name(),
+			category: Category::Timing,
+			help: "Raises its output when its input hasn't changed for N ticks",
+			inputs: vec![PortInfo::any("in", "Any change restarts the countdown")],
+			outputs: vec![PortInfo::bool("timeout", "")],
+		}
+	}
+
+	fn on_input_changed(&mut self, port: u32, value: WireValue) {
+		if port == 0 && value != self.input {
+			self.input = value;
+			self.kicked = true;
+		}
+	}
+
+	fn update(&mut self, _tick: u64) {
+		if self.kicked {
+			self.kicked = false;
+			self.elapsed = 0;
+		} else if !self.expired() {
+			self.elapsed += 1;
+		}
+	}
+
+	fn get_output(&self, port: u32) -> WireValue {
+		if port == 0 { WireValue::Bool(self.expired()) } else { WireValue::Null }
+	}
+
+	fn get_label(&self) -> String {
+		if self.expired() {
+			"timeout".to_string()
+		} else {
+			format!("{}/{}", self.elapsed, self.timeout)
+		}
+	}
+
+	// The input is included so that redelivering it on restore doesn't
+	// count as a kick
+	fn save_state(&self) -> Vec<i32> {
+		let mut state = vec![self.elapsed as i32, self.kicked as i32];
+		state.extend_from_slice(&self.input.to_state());
+		state
+	}
+
+	fn load_state(&mut self, state: &[i32]) {
+		if state.len() != 4 { return }
+
+		self.elapsed = state[0] as u32;
+		self.kicked = state[1] != 0;
+		self.input = WireValue::from_state(&state[2..4]);
+	}
+
+	fn get_params(&self) -> Vec<Param> {
+		vec![Param::int("timeout", self.timeout as i32, 1, 10000)]
+	}
+
+	fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+		validate_param(&self.get_params(), name, &value)?;
+
+		if let ParamValue::Int(v) = value {
+			self.timeout = v as u32;
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,781 @@
+//! Structural Verilog export. The circuit becomes one module clocked by
+//! `clk`, with each rising edge standing for one tick, and a synchronous
+//! `rst` that puts every node back in its starting state. Buttons and
+//! Toggles become input ports, and Output nodes output ports named after
+//! them.
+//!
+//! Bools are 1 bit wide and Ints 32 bit signed. Nodes that update every
+//! tick, like latches and flip-flops, become registers, while the rest are
+//! combinational, so the tick each connection takes in the game is not
+//! reproduced. Clock inputs are sampled and their edges found the way the
+//! game does, rather than being used as clocks. There's no Error value in
+//! hardware, so results the game would make Error are 0, with the node's
+//! error output raised where it has one.
+//!
+//! Only wrapping overflow can be exported. Nodes that talk to the outside
+//! world, buses, loops with no register in them, and nodes the exporter
+//! doesn't know are errors
+
+use wire::*;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerilogError {
+	pub node: u32,
+	pub message: String,
+}
+
+impl fmt::Display for VerilogError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "node {}: {}", self.node, self.message)
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum NetType { Bool, Int }
+
+impl NetType {
+	fn declare(&self) -> &'static str {
+		match *self {
+			NetType::Bool => "wire",
+			NetType::Int => "wire signed [31:0]",
+		}
+	}
+
+	fn zero(&self) -> &'static str {
+		match *self {
+			NetType::Bool => "1'b0",
+			NetType::Int => "32'sd0",
+		}
+	}
+}
+
+fn net(port: (u32, u32)) -> String {
+	format!("n{}_{}", port.0, port.1)
+}
+
+fn int_literal(value: i32) -> String {
+	if value < 0 {
+		format!("-32'sd{}", -(value as i64))
+	} else {
+		format!("32'sd{}", value)
+	}
+}
+
+fn literal(value: WireValue, ty: NetType) -> String {
+	match value {
+		WireValue::Int(v) => int_literal(v),
+		WireValue::Bool(v) => if v { "1'b1".to_string() } else { "1'b0".to_string() },
+		_ => ty.zero().to_string(),
+	}
+}
+
+/// Makes `name` usable as a Verilog identifier
+fn identifier(name: &str) -> String {
+	let mut id: String = name.chars()
+		.map(|c| if (c.is_alphanumeric() && c.is_ascii()) || c == '_' { c } else { '_' })
+		.collect();
+
+	if id.chars().next().map_or(true, |c| c.is_digit(10)) {
+		id.insert(0, '_');
+	}
+
+	id
+}
+
+fn param(node: &WireNode, name: &str) -> Option<ParamValue> {
+	node.get_params().into_iter().find(|p| p.name == name).map(|p| p.value)
+}
+
+fn int_param(node: &WireNode, name: &str) -> i32 {
+	match param(node, name) {
+		Some(ParamValue::Int(v)) => v,
+		_ => 0,
+	}
+}
+
+fn enum_param(node: &WireNode, name: &str) -> &'static str {
+	node.get_params().into_iter()
+		.find(|p| p.name == name)
+		.and_then(|p| match (p.ty, p.value) {
+			(ParamType::Enum(names), ParamValue::Enum(v)) => names.get(v).cloned(),
+			_ => None,
+		})
+		.unwrap_or("")
+}
+
+/// The type of an output port, if it can be known from the node alone
+fn fixed_output_type(type_name: &str, port: u32) -> Option<NetType> {
+	match (type_name, port) {
+		("Constant", _) | ("Register", _) => Some(NetType::Int),
+		("Counter", 0) | ("Add", 0) | ("BinaryOp", 0) | ("UnaryOp", 0) => Some(NetType::Int),
+		("Counter", _) | ("Add", _) | ("BinaryOp", _) | ("UnaryOp", _) => Some(NetType::Bool),
+		("Mux", _) | ("Delay", _) => None,
+		_ => Some(NetType::Bool),
+	}
+}
+
+/// Whether a node's outputs come from registers, which breaks loops
+fn is_registered(node: &WireNode) -> bool {
+	match node.get_type_name() {
+		"Counter" | "SrLatch" | "DLatch" | "DFlipFlop" | "TFlipFlop" | "JkFlipFlop" |
+		"Register" | "EdgeDetector" | "Pulse" => true,
+		"Delay" => int_param(node, "ticks") > 0,
+		_ => false,
+	}
+}
+
+/// Finds a loop of connections through no registers, which would be a
+/// combinational loop in hardware. Returns the nodes around it in order
+fn find_combinational_loop(ctx: &WireContext) -> Option<Vec<u32>> {
+	let combinational: HashSet<u32> = ctx.nodes.iter()
+		.filter(|&&(_, ref node)| !is_registered(&**node))
+		.map(|&(id, _)| id)
+		.collect();
+
+	let mut next: HashMap<u32, Vec<u32>> = HashMap::new();
+	for c in ctx.connections.iter() {
+		if combinational.contains(&c.input_node) && combinational.contains(&c.output_node) {
+			next.entry(c.input_node).or_insert_with(Vec::new).push(c.output_node);
+		}
+	}
+
+	// Nodes on the path being walked are false, finished ones true
+	let mut visited: HashMap<u32, bool> = HashMap::new();
+
+	for &(start, _) in ctx.nodes.iter() {
+		if visited.contains_key(&start) { continue }
+
+		// Each node on the path and the index of the next edge to follow
+		let mut path = vec![(start, 0)];
+		visited.insert(start, false);
+
+		while let Some(&(node, edge)) = path.last() {
+			path.last_mut().unwrap().1 += 1;
+
+			let to = match next.get(&node).and_then(|n| n.get(edge)) {
+				Some(&to) => to,
+				None => {
+					visited.insert(node, true);
+					path.pop();
+					continue
+				}
+			};
+
+			match visited.get(&to).cloned() {
+				Some(false) => {
+					let first = path.iter().position(|&(id, _)| id == to).unwrap();
+					return Some(path[first..].iter().map(|&(id, _)| id).collect());
+				}
+
+				Some(true) => {}
+
+				None => {
+					visited.insert(to, false);
+					path.push((to, 0));
+				}
+			}
+		}
+	}
+
+	None
+}
+
+struct Exporter<'a> {
+	ctx: &'a WireContext,
+	types: HashMap<(u32, u32), NetType>,
+
+	ports: Vec<String>,
+	decls: Vec<String>,
+	body: Vec<String>,
+}
+
+impl<'a> Exporter<'a> {
+	fn driver(&self, to: (u32, u32)) -> Option<(u32, u32)> {
+		self.ctx.connections.iter().find(|c| c.to() == to).map(|c| c.from())
+	}
+
+	/// The error output of the node driving input `port` of `node_id`,
+	/// which is raised where the game would deliver Error to it
+	fn input_error(&self, node_id: u32, port: u32) -> Option<String> {
+		let from = self.driver((node_id, port))?;
+
+		match self.ctx.get_node(from.0)?.get_type_name() {
+			"BinaryOp" | "UnaryOp" if from.1 == 0 => Some(net((from.0, 1))),
+			_ => None,
+		}
+	}
+
+	/// Works out the type of every output, with Muxes and Delays taking the
+	/// type of what drives them, and Int where nothing does
+	fn infer_types(&mut self) {
+		let ctx = self.ctx;
+
+		for &(id, ref node) in ctx.nodes.iter() {
+			for port in 0..node.get_num_outputs() {
+				if let Some(ty) = fixed_output_type(node.get_type_name(), port) {
+					self.types.insert((id, port), ty);
+				}
+			}
+		}
+
+		loop {
+			let mut changed = false;
+
+			for &(id, ref node) in ctx.nodes.iter() {
+				if self.types.contains_key(&(id, 0)) || node.get_num_outputs() == 0 { continue }
+
+				let data_inputs = match node.get_type_name() {
+					"Mux" => node.get_num_inputs() - 1,
+					_ => 1,
+				};
+
+				let ty = (0..data_inputs)
+					.filter_map(|port| self.driver((id, port)))
+					.filter_map(|from| self.types.get(&from).cloned())
+					.next();
+
+				if let Some(ty) = ty {
+					self.types.insert((id, 0), ty);
+					changed = true;
+				}
+			}
+
+			if !changed { break }
+		}
+
+		for &(id, ref node) in ctx.nodes.iter() {
+			if node.get_num_outputs() > 0 {
+				self.types.entry((id, 0)).or_insert(NetType::Int);
+			}
+		}
+	}
+
+	/// Declares a net for input `port` of `node_id` and returns its name
+	fn input(&mut self, node_id: u32, port: u32, ty: NetType) -> Result<String, VerilogError> {
+		let name = format!("n{}_in{}", node_id, port);
+
+		let value = match self.driver((node_id, port)) {
+			Some(from) => {
+				if self.types.get(&from) != Some(&ty) {
+					return Err(VerilogError {
+						node: node_id,
+						message: format!("input #{} is driven by {}:{}, which has the wrong type", port, from.0, from.1),
+					});
+				}
+
+				net(from)
+			}
+
+			None => {
+				let node = self.ctx.get_node(node_id).unwrap();
+				literal(node.get_input_default(port), ty)
+			}
+		};
+
+		self.decls.push(format!("{} {} = {};", ty.declare(), name, value));
+		Ok(name)
+	}
+
+	fn assign(&mut self, port: (u32, u32), value: &str) {
+		self.body.push(format!("assign {} = {};", net(port), value));
+	}
+
+	fn reg(&mut self, ty: NetType, name: &str) {
+		let decl = match ty {
+			NetType::Bool => "reg",
+			NetType::Int => "reg signed [31:0]",
+		};
+
+		self.decls.push(format!("{} {};", decl, name));
+	}
+
+	/// Adds registers updated on each clock, set to their reset values on
+	/// `rst`. `update` is the body of the non-reset branch
+	fn clocked(&mut self, regs: &[(&str, &str)], update: &[String]) {
+		self.body.push("always @(posedge clk) begin".to_string());
+		self.body.push("\tif (rst) begin".to_string());
+		for &(name, reset) in regs {
+			self.body.push(format!("\t\t{} <= {};", name, reset));
+		}
+		self.body.push("\tend else begin".to_string());
+		for line in update {
+			self.body.push(format!("\t\t{}", line));
+		}
+		self.body.push("\tend".to_string());
+		self.body.push("end".to_string());
+	}
+
+	/// A flip-flop style node: Q and !Q outputs from a one bit register
+	fn bool_register(&mut self, id: u32, update: &[String]) {
+		let q = format!("n{}_q", id);
+		self.reg(NetType::Bool, &q);
+		self.clocked(&[(q.as_str(), "1'b0")], update);
+		self.assign((id, 0), &q);
+		self.assign((id, 1), &format!("~{}", q));
+	}
+
+	fn edge_register(&mut self, id: u32, clock: &str) -> String {
+		let prev = format!("n{}_prev", id);
+		self.reg(NetType::Bool, &prev);
+		self.clocked(&[(prev.as_str(), "1'b0")], &[format!("{} <= {};", prev, clock)]);
+		prev
+	}
+
+	fn check_wrapping(&self, id: u32, node: &WireNode) -> Result<(), VerilogError> {
+		match enum_param(node, "overflow") {
+			"" | "wrapping" => Ok(()),
+			mode => Err(VerilogError {
+				node: id,
+				message: format!("{} overflow can't be exported, only wrapping", mode),
+			}),
+		}
+	}
+
+	fn write_node(&mut self, id: u32, node: &WireNode) -> Result<(), VerilogError> {
+		let type_name = node.get_type_name();
+		self.body.push(String::new());
+		self.body.push(format!("// {} {}", id, type_name));
+
+		let q = format!("n{}_q", id);
+
+		match type_name {
+			"Constant" => {
+				let value = int_literal(int_param(node, "value"));
+				self.assign((id, 0), &value);
+			}
+
+			"BoolConstant" => {
+				let value = match param(node, "value") {
+					Some(ParamValue::Bool(true)) => "1'b1",
+					_ => "1'b0",
+				};
+
+				self.assign((id, 0), value);
+			}
+
+			"Counter" => {
+				self.check_wrapping(id, node)?;
+				self.reg(NetType::Int, &q);
+				self.clocked(&[(q.as_str(), "32'sd0")], &[format!("{} <= {} + 32'sd1;", q, q)]);
+				self.assign((id, 0), &q);
+				self.assign((id, 1), "1'b0");
+			}
+
+			"Add" => {
+				self.check_wrapping(id, node)?;
+
+				let mut terms = Vec::new();
+				for port in 0..node.get_num_inputs() {
+					terms.push(self.input(id, port, NetType::Int)?);
+				}
+
+				self.assign((id, 0), &terms.join(" + "));
+				self.assign((id, 1), "1'b0");
+			}
+
+			"And" => {
+				let mut terms = Vec::new();
+				for port in 0..node.get_num_inputs() {
+					terms.push(self.input(id, port, NetType::Bool)?);
+				}
+
+				self.assign((id, 0), &terms.join(" & "));
+			}
+
+			"Gate" => {
+				let mut terms = Vec::new();
+				for port in 0..node.get_num_inputs() {
+					terms.push(self.input(id, port, NetType::Bool)?);
+				}
+
+				let value = match enum_param(node, "op") {
+					"or" => terms.join(" | "),
+					"xor" => terms.join(" ^ "),
+					"nand" => format!("~({})", terms.join(" & ")),
+					"nor" => format!("~({})", terms.join(" | ")),
+					"xnor" => format!("~({})", terms.join(" ^ ")),
+					op => return Err(VerilogError { node: id, message: format!("unknown op '{}'", op) }),
+				};
+
+				self.assign((id, 0), &value);
+			}
+
+			"Not" => {
+				let input = self.input(id, 0, NetType::Bool)?;
+				self.assign((id, 0), &format!("~{}", input));
+			}
+
+			"BinaryOp" => {
+				self.check_wrapping(id, node)?;
+				let a = self.input(id, 0, NetType::Int)?;
+				let b = self.input(id, 1, NetType::Int)?;
+
+				let (value, error) = match enum_param(node, "op") {
+					"sub" => (format!("{} - {}", a, b), "1'b0".to_string()),
+					"mul" => (format!("{} * {}", a, b), "1'b0".to_string()),
+					"div" => (format!("({} == 32'sd0) ? 32'sd0 : {} / {}", b, a, b), format!("{} == 32'sd0", b)),
+					"mod" => (format!("({} == 32'sd0) ? 32'sd0 : {} % {}", b, a, b), format!("{} == 32'sd0", b)),
+					"min" => (format!("({} < {}) ? {} : {}", a, b, a, b), "1'b0".to_string()),
+					"max" => (format!("({} > {}) ? {} : {}", a, b, a, b), "1'b0".to_string()),
+					"shl" => (format!("{} << {}[4:0]", a, b), "1'b0".to_string()),
+					"shr" => (format!("{} >>> {}[4:0]", a, b), "1'b0".to_string()),
+					op => return Err(VerilogError { node: id, message: format!("unknown op '{}'", op) }),
+				};
+
+				self.assign((id, 0), &value);
+				self.assign((id, 1), &error);
+			}
+
+			"UnaryOp" => {
+				self.check_wrapping(id, node)?;
+				let a = self.input(id, 0, NetType::Int)?;
+
+				let value = match enum_param(node, "op") {
+					"neg" => format!("-{}", a),
+					"abs" => format!("{}[31] ? -{} : {}", a, a, a),
+					op => return Err(VerilogError { node: id, message: format!("unknown op '{}'", op) }),
+				};
+
+				self.assign((id, 0), &value);
+				self.assign((id, 1), "1'b0");
+			}
+
+			"Compare" => {
+				let a = self.input(id, 0, NetType::Int)?;
+				let b = self.input(id, 1, NetType::Int)?;
+
+				let op = match enum_param(node, "op") {
+					"eq" => "==", "ne" => "!=",
+					"lt" => "<", "le" => "<=",
+					"gt" => ">", "ge" => ">=",
+					op => return Err(VerilogError { node: id, message: format!("unknown op '{}'", op) }),
+				};
+
+				self.assign((id, 0), &format!("{} {} {}", a, op, b));
+			}
+
+			"SrLatch" => {
+				let s = self.input(id, 0, NetType::Bool)?;
+				let r = self.input(id, 1, NetType::Bool)?;
+
+				self.bool_register(id, &[
+					format!("if ({}) {} <= 1'b0;", r, q),
+					format!("else if ({}) {} <= 1'b1;", s, q),
+				]);
+			}
+
+			"DLatch" => {
+				let d = self.input(id, 0, NetType::Bool)?;
+				let enable = self.input(id, 1, NetType::Bool)?;
+
+				self.bool_register(id, &[format!("if ({}) {} <= {};", enable, q, d)]);
+			}
+
+			"DFlipFlop" | "TFlipFlop" => {
+				let data = self.input(id, 0, NetType::Bool)?;
+				let clock = self.input(id, 1, NetType::Bool)?;
+				let reset = self.input(id, 2, NetType::Bool)?;
+				let prev = self.edge_register(id, &clock);
+
+				let next = if type_name == "DFlipFlop" { data } else { format!("{} ^ {}", q, data) };
+
+				self.bool_register(id, &[
+					format!("if ({}) {} <= 1'b0;", reset, q),
+					format!("else if ({} && !{}) {} <= {};", clock, prev, q, next),
+				]);
+			}
+
+			"JkFlipFlop" => {
+				let j = self.input(id, 0, NetType::Bool)?;
+				let k = self.input(id, 1, NetType::Bool)?;
+				let clock = self.input(id, 2, NetType::Bool)?;
+				let reset = self.input(id, 3, NetType::Bool)?;
+				let prev = self.edge_register(id, &clock);
+
+				self.bool_register(id, &[
+					format!("if ({}) {} <= 1'b0;", reset, q),
+					format!("else if ({} && !{}) {} <= ({} & ~{}) | (~{} & {});", clock, prev, q, j, q, k, q),
+				]);
+			}
+
+			"Register" => {
+				let d = self.input(id, 0, NetType::Int)?;
+				let clock = self.input(id, 1, NetType::Bool)?;
+				let enable = self.input(id, 2, NetType::Bool)?;
+				let reset = self.input(id, 3, NetType::Bool)?;
+				let prev = self.edge_register(id, &clock);
+
+				let width = int_param(node, "width") as u32;
+				let value = if width >= 32 {
+					d
+				} else {
+					format!("{} & {}", d, int_literal(truncate_to_width(-1, width)))
+				};
+
+				self.reg(NetType::Int, &q);
+				self.clocked(&[(q.as_str(), "32'sd0")], &[
+					format!("if ({}) {} <= 32'sd0;", reset, q),
+					format!("else if ({} && !{} && {}) {} <= {};", clock, prev, enable, q, value),
+				]);
+				self.assign((id, 0), &q);
+			}
+
+			"Mux" => {
+				let ty = self.types[&(id, 0)];
+				let channels = node.get_num_inputs() - 1;
+
+				let mut inputs = Vec::new();
+				for port in 0..channels {
+					inputs.push(self.input(id, port, ty)?);
+				}
+
+				let select = self.input(id, channels, NetType::Int)?;
+
+				let mut value = ty.zero().to_string();
+				for (i, input) in inputs.iter().enumerate().rev() {
+					value = format!("({} == {}) ? {} : {}", select, int_literal(i as i32), input, value);
+				}
+
+				// The game's Mux is Error, so 0, while Select is
+				if let Some(error) = self.input_error(id, channels) {
+					value = format!("{} ? {} : {}", error, ty.zero(), value);
+				}
+
+				self.assign((id, 0), &value);
+			}
+
+			"Delay" => {
+				let ty = self.types[&(id, 0)];
+				let input = self.input(id, 0, ty)?;
+				let ticks = int_param(node, "ticks");
+
+				let mut previous = input;
+				let mut regs = Vec::new();
+				let mut update = Vec::new();
+
+				for i in 0..ticks {
+					let name = format!("n{}_d{}", id, i);
+					self.reg(ty, &name);
+					update.push(format!("{} <= {};", name, previous));
+					regs.push(name.clone());
+					previous = name;
+				}
+
+				if !regs.is_empty() {
+					let resets: Vec<_> = regs.iter().map(|r| (r.as_str(), ty.zero())).collect();
+					self.clocked(&resets, &update);
+				}
+
+				self.assign((id, 0), &previous);
+			}
+
+			"EdgeDetector" => {
+				let input = self.input(id, 0, NetType::Bool)?;
+				let prev = self.edge_register(id, &input);
+
+				let edge = match enum_param(node, "mode") {
+					"rising" => format!("{} && !{}", input, prev),
+					"falling" => format!("!{} && {}", input, prev),
+					_ => format!("{} != {}", input, prev),
+				};
+
+				self.reg(NetType::Bool, &q);
+				self.clocked(&[(q.as_str(), "1'b0")], &[format!("{} <= {};", q, edge)]);
+				self.assign((id, 0), &q);
+			}
+
+			"Pulse" => {
+				let input = self.input(id, 0, NetType::Bool)?;
+				let prev = self.edge_register(id, &input);
+				let width = int_literal(int_param(node, "width"));
+				let remaining = format!("n{}_remaining", id);
+
+				self.reg(NetType::Int, &remaining);
+				self.clocked(&[(remaining.as_str(), "32'sd0")], &[
+					format!("if ({} && !{}) {} <= {};", input, prev, remaining, width),
+					format!("else if ({} != 32'sd0) {} <= {} - 32'sd1;", remaining, remaining, remaining),
+				]);
+				self.assign((id, 0), &format!("{} != 32'sd0", remaining));
+			}
+
+			"Button" | "Toggle" => {
+				let port = format!("{}_{}", type_name.to_lowercase(), id);
+				self.ports.push(format!("input wire {}", port));
+				self.assign((id, 0), &port);
+			}
+
+			"Output" => {
+				let driver = self.driver((id, 0));
+				let ty = driver.and_then(|from| self.types.get(&from).cloned()).unwrap_or(NetType::Bool);
+
+				let name = match param(node, "name") {
+					Some(ParamValue::String(ref name)) if !name.is_empty() => identifier(name),
+					_ => format!("output_{}", id),
+				};
+
+				let taken = self.ports.iter().any(|p| p.split_whitespace().last() == Some(name.as_str()));
+				let name = if taken { format!("{}_{}", name, id) } else { name };
+
+				let decl = match ty {
+					NetType::Bool => "output wire",
+					NetType::Int => "output wire signed [31:0]",
+				};
+
+				self.ports.push(format!("{} {}", decl, name));
+				let value = driver.map_or(ty.zero().to_string(), net);
+				self.body.push(format!("assign {} = {};", name, value));
+			}
+
+			"Random" | "Replay" | "Stdin" | "Socket" => return Err(VerilogError {
+				node: id,
+				message: format!("{} has no hardware equivalent", type_name),
+			}),
+
+			_ => return Err(VerilogError {
+				node: id,
+				message: format!("{} can't be exported to Verilog", type_name),
+			}),
+		}
+
+		Ok(())
+	}
+}
+
+/// Converts the circuit to a Verilog module named `module`
+pub fn to_verilog(ctx: &WireContext, module: &str) -> Result<String, VerilogError> {
+	if let Some(bus) = ctx.get_buses().first() {
+		return Err(VerilogError {
+			node: bus.to.0,
+			message: format!("input #{} is a bus, which can't be exported", bus.to.1),
+		});
+	}
+
+	if let Some(nodes) = find_combinational_loop(ctx) {
+		let names: Vec<_> = nodes.iter().map(|id| id.to_string()).collect();
+
+		return Err(VerilogError {
+			node: nodes[0],
+			message: format!("combinational loop through nodes {}, which needs a register to export", names.join(", ")),
+		});
+	}
+
+	let mut exporter = Exporter {
+		ctx,
+		types: HashMap::new(),
+
+		ports: vec!["input wire clk".to_string(), "input wire rst".to_string()],
+		decls: Vec::new(),
+		body: Vec::new(),
+	};
+
+	exporter.infer_types();
+
+	for &(id, ref node) in ctx.nodes.iter() {
+		for port in 0..node.get_num_outputs() {
+			let ty = exporter.types.get(&(id, port)).cloned().unwrap_or(NetType::Bool);
+			exporter.decls.push(format!("{} {};", ty.declare(), net((id, port))));
+		}
+	}
+
+	for &(id, ref node) in ctx.nodes.iter() {
+		exporter.write_node(id, &**node)?;
+	}
+
+	let mut text = format!("module {} (\n", identifier(module));
+	text += &exporter.ports.iter().map(|p| format!("\t{}", p)).collect::<Vec<_>>().join(",\n");
+	text += "\n);\n";
+
+	for decl in exporter.decls.iter() {
+		text += &format!("\t{}\n", decl);
+	}
+
+	for line in exporter.body.iter() {
+		if line.is_empty() {
+			text += "\n";
+		} else {
+			text += &format!("\t{}\n", line);
+		}
+	}
+
+	text += "endmodule\n";
+	Ok(text)
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Exports a fixture circuit and compares it with the Verilog expected
+	fn check_fixture(module: &str, circuit: &str, expected: &str) {
+		let ctx = read_circuit(circuit).unwrap().context;
+		assert_eq!(to_verilog(&ctx, module).unwrap(), expected);
+	}
+
+	#[test]
+	fn half_adder() {
+		check_fixture("half_adder",
+			include_str!("../../tests/fixtures/verilog/half_adder.wire"),
+			include_str!("../../tests/fixtures/verilog/half_adder.v"));
+	}
+
+	#[test]
+	fn dff_chain() {
+		check_fixture("dff_chain",
+			include_str!("../../tests/fixtures/verilog/dff_chain.wire"),
+			include_str!("../../tests/fixtures/verilog/dff_chain.v"));
+	}
+
+	#[test]
+	fn mux() {
+		check_fixture("mux",
+			include_str!("../../tests/fixtures/verilog/mux.wire"),
+			include_str!("../../tests/fixtures/verilog/mux.v"));
+	}
+
+	#[test]
+	fn mux_select_error() {
+		let mut ctx = WireContext::new();
+		let a = ctx.add_node(ConstantNode { value: 3 });
+		let zero = ctx.add_node(ConstantNode { value: 0 });
+		let div = ctx.add_node(BinaryOpNode::new(BinaryOp::Div));
+		let mux = ctx.add_node(MuxNode::new(2));
+		ctx.add_connection((a, 0), (div, 0));
+		ctx.add_connection((zero, 0), (div, 1));
+		ctx.add_connection((a, 0), (mux, 0));
+		ctx.add_connection((div, 0), (mux, 2));
+
+		let verilog = to_verilog(&ctx, "mux_error").unwrap();
+		assert!(verilog.contains(&format!("assign n{}_0 = n{}_1 ? 32'sd0 : ", mux, div)), "{}", verilog);
+	}
+
+	#[test]
+	fn combinational_loop() {
+		let mut ctx = WireContext::new();
+		let toggle = ctx.add_node(ToggleNode::new());
+		let xor = ctx.add_node(GateNode::new(GateOp::Xor, 2));
+		let not = ctx.add_node(NotNode::new());
+		ctx.add_connection((toggle, 0), (xor, 0));
+		ctx.add_connection((not, 0), (xor, 1));
+		ctx.add_connection((xor, 0), (not, 0));
+
+		let error = to_verilog(&ctx, "loop").unwrap_err();
+		assert_eq!(error.node, xor);
+	}
+
+	#[test]
+	fn loop_through_register() {
+		let mut ctx = WireContext::new();
+		let clock = ctx.add_node(ToggleNode::new());
+		let flip_flop = ctx.add_node(DFlipFlopNode::new());
+		let not = ctx.add_node(NotNode::new());
+		ctx.add_connection((flip_flop, 0), (not, 0));
+		ctx.add_connection((not, 0), (flip_flop, 0));
+		ctx.add_connection((clock, 0), (flip_flop, 1));
+
+		assert!(to_verilog(&ctx, "toggle").is_ok());
+	}
+}
@@ -0,0 +1,97 @@
+module dff_chain (
+	input wire clk,
+	input wire rst,
+	input wire toggle_0,
+	input wire button_1,
+	output wire q
+);
+	wire n0_0;
+	wire n1_0;
+	wire n2_0;
+	wire n2_1;
+	wire n3_0;
+	wire n3_1;
+	wire n4_0;
+	wire n4_1;
+	wire n2_in0 = n0_0;
+	wire n2_in1 = n1_0;
+	wire n2_in2 = 1'b0;
+	reg n2_prev;
+	reg n2_q;
+	wire n3_in0 = n2_0;
+	wire n3_in1 = n1_0;
+	wire n3_in2 = 1'b0;
+	reg n3_prev;
+	reg n3_q;
+	wire n4_in0 = n3_0;
+	wire n4_in1 = n1_0;
+	wire n4_in2 = 1'b0;
+	reg n4_prev;
+	reg n4_q;
+
+	// 0 Toggle
+	assign n0_0 = toggle_0;
+
+	// 1 Button
+	assign n1_0 = button_1;
+
+	// 2 DFlipFlop
+	always @(posedge clk) begin
+		if (rst) begin
+			n2_prev <= 1'b0;
+		end else begin
+			n2_prev <= n2_in1;
+		end
+	end
+	always @(posedge clk) begin
+		if (rst) begin
+			n2_q <= 1'b0;
+		end else begin
+			if (n2_in2) n2_q <= 1'b0;
+			else if (n2_in1 && !n2_prev) n2_q <= n2_in0;
+		end
+	end
+	assign n2_0 = n2_q;
+	assign n2_1 = ~n2_q;
+
+	// 3 DFlipFlop
+	always @(posedge clk) begin
+		if (rst) begin
+			n3_prev <= 1'b0;
+		end else begin
+			n3_prev <= n3_in1;
+		end
+	end
+	always @(posedge clk) begin
+		if (rst) begin
+			n3_q <= 1'b0;
+		end else begin
+			if (n3_in2) n3_q <= 1'b0;
+			else if (n3_in1 && !n3_prev) n3_q <= n3_in0;
+		end
+	end
+	assign n3_0 = n3_q;
+	assign n3_1 = ~n3_q;
+
+	// 4 DFlipFlop
+	always @(posedge clk) begin
+		if (rst) begin
+			n4_prev <= 1'b0;
+		end else begin
+			n4_prev <= n4_in1;
+		end
+	end
+	always @(posedge clk) begin
+		if (rst) begin
+			n4_q <= 1'b0;
+		end else begin
+			if (n4_in2) n4_q <= 1'b0;
+			else if (n4_in1 && !n4_prev) n4_q <= n4_in0;
+		end
+	end
+	assign n4_0 = n4_q;
+	assign n4_1 = ~n4_q;
+
+	// 5 Output
+	assign q = n4_0;
+endmodule
@@ -0,0 +1,35 @@
+module half_adder (
+	input wire clk,
+	input wire rst,
+	input wire toggle_0,
+	input wire toggle_1,
+	output wire sum,
+	output wire carry
+);
+	wire n0_0;
+	wire n1_0;
+	wire n2_0;
+	wire n3_0;
+	wire n2_in0 = n0_0;
+	wire n2_in1 = n1_0;
+	wire n3_in0 = n0_0;
+	wire n3_in1 = n1_0;
+
+	// 0 Toggle
+	assign n0_0 = toggle_0;
+
+	// 1 Toggle
+	assign n1_0 = toggle_1;
+
+	// 2 Gate
+	assign n2_0 = n2_in0 ^ n2_in1;
+
+	// 3 And
+	assign n3_0 = n3_in0 & n3_in1;
+
+	// 4 Output
+	assign sum = n2_0;
+
+	// 5 Output
+	assign carry = n3_0;
+endmodule
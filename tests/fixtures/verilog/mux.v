@@ -0,0 +1,38 @@
+module mux (
+	input wire clk,
+	input wire rst,
+	output wire signed [31:0] out
+);
+	wire signed [31:0] n0_0;
+	wire signed [31:0] n1_0;
+	wire signed [31:0] n2_0;
+	wire n2_1;
+	wire signed [31:0] n3_0;
+	reg signed [31:0] n2_q;
+	wire signed [31:0] n3_in0 = n0_0;
+	wire signed [31:0] n3_in1 = n1_0;
+	wire signed [31:0] n3_in2 = n2_0;
+
+	// 0 Constant
+	assign n0_0 = 32'sd3;
+
+	// 1 Constant
+	assign n1_0 = -32'sd5;
+
+	// 2 Counter
+	always @(posedge clk) begin
+		if (rst) begin
+			n2_q <= 32'sd0;
+		end else begin
+			n2_q <= n2_q + 32'sd1;
+		end
+	end
+	assign n2_0 = n2_q;
+	assign n2_1 = 1'b0;
+
+	// 3 Mux
+	assign n3_0 = (n3_in2 == 32'sd0) ? n3_in0 : (n3_in2 == 32'sd1) ? n3_in1 : 32'sd0;
+
+	// 4 Output
+	assign out = n3_0;
+endmodule